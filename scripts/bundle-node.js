@@ -5,13 +5,15 @@ import { join, dirname } from 'path';
 import { fileURLToPath } from 'url';
 
 const __dirname = dirname(fileURLToPath(import.meta.url));
-const dest = join(__dirname, '..', 'src-tauri', 'node.exe');
+// Must match NODE_BINARY in src-tauri/src/lib.rs and the bundle resources
+const binary = process.platform === 'win32' ? 'node.exe' : 'node';
+const dest = join(__dirname, '..', 'src-tauri', binary);
 
-// process.execPath is the path to the currently running node.exe
+// process.execPath is the path to the currently running node binary
 const src = process.execPath;
 
 const sizeMB = (statSync(src).size / 1024 / 1024).toFixed(1);
-console.log(`Bundling Node.js runtime: ${src} (${sizeMB} MB) → src-tauri/node.exe`);
+console.log(`Bundling Node.js runtime: ${src} (${sizeMB} MB) → src-tauri/${binary}`);
 
 copyFileSync(src, dest);
 
@@ -2,7 +2,8 @@
  * P2P Sidecar — libp2p node with direct WebRTC messaging
  *
  * Communicates with the Tauri frontend via:
- *   stdin  <- JSON-line commands  (send, dial)
+ *   stdin  <- JSON-line commands  (send, dial, status, diagnose, regenerate_identity, shutdown,
+ *                                  file_offer, file_chunk, file_complete, file_abort)
  *   stdout -> JSON-line events    (ready, message, peer:connect, error, ...)
  *   stderr -> debug log
  *
@@ -19,7 +20,9 @@
  *   No message data ever flows through the relay.
  *
  * Environment:
- *   CONCORD_DATA_DIR — app data directory for persistent identity
+ *   CONCORD_DATA_DIR        — app data directory for persistent identity
+ *   CONCORD_LISTEN_PORT     — fixed TCP listen port (otherwise saved or random)
+ *   CONCORD_BOOTSTRAP_PEERS — comma-separated multiaddrs dialled after start
  */
 import { createServer } from 'net';
 import https from 'https';
@@ -37,17 +40,26 @@ import { webRTC } from '@libp2p/webrtc';
 import { mdns } from '@libp2p/mdns';
 import { createLibp2p } from 'libp2p';
 import { toString, fromString } from 'uint8arrays';
-import { generateKeyPair, privateKeyFromProtobuf, privateKeyToProtobuf } from '@libp2p/crypto/keys';
+import { generateKeyPair, privateKeyFromProtobuf, privateKeyToProtobuf, publicKeyFromProtobuf, publicKeyToProtobuf } from '@libp2p/crypto/keys';
+import { peerIdFromPrivateKey, peerIdFromPublicKey } from '@libp2p/peer-id';
 import { multiaddr } from '@multiformats/multiaddr';
 
 const __dirname = dirname(fileURLToPath(import.meta.url));
 const CONFIG_PATH = join(__dirname, 'relay-config.json');
 const CHAT_PROTOCOL = '/concord/chat/1.0.0';
 const DEFAULT_CHANNEL = 'general';
+// Reserved channel for signed "I have a new identity" notices
+const IDENTITY_NOTICE_CHANNEL = '__identity__';
 
 const DATA_DIR = process.env.CONCORD_DATA_DIR || join(__dirname, '..');
 const IDENTITY_PATH = join(DATA_DIR, 'node-identity.json');
 const IS_INCOGNITO = process.env.CONCORD_INCOGNITO === '1';
+// Launch options from the bridge's config.json
+const LISTEN_PORT = Number(process.env.CONCORD_LISTEN_PORT) || null;
+const BOOTSTRAP_PEERS = (process.env.CONCORD_BOOTSTRAP_PEERS || '')
+  .split(',')
+  .map(a => a.trim())
+  .filter(Boolean);
 
 // ── Relay configuration ─────────────────────────────────────────
 const RELAY_HTTP_URL = 'https://concord-relay.fly.dev:8080';
@@ -131,11 +143,7 @@ async function loadOrCreateIdentity(portConflict) {
 
   const key = await generateKeyPair('Ed25519');
   try {
-    mkdirSync(dirname(IDENTITY_PATH), { recursive: true });
-    writeFileSync(IDENTITY_PATH, JSON.stringify({
-      privateKey: Buffer.from(privateKeyToProtobuf(key)).toString('base64'),
-      createdAt: new Date().toISOString(),
-    }, null, 2));
+    saveIdentity(key);
     log(`Saved new identity to ${IDENTITY_PATH}`);
   } catch (e) {
     log(`Warning: could not save identity: ${e.message}`);
@@ -143,6 +151,36 @@ async function loadOrCreateIdentity(portConflict) {
   return { privateKey: key, isNew: true, isEphemeral: false };
 }
 
+function saveIdentity(key) {
+  mkdirSync(dirname(IDENTITY_PATH), { recursive: true });
+  writeFileSync(IDENTITY_PATH, JSON.stringify({
+    privateKey: Buffer.from(privateKeyToProtobuf(key)).toString('base64'),
+    createdAt: new Date().toISOString(),
+  }, null, 2));
+}
+
+/**
+ * Verify an identity notice: it must be signed by the key of the peer that
+ * sent it, and name that peer as the old identity.
+ */
+async function verifyIdentityNotice(msg, remotePeer) {
+  try {
+    const notice = JSON.parse(msg.notice);
+    const publicKey = publicKeyFromProtobuf(Buffer.from(msg.publicKey, 'base64'));
+    const signer = peerIdFromPublicKey(publicKey).toString();
+    const valid = await publicKey.verify(fromString(msg.notice), Buffer.from(msg.signature, 'base64'));
+    return {
+      oldPeerId: notice.oldPeerId,
+      newPeerId: notice.newPeerId,
+      timestamp: notice.timestamp,
+      verified: Boolean(valid) && signer === remotePeer && notice.oldPeerId === remotePeer,
+    };
+  } catch (e) {
+    log(`identity notice: verification error: ${e.message}`);
+    return null;
+  }
+}
+
 // ── Port management ──────────────────────────────────────────────
 
 function getAvailablePort() {
@@ -165,6 +203,8 @@ function isPortAvailable(port) {
 }
 
 async function loadOrCreatePort() {
+  // A configured port is used as is; a conflict fails the start
+  if (LISTEN_PORT) return { port: LISTEN_PORT, conflict: false, fixed: true };
   try {
     const data = JSON.parse(readFileSync(CONFIG_PATH, 'utf-8'));
     if (typeof data.port === 'number' && data.port > 0) {
@@ -284,6 +324,72 @@ async function sendToAllPeers(node, payload, relayId) {
   }
 }
 
+/**
+ * Per-conversation send queues. Stdin lines are handled concurrently, so
+ * without this a later message could finish dialing first and overtake an
+ * earlier one in the same conversation.
+ */
+const sendQueues = new Map();
+
+function inConversationOrder(conversation, task) {
+  const previous = sendQueues.get(conversation) ?? Promise.resolve();
+  const next = previous.then(task, task);
+  sendQueues.set(conversation, next);
+  const cleanup = () => {
+    if (sendQueues.get(conversation) === next) sendQueues.delete(conversation);
+  };
+  next.then(cleanup, cleanup);
+  return next;
+}
+
+/** Incoming file transfer payloads, by kind, and the event each becomes. */
+const FILE_EVENTS = {
+  'file-offer': 'file_offer',
+  'file-chunk': 'file_chunk',
+  'file-complete': 'file_complete',
+  'file-abort': 'file_abort',
+};
+
+/** Forward a parsed incoming payload to the frontend. */
+async function deliverIncoming(msg, remotePeer) {
+  if (FILE_EVENTS[msg.kind] && typeof msg.transferId === 'string') {
+    emit({
+      type: FILE_EVENTS[msg.kind],
+      from: remotePeer,
+      transferId: msg.transferId,
+      channelId: msg.channelId || DEFAULT_CHANNEL,
+      direct: msg.direct === true,
+      name: msg.name,
+      size: msg.size,
+      chunks: msg.chunks,
+      index: msg.index,
+      data: msg.data,
+      checksum: msg.checksum,
+    });
+    return;
+  }
+  if (msg.channelId === IDENTITY_NOTICE_CHANNEL && msg.kind === 'identity-notice') {
+    const notice = await verifyIdentityNotice(msg, remotePeer);
+    if (notice) {
+      log(`identity notice from ${remotePeer.slice(0, 16)}: verified=${notice.verified}`);
+      emit({ type: 'identity_notice', from: remotePeer, ...notice });
+    }
+    return;
+  }
+  emit({
+    type: 'message',
+    id: typeof msg.id === 'string' ? msg.id : undefined,
+    channelId: msg.channelId || DEFAULT_CHANNEL,
+    data: msg.data,
+    from: remotePeer,
+    seq: Number.isSafeInteger(msg.seq) ? msg.seq : undefined,
+    direct: msg.direct === true,
+    // Compression is the bridge's business; pass it through untouched
+    encoding: typeof msg.encoding === 'string' ? msg.encoding : undefined,
+    caps: Array.isArray(msg.caps) ? msg.caps : undefined,
+  });
+}
+
 /**
  * Register the incoming chat protocol handler.
  * Reads newline-delimited JSON from the stream.
@@ -316,12 +422,7 @@ function registerChatHandler(node) {
               const msg = JSON.parse(line);
               stats.recv++;
               log(`recv: msg from=${remoteShort} ch=${msg.channelId} (total recv: ${stats.recv})`);
-              emit({
-                type: 'message',
-                channelId: msg.channelId || DEFAULT_CHANNEL,
-                data: msg.data,
-                from: remotePeer,
-              });
+              await deliverIncoming(msg, remotePeer);
             } catch (e) {
               stats.recvFail++;
               log(`recv: parse error: ${e.message} | raw: ${line.slice(0, 100)}`);
@@ -334,12 +435,7 @@ function registerChatHandler(node) {
           try {
             const msg = JSON.parse(buffer);
             stats.recv++;
-            emit({
-              type: 'message',
-              channelId: msg.channelId || DEFAULT_CHANNEL,
-              data: msg.data,
-              from: remotePeer,
-            });
+            await deliverIncoming(msg, remotePeer);
           } catch { /* incomplete data */ }
         }
       } catch (e) {
@@ -357,7 +453,7 @@ function registerChatHandler(node) {
 let node;
 
 try {
-  const { port, conflict } = await loadOrCreatePort();
+  const { port, conflict, fixed } = await loadOrCreatePort();
   const { privateKey, isNew, isEphemeral } = await loadOrCreateIdentity(conflict);
 
   // Fetch relay info before creating the node so we can include
@@ -372,7 +468,7 @@ try {
       err?.message?.includes('EADDRINUSE') ||
       err?.code === 'ERR_NO_VALID_ADDRESSES' ||
       err?.constructor?.name === 'UnsupportedListenAddressesError';
-    if (addrInUse) {
+    if (addrInUse && !fixed) {
       log(`Port ${port} in use, retrying...`);
       try { unlinkSync(CONFIG_PATH); } catch { /* ok */ }
       const newPort = await getAvailablePort();
@@ -440,6 +536,14 @@ try {
     setTimeout(registerInviteCode, 3000);
   }
 
+  for (const addr of BOOTSTRAP_PEERS) {
+    // multiaddr() throws on a malformed address; keep that in the chain
+    Promise.resolve()
+      .then(() => node.dial(multiaddr(addr)))
+      .then(() => log(`Bootstrap peer connected: ${addr}`))
+      .catch(e => log(`Bootstrap dial to ${addr} failed: ${e.message}`));
+  }
+
   /** Return connected chat peers (excluding the relay). */
   function chatPeers() {
     return node.getPeers()
@@ -521,32 +625,78 @@ try {
     inviteCode,
   });
 
+  /**
+   * Send a payload to a channel, or only to `targetPeerId` (a DM), after
+   * everything queued before it for the same conversation.
+   */
+  function sendInConversation(channelId, targetPeerId, payload) {
+    const conversation = targetPeerId ? `${channelId}\u001f${targetPeerId}` : channelId;
+    return inConversationOrder(conversation, async () => {
+      if (targetPeerId) {
+        // Point-to-point DM: send only to the specified peer
+        log(`send: targeted send to ${targetPeerId.slice(0, 16)}`);
+        const targetPeer = node.getPeers().find(p => p.toString() === targetPeerId);
+        if (targetPeer) {
+          await sendToPeer(node, targetPeer, payload);
+        } else {
+          log(`send: target ${targetPeerId.slice(0, 16)} not connected — cannot deliver`);
+          stats.sendFail++;
+        }
+      } else {
+        // Broadcast to all connected peers
+        await sendToAllPeers(node, payload, relayPeerId);
+      }
+    });
+  }
+
   // ── Stdin commands ─────────────────────────────────────────────
   const rl = createInterface({ input: process.stdin });
 
   rl.on('line', async (line) => {
     try {
       const cmd = JSON.parse(line);
+      // Replies echo the bridge's requestId so it can match them to the
+      // waiting command; they are ordinary events otherwise.
+      const reply = (event) => emit({ ...event, requestId: cmd.requestId });
 
       switch (cmd.cmd) {
         case 'send': {
           const channelId = cmd.channelId || DEFAULT_CHANNEL;
-          const payload = JSON.stringify({ channelId, data: cmd.data });
-
-          if (cmd.targetPeerId) {
-            // Point-to-point DM: send only to the specified peer
-            log(`send: targeted send to ${cmd.targetPeerId.slice(0, 16)}`);
-            const targetPeer = node.getPeers().find(p => p.toString() === cmd.targetPeerId);
-            if (targetPeer) {
-              await sendToPeer(node, targetPeer, payload);
-            } else {
-              log(`send: target ${cmd.targetPeerId.slice(0, 16)} not connected — cannot deliver`);
-              stats.sendFail++;
-            }
-          } else {
-            // Broadcast to all connected peers
-            await sendToAllPeers(node, payload, relayPeerId);
-          }
+          const direct = Boolean(cmd.targetPeerId);
+          // id, seq (per conversation), encoding and caps come from the bridge
+          const payload = JSON.stringify({
+            id: cmd.id,
+            channelId,
+            data: cmd.data,
+            seq: cmd.seq,
+            direct,
+            encoding: cmd.encoding,
+            caps: cmd.caps,
+          });
+          await sendInConversation(channelId, cmd.targetPeerId, payload);
+          break;
+        }
+
+        case 'file_offer':
+        case 'file_chunk':
+        case 'file_complete':
+        case 'file_abort': {
+          // File transfers travel like messages, in order with them; the
+          // bridge reads the file, chunks it and computes the checksum
+          const channelId = cmd.channelId || DEFAULT_CHANNEL;
+          const payload = JSON.stringify({
+            kind: cmd.cmd.replace('_', '-'),
+            transferId: cmd.transferId,
+            channelId,
+            direct: Boolean(cmd.targetPeerId),
+            name: cmd.name,
+            size: cmd.size,
+            chunks: cmd.chunks,
+            index: cmd.index,
+            data: cmd.data,
+            checksum: cmd.checksum,
+          });
+          await sendInConversation(channelId, cmd.targetPeerId, payload);
           break;
         }
 
@@ -559,7 +709,7 @@ try {
             try {
               const lookup = await fetchJson(`${RELAY_HTTP_URL}/lookup?code=${encodeURIComponent(addr)}`);
               if (!lookup.circuitAddr) {
-                emit({ type: 'dial_result', ok: false, address: addr, error: 'Code not found or expired' });
+                reply({ type: 'dial_result', ok: false, address: addr, error: 'Code not found or expired' });
                 break;
               }
               log(`Resolved code ${addr} -> peerId=${lookup.peerId}`);
@@ -609,18 +759,18 @@ try {
 
                   if (!connected) {
                     log(`WebRTC did not establish for ${addr} after retries`);
-                    emit({ type: 'dial_result', ok: false, address: addr, error: 'Connection timed out — peer may be offline or NAT is too restrictive.' });
+                    reply({ type: 'dial_result', ok: false, address: addr, error: 'Connection timed out — peer may be offline or NAT is too restrictive.' });
                     break;
                   }
                 } else {
                   // Genuine dial failure (not signaling EOF)
                   log(`Dial failed for ${addr}: ${dialErr.message}`);
-                  emit({ type: 'dial_result', ok: false, address: addr, error: `Connection failed: ${dialErr.message}` });
+                  reply({ type: 'dial_result', ok: false, address: addr, error: `Connection failed: ${dialErr.message}` });
                   break;
                 }
               }
 
-              emit({
+              reply({
                 type: 'dial_result',
                 ok: true,
                 address: addr,
@@ -630,30 +780,30 @@ try {
             } catch (e) {
               const msg = e instanceof Error ? e.message : String(e);
               log(`Invite code dial failed: ${msg}`);
-              emit({ type: 'dial_result', ok: false, address: addr, error: msg });
+              reply({ type: 'dial_result', ok: false, address: addr, error: msg });
             }
             break;
           }
 
           // Regular multiaddr dial
           if (!addr || !addr.startsWith('/')) {
-            emit({ type: 'dial_result', ok: false, address: addr, error: 'Invalid address — use an invite code (XXXX-XXXX) or a multiaddr starting with /' });
+            reply({ type: 'dial_result', ok: false, address: addr, error: 'Invalid address — use an invite code (XXXX-XXXX) or a multiaddr starting with /' });
             break;
           }
           try {
             await node.dial(multiaddr(addr));
             log(`Dialed: ${addr.slice(0, 60)}...`);
-            emit({ type: 'dial_result', ok: true, address: addr, peers: node.getPeers().map(String) });
+            reply({ type: 'dial_result', ok: true, address: addr, peers: node.getPeers().map(String) });
           } catch (e) {
             const msg = e instanceof Error ? e.message : String(e);
             log(`Dial failed: ${msg}`);
-            emit({ type: 'dial_result', ok: false, address: addr, error: msg });
+            reply({ type: 'dial_result', ok: false, address: addr, error: msg });
           }
           break;
         }
 
         case 'status': {
-          emit({
+          reply({
             type: 'status',
             peerId,
             address: localAddr,
@@ -664,8 +814,76 @@ try {
           break;
         }
 
+        case 'regenerate_identity': {
+          // Write a fresh keypair to disk; the bridge restarts us to bring it
+          // live. The optional notice is signed with the OLD key so contacts
+          // can verify the handover came from us.
+          if (isEphemeral) {
+            reply({ type: 'identity_regenerated', ok: false, error: 'Ephemeral identities cannot be regenerated' });
+            break;
+          }
+          try {
+            const newKey = await generateKeyPair('Ed25519');
+            const newPeerId = peerIdFromPrivateKey(newKey).toString();
+            let notified = 0;
+            if (cmd.notify) {
+              const notice = JSON.stringify({ oldPeerId: peerId, newPeerId, timestamp: Date.now() });
+              const signature = await privateKey.sign(fromString(notice));
+              const payload = JSON.stringify({
+                channelId: IDENTITY_NOTICE_CHANNEL,
+                kind: 'identity-notice',
+                notice,
+                signature: Buffer.from(signature).toString('base64'),
+                publicKey: Buffer.from(publicKeyToProtobuf(privateKey.publicKey)).toString('base64'),
+              });
+              notified = chatPeers().length;
+              await sendToAllPeers(node, payload, relayPeerId);
+            }
+            saveIdentity(newKey);
+            log(`Identity regenerated: ${peerId.slice(0, 16)} -> ${newPeerId.slice(0, 16)}`);
+            reply({ type: 'identity_regenerated', ok: true, oldPeerId: peerId, newPeerId, notified });
+          } catch (e) {
+            const msg = e instanceof Error ? e.message : String(e);
+            reply({ type: 'identity_regenerated', ok: false, error: msg });
+          }
+          break;
+        }
+
+        case 'diagnose': {
+          // Relay round-trip for the connectivity check: are we connected to
+          // the relay, do we hold a reservation, and does our invite code
+          // resolve back to us?
+          const result = {
+            type: 'diagnose_result',
+            listenAddrs: node.getMultiaddrs().map(String),
+            relayPeerId,
+            relayConnected: !!relayPeerId && node.getPeers().some(p => p.toString() === relayPeerId),
+            relayReservation: node.getMultiaddrs().some(ma => ma.toString().includes('p2p-circuit')),
+            inviteCode,
+            inviteCodeResolves: null,
+            peers: chatPeers().length,
+          };
+          if (inviteCode) {
+            try {
+              const lookup = await fetchJson(`${RELAY_HTTP_URL}/lookup?code=${encodeURIComponent(inviteCode)}`);
+              result.inviteCodeResolves = lookup.peerId === peerId;
+            } catch (e) {
+              result.lookupError = e instanceof Error ? e.message : String(e);
+            }
+          }
+          reply(result);
+          break;
+        }
+
+        case 'shutdown': {
+          // The bridge kills us if this takes longer than its grace period
+          await shutdown('command');
+          break;
+        }
+
         default:
           log(`Unknown command: ${cmd.cmd}`);
+          if (cmd.requestId) reply({ type: 'error', message: `Unknown command: ${cmd.cmd}` });
       }
     } catch (e) {
       log(`Bad stdin: ${e.message}`);
@@ -683,7 +901,12 @@ try {
 
 // ── Shutdown ─────────────────────────────────────────────────────
 
+let shuttingDown = false;
+
 async function shutdown(signal) {
+  // The shutdown command is followed by stdin closing; stop only once
+  if (shuttingDown) return;
+  shuttingDown = true;
   log(`Shutting down (${signal})`);
   try { if (node) await node.stop(); } catch { /* best-effort */ }
   process.exit(0);
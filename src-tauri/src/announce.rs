@@ -0,0 +1,345 @@
+// Screen-reader announcements — with `accessibility.announcements` on, the
+// bridge speaks new messages and connection changes as UI Automation
+// notifications, which Narrator and NVDA pick up even while the window is
+// unfocused or the webview (and its ARIA live regions) is still loading.
+// Bursts are rate limited; whatever is held back is summarised in one
+// announcement when the window regains focus.
+
+use std::collections::{HashSet, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde_json::Value;
+use tauri::Manager;
+
+use super::settings::AnnouncementVerbosity;
+
+const MAIN_WINDOW: &str = "main";
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+/// Announce even while focused until the webview has had time to mount.
+const STARTUP_GRACE: Duration = Duration::from_secs(10);
+const MAX_CONTENT_CHARS: usize = 140;
+const PEER_ID_CHARS: usize = 8;
+
+static FOCUSED: AtomicBool = AtomicBool::new(true);
+static LAUNCHED_AT: Mutex<Option<Instant>> = Mutex::new(None);
+static LIMITER: Mutex<Option<Limiter>> = Mutex::new(None);
+
+#[derive(Default)]
+struct Limiter {
+    spoken: VecDeque<Instant>,
+    held_messages: u32,
+    held_conversations: HashSet<String>,
+    held_connection_changes: u32,
+}
+
+impl Limiter {
+    fn allow(&mut self, max_per_minute: u32) -> bool {
+        let now = Instant::now();
+        while self
+            .spoken
+            .front()
+            .is_some_and(|t| now.duration_since(*t) >= RATE_WINDOW)
+        {
+            self.spoken.pop_front();
+        }
+        if self.spoken.len() >= max_per_minute as usize {
+            return false;
+        }
+        self.spoken.push_back(now);
+        true
+    }
+
+    fn hold(&mut self, event: &Value) {
+        if event["type"].as_str() == Some("message") {
+            self.held_messages += 1;
+            let channel = event["channelId"].as_str().unwrap_or_default();
+            self.held_conversations.insert(channel.to_string());
+        } else {
+            self.held_connection_changes += 1;
+        }
+    }
+
+    fn take_summary(&mut self) -> Option<String> {
+        let mut parts = Vec::new();
+        match (self.held_messages, self.held_conversations.len()) {
+            (0, _) => {}
+            (1, _) => parts.push("1 message".to_string()),
+            (n, 1) => parts.push(format!("{} messages", n)),
+            (n, c) => parts.push(format!("{} messages in {} conversations", n, c)),
+        }
+        match self.held_connection_changes {
+            0 => {}
+            1 => parts.push("1 connection change".to_string()),
+            n => parts.push(format!("{} connection changes", n)),
+        }
+        self.held_messages = 0;
+        self.held_conversations.clear();
+        self.held_connection_changes = 0;
+        (!parts.is_empty()).then(|| format!("While away: {}", parts.join(", ")))
+    }
+}
+
+fn short_peer(id: &str) -> String {
+    id.chars().take(PEER_ID_CHARS).collect()
+}
+
+/// Spoken text for a sidecar event, or None if it isn't worth announcing.
+/// Message text is only included at `Full` verbosity.
+pub(crate) fn announcement_text(event: &Value, verbosity: AnnouncementVerbosity) -> Option<String> {
+    let brief = verbosity == AnnouncementVerbosity::Brief;
+    match event["type"].as_str()? {
+        "message" => {
+            let channel = event["channelId"].as_str().unwrap_or_default();
+            let mut text = if channel.starts_with("dm:") {
+                "Direct message".to_string()
+            } else {
+                format!("Message in {}", channel)
+            };
+            if !brief {
+                if let Some(from) = event["from"].as_str() {
+                    text.push_str(&format!(" from {}", short_peer(from)));
+                }
+            }
+            if verbosity == AnnouncementVerbosity::Full {
+                let content = event["data"]
+                    .as_str()
+                    .and_then(|d| serde_json::from_str::<Value>(d).ok())
+                    .and_then(|m| m["content"].as_str().map(str::to_string));
+                if let Some(content) = content.filter(|c| !c.trim().is_empty()) {
+                    let mut clipped: String = content.chars().take(MAX_CONTENT_CHARS).collect();
+                    if clipped.len() < content.len() {
+                        clipped.push('…');
+                    }
+                    text.push_str(&format!(": {}", clipped));
+                }
+            }
+            Some(text)
+        }
+        kind @ ("peer:connect" | "peer:disconnect") => {
+            let verb = if kind == "peer:connect" {
+                "connected"
+            } else {
+                "disconnected"
+            };
+            match event["peerId"].as_str() {
+                Some(peer) if !brief => Some(format!("Peer {} {}", short_peer(peer), verb)),
+                _ => Some(format!("Peer {}", verb)),
+            }
+        }
+        "ready" => Some("Connected to the network".to_string()),
+        "error" if event["message"].as_str() == Some("Sidecar process exited") => {
+            Some("Disconnected from the network".to_string())
+        }
+        _ => None,
+    }
+}
+
+/// Record launch time; announcements ignore focus during STARTUP_GRACE.
+pub(crate) fn mark_launched() {
+    *LAUNCHED_AT.lock().unwrap_or_else(|e| e.into_inner()) = Some(Instant::now());
+}
+
+fn in_startup_grace() -> bool {
+    LAUNCHED_AT
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .is_some_and(|t| t.elapsed() < STARTUP_GRACE)
+}
+
+#[cfg(windows)]
+fn raise_notification(window: &tauri::WebviewWindow, text: &str) {
+    use std::ffi::c_void;
+    use windows_sys::Win32::Foundation::{SysAllocString, SysFreeString};
+    use windows_sys::Win32::UI::Accessibility::{
+        NotificationKind_Other, NotificationProcessing_ImportantAll, UiaHostProviderFromHwnd,
+        UiaRaiseNotificationEvent,
+    };
+
+    #[repr(C)]
+    struct UnknownVtbl {
+        _query_interface: usize,
+        _add_ref: usize,
+        release: unsafe extern "system" fn(*mut c_void) -> u32,
+    }
+
+    let Ok(hwnd) = window.hwnd() else {
+        return;
+    };
+    let display: Vec<u16> = text.encode_utf16().chain(Some(0)).collect();
+    let activity: Vec<u16> = "concord.announcement"
+        .encode_utf16()
+        .chain(Some(0))
+        .collect();
+    // SAFETY: runs on the main thread for a live window; the provider is
+    // released and both BSTRs freed before returning.
+    unsafe {
+        let mut provider: *mut c_void = std::ptr::null_mut();
+        if UiaHostProviderFromHwnd(hwnd.0 as _, &mut provider) < 0 || provider.is_null() {
+            return;
+        }
+        let display = SysAllocString(display.as_ptr());
+        let activity = SysAllocString(activity.as_ptr());
+        UiaRaiseNotificationEvent(
+            provider,
+            NotificationKind_Other,
+            NotificationProcessing_ImportantAll,
+            display,
+            activity,
+        );
+        SysFreeString(display);
+        SysFreeString(activity);
+        let vtbl = *(provider as *const *const UnknownVtbl);
+        ((*vtbl).release)(provider);
+    }
+}
+
+#[cfg(not(windows))]
+fn raise_notification(_window: &tauri::WebviewWindow, _text: &str) {}
+
+fn speak(app: &tauri::AppHandle, text: String) {
+    let Some(window) = app.get_webview_window(MAIN_WINDOW) else {
+        return;
+    };
+    let _ = app.run_on_main_thread(move || raise_notification(&window, &text));
+}
+
+/// Called for every sidecar event on its way to the frontend.
+pub(crate) fn observe_event(app: &tauri::AppHandle, event: &Value) {
+    let settings = super::settings::current().accessibility;
+    if !settings.announcements {
+        return;
+    }
+    // While focused the webview's live regions do the announcing
+    if FOCUSED.load(Ordering::SeqCst) && !in_startup_grace() {
+        return;
+    }
+    if super::attention::suppressed() {
+        return;
+    }
+    if event["type"].as_str() == Some("message")
+        && super::mutes::is_muted(event["channelId"].as_str().unwrap_or_default())
+    {
+        return;
+    }
+    let Some(text) = announcement_text(event, settings.verbosity) else {
+        return;
+    };
+    let allowed = {
+        let mut guard = LIMITER.lock().unwrap_or_else(|e| e.into_inner());
+        let limiter = guard.get_or_insert_with(Limiter::default);
+        let allowed = limiter.allow(settings.max_per_minute);
+        if !allowed {
+            limiter.hold(event);
+        }
+        allowed
+    };
+    if allowed {
+        speak(app, text);
+    }
+}
+
+/// On focus, announce a summary of whatever the rate limit held back.
+pub(crate) fn on_focus_changed(app: &tauri::AppHandle, focused: bool) {
+    FOCUSED.store(focused, Ordering::SeqCst);
+    if !focused || !super::settings::current().accessibility.announcements {
+        return;
+    }
+    let summary = LIMITER
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .as_mut()
+        .and_then(Limiter::take_summary);
+    if let Some(summary) = summary {
+        speak(app, summary);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn message(channel: &str, content: &str) -> Value {
+        json!({
+            "type": "message",
+            "channelId": channel,
+            "from": "12D3KooWAbcdefgh",
+            "data": json!({"content": content}).to_string(),
+        })
+    }
+
+    #[test]
+    fn verbosity_controls_what_is_spoken() {
+        let event = message("general", "hello");
+        let text = |v| announcement_text(&event, v).unwrap();
+        assert_eq!(text(AnnouncementVerbosity::Brief), "Message in general");
+        assert_eq!(
+            text(AnnouncementVerbosity::Standard),
+            "Message in general from 12D3KooW"
+        );
+        assert_eq!(
+            text(AnnouncementVerbosity::Full),
+            "Message in general from 12D3KooW: hello"
+        );
+    }
+
+    #[test]
+    fn direct_messages_and_long_text() {
+        let long = "é".repeat(MAX_CONTENT_CHARS + 5);
+        let text =
+            announcement_text(&message("dm:someone", &long), AnnouncementVerbosity::Full).unwrap();
+        assert!(text.starts_with("Direct message from 12D3KooW: "));
+        assert!(text.ends_with('…'));
+        assert_eq!(text.matches('é').count(), MAX_CONTENT_CHARS);
+
+        let blank = announcement_text(&message("general", "  "), AnnouncementVerbosity::Full);
+        assert_eq!(blank.as_deref(), Some("Message in general from 12D3KooW"));
+    }
+
+    #[test]
+    fn connection_changes_are_announced() {
+        let connect = json!({"type": "peer:connect", "peerId": "12D3KooWAbcdefgh"});
+        assert_eq!(
+            announcement_text(&connect, AnnouncementVerbosity::Standard).as_deref(),
+            Some("Peer 12D3KooW connected")
+        );
+        let disconnect = json!({"type": "peer:disconnect", "peerId": "x"});
+        assert_eq!(
+            announcement_text(&disconnect, AnnouncementVerbosity::Brief).as_deref(),
+            Some("Peer disconnected")
+        );
+        let exited = json!({"type": "error", "message": "Sidecar process exited"});
+        assert_eq!(
+            announcement_text(&exited, AnnouncementVerbosity::Brief).as_deref(),
+            Some("Disconnected from the network")
+        );
+        let other = json!({"type": "error", "message": "Something else"});
+        assert!(announcement_text(&other, AnnouncementVerbosity::Full).is_none());
+    }
+
+    #[test]
+    fn the_limiter_holds_back_a_burst_and_summarises_it() {
+        let mut limiter = Limiter::default();
+        assert!(limiter.allow(2));
+        assert!(limiter.allow(2));
+        assert!(!limiter.allow(2));
+
+        limiter.hold(&message("general", "a"));
+        limiter.hold(&message("general", "b"));
+        limiter.hold(&message("random", "c"));
+        limiter.hold(&json!({"type": "peer:connect"}));
+        assert_eq!(
+            limiter.take_summary().as_deref(),
+            Some("While away: 3 messages in 2 conversations, 1 connection change")
+        );
+        assert!(limiter.take_summary().is_none());
+
+        limiter.hold(&message("general", "a"));
+        assert_eq!(
+            limiter.take_summary().as_deref(),
+            Some("While away: 1 message")
+        );
+    }
+}
@@ -0,0 +1,374 @@
+// Approval prompts — one "ask the user first" mechanism for sensitive
+// operations. An action calls `require_approval`, which emits
+// `approval-requested` with a nonce and waits for `resolve_approval`.
+// Unanswered prompts are denied after a timeout or when the window closes.
+// Prompts still pending when the webview reloads are emitted again, and
+// `pending_approvals` returns them for a UI that mounts late.
+// Kinds that allow it can be remembered per scope ("always allow dials from
+// this domain"); remembered choices persist in approvals.json.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{mpsc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use super::error::BridgeError;
+
+pub(crate) const APPROVALS_FILE: &str = "approvals.json";
+const APPROVAL_TIMEOUT: Duration = Duration::from_secs(120);
+/// Scope used when a request has none: the choice covers the whole kind.
+const ANY_SCOPE: &str = "*";
+
+static PENDING: Mutex<Vec<Pending>> = Mutex::new(Vec::new());
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ApprovalRequest {
+    pub nonce: String,
+    pub kind: String,
+    pub scope: Option<String>,
+    pub details: serde_json::Value,
+    /// Whether "remember my choice" may be offered for this request.
+    pub rememberable: bool,
+    pub requested_at: u64,
+    pub expires_in_secs: u64,
+}
+
+struct Pending {
+    request: ApprovalRequest,
+    reply: mpsc::Sender<bool>,
+}
+
+/// Remembered choices: kind → scope → allowed.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default, rename_all = "camelCase")]
+struct Remembered {
+    choices: HashMap<String, HashMap<String, bool>>,
+}
+
+fn remembered_path() -> Result<PathBuf, BridgeError> {
+    Ok(super::app_data_dir()?.join(APPROVALS_FILE))
+}
+
+fn load_remembered() -> Remembered {
+    let Ok(path) = remembered_path() else {
+        return Remembered::default();
+    };
+    match super::storage::read_to_string(&path) {
+        Ok(s) => serde_json::from_str(&s).unwrap_or_else(|e| {
+            eprintln!("Ignoring malformed {}: {}", path.display(), e);
+            Remembered::default()
+        }),
+        Err(_) => Remembered::default(),
+    }
+}
+
+fn save_remembered(remembered: &Remembered) -> Result<(), BridgeError> {
+    let path = remembered_path()?;
+    let json = serde_json::to_string_pretty(remembered).map_err(|e| e.to_string())?;
+    super::storage::write(&path, json).map_err(|e| BridgeError::io("Cannot save approvals", e))
+}
+
+/// The remembered choice for `kind` in `scope`, or for the whole kind when
+/// the request has no scope.
+fn remembered_choice(remembered: &Remembered, kind: &str, scope: Option<&str>) -> Option<bool> {
+    remembered
+        .choices
+        .get(kind)
+        .and_then(|c| c.get(scope.unwrap_or(ANY_SCOPE)))
+        .copied()
+}
+
+fn store_choice(remembered: &mut Remembered, request: &ApprovalRequest, approved: bool) {
+    remembered
+        .choices
+        .entry(request.kind.clone())
+        .or_default()
+        .insert(
+            request
+                .scope
+                .clone()
+                .unwrap_or_else(|| ANY_SCOPE.to_string()),
+            approved,
+        );
+}
+
+/// How a pending request ended.
+#[derive(Debug, PartialEq)]
+enum Answer {
+    Approved,
+    /// Denied by the user, or by `deny_all`.
+    Denied,
+    /// Unanswered within the timeout; withdrawn.
+    Expired,
+}
+
+/// Add `request` to the pending list; the answer arrives on the receiver.
+fn register(request: ApprovalRequest) -> mpsc::Receiver<bool> {
+    let (tx, rx) = mpsc::channel();
+    PENDING
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .push(Pending { request, reply: tx });
+    rx
+}
+
+/// Block until `nonce` is answered or `timeout` elapses.
+fn wait_for(nonce: &str, answer: mpsc::Receiver<bool>, timeout: Duration) -> Answer {
+    match answer.recv_timeout(timeout) {
+        Ok(true) => Answer::Approved,
+        Ok(false) => Answer::Denied,
+        // Still pending means timed out; gone means `deny_all` dropped it
+        Err(_) => match take_pending(nonce) {
+            Some(_) => Answer::Expired,
+            None => Answer::Denied,
+        },
+    }
+}
+
+fn take_pending(nonce: &str) -> Option<Pending> {
+    let mut pending = PENDING.lock().unwrap_or_else(|e| e.into_inner());
+    let index = pending.iter().position(|p| p.request.nonce == nonce)?;
+    Some(pending.remove(index))
+}
+
+fn emit_request(app: &tauri::AppHandle, request: &ApprovalRequest) {
+    let mut event = serde_json::to_value(request).unwrap_or_default();
+    event["type"] = serde_json::json!("approval-requested");
+    super::emit_p2p_event(app, event);
+}
+
+/// Ask the user to approve `kind` (e.g. "reset-settings") and wait for the
+/// answer. `scope` narrows a remembered choice, e.g. to one domain; only
+/// `rememberable` requests honour or store remembered choices.
+pub(crate) async fn require_approval(
+    app: &tauri::AppHandle,
+    kind: &str,
+    scope: Option<&str>,
+    details: serde_json::Value,
+    rememberable: bool,
+) -> Result<(), BridgeError> {
+    let denied = || BridgeError::Denied(format!("'{}' was not approved", kind));
+    if rememberable {
+        match remembered_choice(&load_remembered(), kind, scope) {
+            Some(true) => return Ok(()),
+            Some(false) => return Err(denied()),
+            None => {}
+        }
+    }
+
+    let request = ApprovalRequest {
+        nonce: super::dedup::new_message_id(),
+        kind: kind.to_string(),
+        scope: scope.map(str::to_string),
+        details,
+        rememberable,
+        requested_at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0),
+        expires_in_secs: APPROVAL_TIMEOUT.as_secs(),
+    };
+    let nonce = request.nonce.clone();
+    let rx = register(request.clone());
+    emit_request(app, &request);
+
+    let waiting = nonce.clone();
+    let answer =
+        tauri::async_runtime::spawn_blocking(move || wait_for(&waiting, rx, APPROVAL_TIMEOUT))
+            .await
+            .map_err(|e| e.to_string())?;
+    match answer {
+        Answer::Approved => Ok(()),
+        Answer::Denied => Err(denied()),
+        Answer::Expired => {
+            super::emit_p2p_event(
+                app,
+                serde_json::json!({"type": "approval-expired", "nonce": nonce}),
+            );
+            Err(denied())
+        }
+    }
+}
+
+/// Deny every pending request; called when the main window closes.
+pub(crate) fn deny_all() {
+    let drained: Vec<Pending> = PENDING
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .drain(..)
+        .collect();
+    for pending in drained {
+        let _ = pending.reply.send(false);
+    }
+}
+
+/// Emit pending requests again, for a webview that just reloaded.
+pub(crate) fn reemit_pending(app: &tauri::AppHandle) {
+    let requests: Vec<ApprovalRequest> = PENDING
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .map(|p| p.request.clone())
+        .collect();
+    for request in &requests {
+        emit_request(app, request);
+    }
+}
+
+// ── Tauri commands ───────────────────────────────────────────────
+
+/// Requests still waiting for an answer.
+#[tauri::command]
+pub fn pending_approvals() -> Vec<ApprovalRequest> {
+    PENDING
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .map(|p| p.request.clone())
+        .collect()
+}
+
+/// Answer a request. With `remember`, the choice is stored for the request's
+/// kind and scope and future requests are answered without asking.
+#[tauri::command]
+pub fn resolve_approval(
+    nonce: String,
+    approved: bool,
+    remember: Option<bool>,
+) -> Result<(), BridgeError> {
+    let pending = take_pending(&nonce)
+        .ok_or_else(|| BridgeError::NotFound(format!("No pending approval '{}'", nonce)))?;
+    if remember.unwrap_or(false) {
+        if !pending.request.rememberable {
+            // Put it back so the prompt can still be answered
+            PENDING
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .push(pending);
+            return Err(BridgeError::InvalidArgument(
+                "This request cannot be remembered".to_string(),
+            ));
+        }
+        let mut remembered = load_remembered();
+        store_choice(&mut remembered, &pending.request, approved);
+        save_remembered(&remembered)?;
+    }
+    let _ = pending.reply.send(approved);
+    Ok(())
+}
+
+/// Forget all remembered approval choices of the active profile.
+#[tauri::command]
+pub fn clear_remembered_approvals() -> Result<(), BridgeError> {
+    save_remembered(&Remembered::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests share PENDING, and `deny_all` empties it.
+    static BROKER: Mutex<()> = Mutex::new(());
+
+    fn broker() -> std::sync::MutexGuard<'static, ()> {
+        BROKER.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn request(nonce: &str, scope: Option<&str>, rememberable: bool) -> ApprovalRequest {
+        ApprovalRequest {
+            nonce: nonce.to_string(),
+            kind: "dial-invite".to_string(),
+            scope: scope.map(str::to_string),
+            details: serde_json::Value::Null,
+            rememberable,
+            requested_at: 0,
+            expires_in_secs: APPROVAL_TIMEOUT.as_secs(),
+        }
+    }
+
+    fn is_pending(nonce: &str) -> bool {
+        pending_approvals().iter().any(|r| r.nonce == nonce)
+    }
+
+    const SHORT: Duration = Duration::from_millis(20);
+
+    #[test]
+    fn concurrent_prompts_are_answered_separately() {
+        let _broker = broker();
+        let first = register(request("approval-first", None, false));
+        let second = register(request("approval-second", None, false));
+        resolve_approval("approval-second".to_string(), false, None).unwrap();
+        assert_eq!(wait_for("approval-second", second, SHORT), Answer::Denied);
+        assert!(is_pending("approval-first"));
+
+        resolve_approval("approval-first".to_string(), true, None).unwrap();
+        assert_eq!(wait_for("approval-first", first, SHORT), Answer::Approved);
+        assert!(!is_pending("approval-first"));
+        assert!(matches!(
+            resolve_approval("approval-first".to_string(), true, None),
+            Err(BridgeError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn unanswered_prompts_expire_and_are_withdrawn() {
+        let _broker = broker();
+        let answer = register(request("approval-timeout", None, false));
+        assert_eq!(wait_for("approval-timeout", answer, SHORT), Answer::Expired);
+        assert!(!is_pending("approval-timeout"));
+    }
+
+    #[test]
+    fn deny_all_denies_every_pending_prompt() {
+        let _broker = broker();
+        let first = register(request("approval-deny-1", None, false));
+        let second = register(request("approval-deny-2", Some("example.com"), true));
+        deny_all();
+        assert!(pending_approvals().is_empty());
+        assert_eq!(wait_for("approval-deny-1", first, SHORT), Answer::Denied);
+        assert_eq!(wait_for("approval-deny-2", second, SHORT), Answer::Denied);
+    }
+
+    #[test]
+    fn remembered_choices_are_looked_up_by_kind_and_scope() {
+        let mut remembered = Remembered::default();
+        store_choice(
+            &mut remembered,
+            &request("n1", Some("example.com"), true),
+            true,
+        );
+        store_choice(&mut remembered, &request("n2", None, true), false);
+        assert_eq!(
+            remembered_choice(&remembered, "dial-invite", Some("example.com")),
+            Some(true)
+        );
+        assert_eq!(
+            remembered_choice(&remembered, "dial-invite", Some("other.org")),
+            None
+        );
+        assert_eq!(
+            remembered_choice(&remembered, "dial-invite", None),
+            Some(false)
+        );
+        assert_eq!(
+            remembered_choice(&remembered, "reset-settings", Some("example.com")),
+            None
+        );
+    }
+
+    #[test]
+    fn remembering_a_non_rememberable_request_leaves_it_pending() {
+        let _broker = broker();
+        let answer = register(request("approval-once", None, false));
+        assert!(matches!(
+            resolve_approval("approval-once".to_string(), true, Some(true)),
+            Err(BridgeError::InvalidArgument(_))
+        ));
+        assert!(is_pending("approval-once"));
+        resolve_approval("approval-once".to_string(), true, None).unwrap();
+        assert_eq!(wait_for("approval-once", answer, SHORT), Answer::Approved);
+    }
+}
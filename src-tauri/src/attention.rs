@@ -0,0 +1,179 @@
+// Attention cues — taskbar flash and "(N)" window title, driven from the
+// bridge so they work even while the webview is busy or still loading.
+
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::Mutex;
+
+use tauri::Manager;
+
+const MAIN_WINDOW: &str = "main";
+
+static UNREAD: AtomicU32 = AtomicU32::new(0);
+static FOCUSED: AtomicBool = AtomicBool::new(true);
+static BASE_TITLE: Mutex<Option<String>> = Mutex::new(None);
+static OWN_PEER_ID: Mutex<Option<String>> = Mutex::new(None);
+
+/// Whether the bridge owns the window title (reported via `get_app_info`),
+/// so the frontend knows not to set one itself.
+pub(crate) fn bridge_manages_title() -> bool {
+    super::settings::current().attention.title_unread_count
+}
+
+pub(crate) fn is_mention(data: &str) -> bool {
+    let own = OWN_PEER_ID
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone();
+    let Some(me) = own else {
+        return false;
+    };
+    let content = serde_json::from_str::<serde_json::Value>(data)
+        .ok()
+        .and_then(|m| m["content"].as_str().map(str::to_string))
+        .unwrap_or_default();
+    // The UI shows peers by the first 16 characters of their id
+    content.contains(&format!("@{}", &me[..me.len().min(16)]))
+}
+
+/// Do-not-disturb, quiet hours or a focus session; also honoured by
+/// screen-reader announcements.
+pub(crate) fn suppressed() -> bool {
+    if super::focus::active() {
+        return true;
+    }
+    let notifications = super::settings::current().notifications;
+    if notifications.dnd {
+        return true;
+    }
+    match (notifications.quiet_hours, local_minute_of_day()) {
+        (Some(q), Some(minute)) => q.contains(minute),
+        _ => false,
+    }
+}
+
+#[cfg(windows)]
+pub(crate) fn local_minute_of_day() -> Option<u16> {
+    use windows_sys::Win32::Foundation::SYSTEMTIME;
+    use windows_sys::Win32::System::SystemInformation::GetLocalTime;
+    // SAFETY: GetLocalTime only writes into the provided struct.
+    let st = unsafe {
+        let mut st: SYSTEMTIME = std::mem::zeroed();
+        GetLocalTime(&mut st);
+        st
+    };
+    Some(st.wHour * 60 + st.wMinute)
+}
+
+#[cfg(not(windows))]
+pub(crate) fn local_minute_of_day() -> Option<u16> {
+    None
+}
+
+#[cfg(windows)]
+fn flash(window: &tauri::WebviewWindow, count: u32) {
+    use windows_sys::Win32::UI::WindowsAndMessaging::{FlashWindowEx, FLASHWINFO, FLASHW_TRAY};
+    let Ok(hwnd) = window.hwnd() else {
+        return;
+    };
+    let info = FLASHWINFO {
+        cbSize: std::mem::size_of::<FLASHWINFO>() as u32,
+        hwnd: hwnd.0 as _,
+        dwFlags: FLASHW_TRAY,
+        uCount: count,
+        dwTimeout: 0,
+    };
+    // SAFETY: info is a fully initialised FLASHWINFO for a live window.
+    unsafe {
+        FlashWindowEx(&info);
+    }
+}
+
+#[cfg(not(windows))]
+fn flash(window: &tauri::WebviewWindow, _count: u32) {
+    let _ = window.request_user_attention(Some(tauri::UserAttentionType::Informational));
+}
+
+/// Flash the taskbar once with the configured count, e.g. for a focus
+/// digest. Honours the same settings as message flashes.
+pub(crate) fn flash_once(app: &tauri::AppHandle) {
+    let attention = super::settings::current().attention;
+    if !attention.flash_taskbar || suppressed() || FOCUSED.load(Ordering::SeqCst) {
+        return;
+    }
+    if let Some(window) = app.get_webview_window(MAIN_WINDOW) {
+        flash(&window, attention.flash_count);
+    }
+}
+
+fn update_title(app: &tauri::AppHandle) {
+    let Some(window) = app.get_webview_window(MAIN_WINDOW) else {
+        return;
+    };
+    let base = {
+        let mut guard = BASE_TITLE.lock().unwrap_or_else(|e| e.into_inner());
+        guard
+            .get_or_insert_with(|| window.title().unwrap_or_else(|_| "Concord".to_string()))
+            .clone()
+    };
+    let unread = UNREAD.load(Ordering::SeqCst);
+    let title = if unread > 0 && bridge_manages_title() {
+        format!("{} ({})", base, unread)
+    } else {
+        base
+    };
+    let _ = window.set_title(&title);
+}
+
+/// Called by the stdout reader for every parsed sidecar event.
+pub(crate) fn observe_event(app: &tauri::AppHandle, event: &serde_json::Value) {
+    match event.get("type").and_then(|t| t.as_str()) {
+        Some("ready") => {
+            if let Some(id) = event["peerId"].as_str() {
+                *OWN_PEER_ID.lock().unwrap_or_else(|e| e.into_inner()) = Some(id.to_string());
+            }
+        }
+        Some("message") => {
+            let channel = event["channelId"].as_str().unwrap_or_default();
+            // A focus session collects these for its digest instead
+            let counted = !FOCUSED.load(Ordering::SeqCst)
+                && !super::mutes::is_muted(channel)
+                && !super::focus::active();
+            super::tray::observe_message(channel, counted);
+            if !counted {
+                return;
+            }
+            UNREAD.fetch_add(1, Ordering::SeqCst);
+            update_title(app);
+
+            let is_dm = channel.starts_with("dm:");
+            if !is_dm && !is_mention(event["data"].as_str().unwrap_or_default()) {
+                return;
+            }
+            let attention = super::settings::current().attention;
+            if !attention.flash_taskbar || suppressed() {
+                return;
+            }
+            if let Some(window) = app.get_webview_window(MAIN_WINDOW) {
+                flash(&window, attention.flash_count);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Focus returning clears the counter and restores the clean title.
+pub(crate) fn on_focus_changed(app: &tauri::AppHandle, focused: bool) {
+    FOCUSED.store(focused, Ordering::SeqCst);
+    if focused {
+        super::tray::clear_unread();
+    }
+    if focused && UNREAD.swap(0, Ordering::SeqCst) > 0 {
+        update_title(app);
+    }
+}
+
+/// Re-apply the title when the attention settings change. A settings
+/// subscriber.
+pub(crate) fn settings_changed(app: &tauri::AppHandle) {
+    update_title(app);
+}
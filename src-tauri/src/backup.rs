@@ -0,0 +1,733 @@
+// Full-profile backup — one passphrase-encrypted archive of everything the
+// profile persists, plus restore through a staging directory and an
+// optional scheduled auto-backup that uses the same code path.
+//
+// Archive layout:
+//   MAGIC | format version (u16 LE) | salt | nonce | XChaCha20-Poly1305(payload)
+//   payload = manifest length (u32 LE) | manifest JSON | file bytes in manifest order
+// The AEAD tag covers the whole payload, so a wrong passphrase or a corrupt
+// file is detected before anything on disk is touched.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use argon2::Argon2;
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use serde::{Deserialize, Serialize};
+
+use super::error::BridgeError;
+use super::operations::OpHandle;
+
+const MAGIC: &[u8; 16] = b"CONCORD-BACKUP\0\0";
+const FORMAT_VERSION: u16 = 1;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+const HEADER_LEN: usize = MAGIC.len() + 2 + SALT_LEN + NONCE_LEN;
+const MIN_PASSPHRASE_LEN: usize = 8;
+
+pub const COMPONENT_IDENTITY: u32 = 1;
+pub const COMPONENT_SETTINGS: u32 = 2;
+pub const COMPONENT_HISTORY: u32 = 4;
+/// Launch options, mutes, message requests, send policies, contact
+/// overrides and remembered approvals.
+pub const COMPONENT_STATE: u32 = 8;
+pub const COMPONENT_ALL: u32 =
+    COMPONENT_IDENTITY | COMPONENT_SETTINGS | COMPONENT_HISTORY | COMPONENT_STATE;
+
+const AUTO_BACKUP_KEY_FILE: &str = "auto-backup.key";
+const AUTO_BACKUP_PREFIX: &str = "concord-";
+const AUTO_BACKUP_EXT: &str = ".concordbackup";
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(2);
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Manifest {
+    format_version: u16,
+    app_version: String,
+    profile: String,
+    created_at: u64,
+    components: u32,
+    include_attachments: bool,
+    entries: Vec<EntryMeta>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EntryMeta {
+    path: String,
+    component: u32,
+    size: u64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupSummary {
+    pub path: String,
+    pub files: usize,
+    pub bytes: u64,
+    pub components: u32,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RestoreSummary {
+    pub profile: String,
+    pub created_at: u64,
+    pub files: usize,
+    pub components: u32,
+}
+
+// ── Helpers ──────────────────────────────────────────────────────
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Which component a profile-relative path belongs to, if any.
+/// Logs, reports and scratch files are deliberately not backed up, and
+/// neither are SQLite's -wal/-shm files: the database is backed up from a
+/// snapshot instead (see `read_entry`). Nor are the outbound sequence
+/// counters (restoring older ones would reuse numbers peers already saw),
+/// the focus session (transient) or the auto-backup key (sealed to this
+/// machine).
+fn classify(rel: &str) -> Option<u32> {
+    match rel {
+        "node-identity.json" | "identity-history.json" => Some(COMPONENT_IDENTITY),
+        "settings.json" => Some(COMPONENT_SETTINGS),
+        super::history::DB_FILE => Some(COMPONENT_HISTORY),
+        super::sidecar_config::CONFIG_FILE
+        | super::mutes::MUTES_FILE
+        | super::message_requests::REQUESTS_FILE
+        | super::send_policy::POLICIES_FILE
+        | super::presentation::PRESENTATION_FILE
+        | super::approvals::APPROVALS_FILE => Some(COMPONENT_STATE),
+        _ if rel.starts_with("identity-backups/") => Some(COMPONENT_IDENTITY),
+        _ => None,
+    }
+}
+
+fn collect_files(dir: &Path, base: &Path, out: &mut Vec<(String, PathBuf)>) -> Result<(), String> {
+    for entry in fs::read_dir(dir).map_err(|e| e.to_string())?.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_files(&path, base, out)?;
+        } else if let Ok(rel) = path.strip_prefix(base) {
+            let rel = rel.to_string_lossy().replace('\\', "/");
+            out.push((rel, path));
+        }
+    }
+    Ok(())
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> Result<[u8; 32], String> {
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| format!("Key derivation failed: {}", e))?;
+    Ok(key)
+}
+
+// ── Archive encoding ─────────────────────────────────────────────
+
+/// The contents of one backed-up file. The message database is written
+/// to continuously, so it is read from a snapshot (`VACUUM INTO`) rather
+/// than copied mid-transaction.
+fn read_entry(rel: &str, path: &Path) -> Result<Vec<u8>, String> {
+    if rel != super::history::DB_FILE {
+        return fs::read(path).map_err(|e| format!("Cannot read {}: {}", rel, e));
+    }
+    let snapshot = super::storage::writable_dir(None).join(format!(
+        "concord-history-snapshot-{}-{}.db",
+        std::process::id(),
+        now_ms()
+    ));
+    let result = super::history::snapshot(&snapshot)
+        .map_err(String::from)
+        .and_then(|_| fs::read(&snapshot).map_err(|e| format!("Cannot read {}: {}", rel, e)));
+    let _ = fs::remove_file(&snapshot);
+    result
+}
+
+/// Read every backed-up file of the active profile into manifest entries
+/// and one blob of their contents.
+fn read_profile(op: &OpHandle) -> Result<(Vec<EntryMeta>, Vec<u8>), String> {
+    let profile_dir = super::app_data_dir()?;
+    let mut files = Vec::new();
+    collect_files(&profile_dir, &profile_dir, &mut files)?;
+    files.retain(|(rel, _)| classify(rel).is_some());
+    files.sort();
+
+    let total = files.len();
+    let mut entries = Vec::with_capacity(total);
+    let mut blob = Vec::new();
+    for (i, (rel, path)) in files.iter().enumerate() {
+        op.checkpoint()?;
+        let data = read_entry(rel, path)?;
+        entries.push(EntryMeta {
+            path: rel.clone(),
+            component: classify(rel).unwrap_or(0),
+            size: data.len() as u64,
+        });
+        blob.extend_from_slice(&data);
+        op.progress("reading", i + 1, total, None);
+    }
+    Ok((entries, blob))
+}
+
+/// The plaintext payload: manifest length, manifest, file contents.
+fn build_payload(
+    app: &tauri::AppHandle,
+    entries: Vec<EntryMeta>,
+    blob: &[u8],
+    include_attachments: bool,
+) -> Result<Vec<u8>, String> {
+    let manifest = Manifest {
+        format_version: FORMAT_VERSION,
+        app_version: app.package_info().version.to_string(),
+        profile: super::profiles::active_profile()?,
+        created_at: now_ms(),
+        components: COMPONENT_ALL,
+        include_attachments,
+        entries,
+    };
+    let manifest_json = serde_json::to_vec(&manifest).map_err(|e| e.to_string())?;
+    let mut payload = Vec::with_capacity(4 + manifest_json.len() + blob.len());
+    payload.extend_from_slice(&(manifest_json.len() as u32).to_le_bytes());
+    payload.extend_from_slice(&manifest_json);
+    payload.extend_from_slice(blob);
+    Ok(payload)
+}
+
+/// Derive a key from `passphrase` and encrypt the payload into a complete
+/// archive, header included.
+fn seal_payload(passphrase: &str, payload: &[u8]) -> Result<Vec<u8>, String> {
+    let mut salt = [0u8; SALT_LEN];
+    let mut nonce = [0u8; NONCE_LEN];
+    getrandom::getrandom(&mut salt).map_err(|e| e.to_string())?;
+    getrandom::getrandom(&mut nonce).map_err(|e| e.to_string())?;
+    let key = derive_key(passphrase, &salt)?;
+    let cipher = XChaCha20Poly1305::new(Key::from_slice(&key));
+    let sealed = cipher
+        .encrypt(XNonce::from_slice(&nonce), payload)
+        .map_err(|_| "Encryption failed".to_string())?;
+
+    let mut archive = Vec::with_capacity(HEADER_LEN + sealed.len());
+    archive.extend_from_slice(MAGIC);
+    archive.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    archive.extend_from_slice(&salt);
+    archive.extend_from_slice(&nonce);
+    archive.extend_from_slice(&sealed);
+    Ok(archive)
+}
+
+/// Write next to the destination first so a failed write never leaves a
+/// truncated archive under the final name.
+fn save_archive(out_path: &Path, archive: &[u8]) -> Result<(), String> {
+    let tmp = out_path.with_extension("partial");
+    fs::write(&tmp, archive).map_err(|e| format!("Cannot write backup: {}", e))?;
+    fs::rename(&tmp, out_path).map_err(|e| format!("Cannot write backup: {}", e))
+}
+
+fn write_archive(
+    app: &tauri::AppHandle,
+    op: &OpHandle,
+    out_path: &Path,
+    passphrase: &str,
+    include_attachments: bool,
+) -> Result<BackupSummary, String> {
+    let (entries, blob) = read_profile(op)?;
+    let files = entries.len();
+    let payload = build_payload(app, entries, &blob, include_attachments)?;
+    op.checkpoint()?;
+    op.progress("encrypting", files, files, None);
+    let archive = seal_payload(passphrase, &payload)?;
+    save_archive(out_path, &archive)?;
+    Ok(BackupSummary {
+        path: out_path.to_string_lossy().into_owned(),
+        files,
+        bytes: blob.len() as u64,
+        components: COMPONENT_ALL,
+    })
+}
+
+/// Decrypt and fully validate an archive. Nothing on disk is modified.
+fn read_archive(path: &Path, passphrase: &str) -> Result<(Manifest, Vec<u8>), String> {
+    let data = fs::read(path).map_err(|e| format!("Cannot read backup: {}", e))?;
+    if data.len() < HEADER_LEN || &data[..MAGIC.len()] != MAGIC {
+        return Err("Not a Concord backup file".to_string());
+    }
+    let mut at = MAGIC.len();
+    let version = u16::from_le_bytes([data[at], data[at + 1]]);
+    at += 2;
+    if version > FORMAT_VERSION {
+        return Err(format!(
+            "Backup format {} is newer than this version of Concord supports",
+            version
+        ));
+    }
+    let salt = &data[at..at + SALT_LEN];
+    at += SALT_LEN;
+    let nonce = &data[at..at + NONCE_LEN];
+    at += NONCE_LEN;
+
+    let key = derive_key(passphrase, salt)?;
+    let cipher = XChaCha20Poly1305::new(Key::from_slice(&key));
+    let payload = cipher
+        .decrypt(XNonce::from_slice(nonce), &data[at..])
+        .map_err(|_| "Wrong passphrase or corrupt backup".to_string())?;
+
+    let manifest_len = payload
+        .get(..4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize)
+        .ok_or("Corrupt backup: truncated manifest")?;
+    let manifest_bytes = payload
+        .get(4..4 + manifest_len)
+        .ok_or("Corrupt backup: truncated manifest")?;
+    let manifest: Manifest = serde_json::from_slice(manifest_bytes)
+        .map_err(|e| format!("Corrupt backup manifest: {}", e))?;
+    let blob = payload[4 + manifest_len..].to_vec();
+
+    let expected: u64 = manifest.entries.iter().map(|e| e.size).sum();
+    if expected != blob.len() as u64 {
+        return Err("Corrupt backup: file sizes do not match the manifest".to_string());
+    }
+    for entry in &manifest.entries {
+        // Only restore paths we would have written ourselves
+        if entry.path.contains("..") || classify(&entry.path) != Some(entry.component) {
+            return Err(format!("Corrupt backup: unexpected entry '{}'", entry.path));
+        }
+    }
+    Ok((manifest, blob))
+}
+
+fn copy_dir(from: &Path, to: &Path) -> Result<(), String> {
+    fs::create_dir_all(to).map_err(|e| e.to_string())?;
+    for entry in fs::read_dir(from).map_err(|e| e.to_string())?.flatten() {
+        let src = entry.path();
+        let dst = to.join(entry.file_name());
+        if src.is_dir() {
+            copy_dir(&src, &dst)?;
+        } else {
+            fs::copy(&src, &dst).map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(())
+}
+
+/// Build the restored profile in a staging directory beside the live one,
+/// then swap the two with renames.
+fn apply_restore(
+    op: &OpHandle,
+    manifest: &Manifest,
+    blob: &[u8],
+    components: u32,
+) -> Result<usize, String> {
+    let live = super::app_data_dir()?;
+    let name = live
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .ok_or("Invalid profile directory")?;
+    let staging = live.with_file_name(format!("{}.restore-staging", name));
+    let retired = live.with_file_name(format!("{}.restore-old", name));
+    let _ = fs::remove_dir_all(&staging);
+    let _ = fs::remove_dir_all(&retired);
+
+    copy_dir(&live, &staging)?;
+    let selected: Vec<_> = manifest
+        .entries
+        .iter()
+        .scan(0usize, |offset, entry| {
+            let start = *offset;
+            *offset += entry.size as usize;
+            Some((entry, start))
+        })
+        .filter(|(entry, _)| entry.component & components != 0)
+        .collect();
+    let total = selected.len();
+    for (i, (entry, start)) in selected.iter().enumerate() {
+        op.checkpoint()?;
+        let target = staging.join(&entry.path);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        fs::write(&target, &blob[*start..*start + entry.size as usize])
+            .map_err(|e| format!("Cannot stage {}: {}", entry.path, e))?;
+        if entry.path == super::history::DB_FILE {
+            // The live database's journal doesn't belong to the restored one
+            for suffix in ["-wal", "-shm"] {
+                let _ = fs::remove_file(staging.join(format!("{}{}", entry.path, suffix)));
+            }
+        }
+        op.progress("staging", i + 1, total, None);
+    }
+
+    fs::rename(&live, &retired).map_err(|e| format!("Cannot swap in restored data: {}", e))?;
+    if let Err(e) = fs::rename(&staging, &live) {
+        let _ = fs::rename(&retired, &live);
+        return Err(format!("Cannot swap in restored data: {}", e));
+    }
+    let _ = fs::remove_dir_all(&retired);
+    Ok(total)
+}
+
+fn validate_passphrase(passphrase: &str) -> Result<(), BridgeError> {
+    if passphrase.chars().count() < MIN_PASSPHRASE_LEN {
+        return Err(BridgeError::InvalidArgument(format!(
+            "Passphrase must be at least {} characters",
+            MIN_PASSPHRASE_LEN
+        )));
+    }
+    Ok(())
+}
+
+// ── Scheduled auto-backup ────────────────────────────────────────
+
+fn auto_backup_key_path() -> Result<PathBuf, String> {
+    Ok(super::app_data_dir()?.join(AUTO_BACKUP_KEY_FILE))
+}
+
+fn auto_backup_passphrase() -> Option<String> {
+    let sealed = fs::read(auto_backup_key_path().ok()?).ok()?;
+    String::from_utf8(super::dpapi::unprotect(&sealed).ok()?).ok()
+}
+
+/// Existing auto-backups for the active profile in `folder`, oldest first.
+fn auto_backups(folder: &Path, profile: &str) -> Vec<(u64, PathBuf)> {
+    let prefix = format!("{}{}-", AUTO_BACKUP_PREFIX, profile);
+    let mut found: Vec<(u64, PathBuf)> = fs::read_dir(folder)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|e| {
+            let name = e.file_name().into_string().ok()?;
+            let ts = name
+                .strip_prefix(&prefix)?
+                .strip_suffix(AUTO_BACKUP_EXT)?
+                .parse()
+                .ok()?;
+            Some((ts, e.path()))
+        })
+        .collect();
+    found.sort();
+    found
+}
+
+/// Validate the archive, then swap it in with the sidecar stopped.
+fn restore(
+    app: &tauri::AppHandle,
+    op: &OpHandle,
+    path: &Path,
+    passphrase: &str,
+    components: u32,
+) -> Result<RestoreSummary, BridgeError> {
+    op.progress("validating", 0, 0, None);
+    let (manifest, blob) = read_archive(path, passphrase)?;
+    // Last chance to cancel before the live profile is touched
+    op.checkpoint()?;
+
+    let sidecar = super::sidecar::manager(app);
+    let was_running = sidecar.is_running();
+    sidecar.stop_gracefully(SHUTDOWN_GRACE);
+    super::sidecar_log::close();
+    super::history::suspend();
+    let result = apply_restore(op, &manifest, &blob, components);
+    super::history::resume();
+    // The restored files sit at the same paths, so drop what is cached
+    super::settings::invalidate(app);
+    super::mutes::invalidate();
+    super::message_requests::invalidate();
+    super::send_policy::invalidate();
+    super::presentation::invalidate();
+    if was_running {
+        super::start_sidecar(app.clone(), sidecar.incognito())?;
+    }
+    let files = result?;
+    Ok(RestoreSummary {
+        profile: manifest.profile,
+        created_at: manifest.created_at,
+        files,
+        components: components & manifest.components,
+    })
+}
+
+/// Folder, passphrase and profile for an auto-backup that is due now.
+fn due_auto_backup() -> Result<Option<(PathBuf, String, String)>, String> {
+    let cfg = super::settings::current().backup;
+    if !cfg.auto_enabled || super::profiles::is_ephemeral() {
+        return Ok(None);
+    }
+    let Some(folder) = cfg.folder.map(PathBuf::from) else {
+        return Ok(None);
+    };
+    let Some(passphrase) = auto_backup_passphrase() else {
+        return Ok(None);
+    };
+    let profile = super::profiles::active_profile()?;
+    let existing = auto_backups(&folder, &profile);
+    let interval_ms = u64::from(cfg.interval_days) * 24 * 60 * 60 * 1000;
+    if let Some((last, _)) = existing.last() {
+        if now_ms().saturating_sub(*last) < interval_ms {
+            return Ok(None);
+        }
+    }
+    Ok(Some((folder, passphrase, profile)))
+}
+
+/// A scheduled backup between maintenance steps.
+struct AutoBackupRun {
+    folder: PathBuf,
+    passphrase: String,
+    profile: String,
+    op: OpHandle,
+    stage: AutoBackupStage,
+}
+
+enum AutoBackupStage {
+    /// The profile has been read; the archive is sealed next.
+    Read { payload: Vec<u8> },
+    /// The archive is sealed; it is written and old ones pruned next.
+    Sealed { archive: Vec<u8> },
+}
+
+static AUTO_BACKUP: Mutex<Option<AutoBackupRun>> = Mutex::new(None);
+
+/// The scheduled auto-backup, run by the maintenance scheduler in three
+/// steps: read the profile (at once, so the snapshot is consistent), derive
+/// the key and seal, then write and prune. The run in progress is kept in
+/// memory between steps, so a pass that yields to the user resumes it; a
+/// profile switch in between abandons it.
+pub(crate) struct AutoBackup;
+
+impl AutoBackup {
+    fn begin(app: &tauri::AppHandle) -> Result<Option<AutoBackupRun>, String> {
+        let Some((folder, passphrase, profile)) = due_auto_backup()? else {
+            return Ok(None);
+        };
+        let op = super::operations::start(app, "backup", "Scheduled backup");
+        let payload =
+            read_profile(&op).and_then(|(entries, blob)| build_payload(app, entries, &blob, false));
+        match payload {
+            Ok(payload) => Ok(Some(AutoBackupRun {
+                folder,
+                passphrase,
+                profile,
+                op,
+                stage: AutoBackupStage::Read { payload },
+            })),
+            Err(e) => {
+                let _ = op.finish::<()>(Err(BridgeError::from(e.clone())));
+                Err(e)
+            }
+        }
+    }
+
+    /// Take the next step of `run`; true once it is finished.
+    fn advance(run: &mut AutoBackupRun) -> Result<bool, String> {
+        run.op.checkpoint()?;
+        if super::profiles::active_profile()? != run.profile {
+            return Err("The profile changed during the backup".to_string());
+        }
+        match &run.stage {
+            AutoBackupStage::Read { payload } => {
+                run.op.progress("encrypting", 0, 0, None);
+                let archive = seal_payload(&run.passphrase, payload)?;
+                run.stage = AutoBackupStage::Sealed { archive };
+                Ok(false)
+            }
+            AutoBackupStage::Sealed { archive } => {
+                run.op.progress("writing", 0, 0, None);
+                fs::create_dir_all(&run.folder).map_err(|e| e.to_string())?;
+                let out = run.folder.join(format!(
+                    "{}{}-{}{}",
+                    AUTO_BACKUP_PREFIX,
+                    run.profile,
+                    now_ms(),
+                    AUTO_BACKUP_EXT
+                ));
+                save_archive(&out, archive)?;
+                let keep = super::settings::current().backup.keep;
+                let existing = auto_backups(&run.folder, &run.profile);
+                let excess = existing.len().saturating_sub(keep as usize);
+                for (_, path) in existing.into_iter().take(excess) {
+                    let _ = fs::remove_file(path);
+                }
+                Ok(true)
+            }
+        }
+    }
+}
+
+impl super::maintenance::Job for AutoBackup {
+    fn name(&self) -> &'static str {
+        "auto-backup"
+    }
+
+    fn pending(&self) -> bool {
+        AUTO_BACKUP
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .is_some()
+            || matches!(due_auto_backup(), Ok(Some(_)))
+    }
+
+    fn step(&self, app: &tauri::AppHandle) -> Result<super::maintenance::Step, String> {
+        use super::maintenance::Step;
+        // Not held during the work: status queries call `pending`
+        let store = |run| *AUTO_BACKUP.lock().unwrap_or_else(|e| e.into_inner()) = run;
+        let taken = AUTO_BACKUP.lock().unwrap_or_else(|e| e.into_inner()).take();
+        let Some(mut run) = taken else {
+            let Some(run) = Self::begin(app)? else {
+                return Ok(Step::Done);
+            };
+            store(Some(run));
+            return Ok(Step::More);
+        };
+        match Self::advance(&mut run) {
+            Ok(false) => {
+                store(Some(run));
+                Ok(Step::More)
+            }
+            Ok(true) => {
+                let _ = run.op.finish(Ok(()));
+                Ok(Step::Done)
+            }
+            Err(e) => {
+                let _ = run.op.finish::<()>(Err(BridgeError::from(e.clone())));
+                Err(e)
+            }
+        }
+    }
+}
+
+// ── Tauri commands ───────────────────────────────────────────────
+
+/// Write an encrypted backup of the active profile to `path`.
+/// Attachments are not stored by the bridge yet, so `include_attachments`
+/// is only recorded in the manifest.
+#[tauri::command]
+pub async fn create_backup(
+    app: tauri::AppHandle,
+    path: String,
+    passphrase: String,
+    include_attachments: bool,
+) -> Result<BackupSummary, BridgeError> {
+    if super::profiles::is_ephemeral() {
+        return Err(BridgeError::NotReady(
+            super::profiles::EPHEMERAL_UNAVAILABLE.to_string(),
+        ));
+    }
+    validate_passphrase(&passphrase)?;
+    tauri::async_runtime::spawn_blocking(move || -> Result<BackupSummary, BridgeError> {
+        let op = super::operations::start(&app, "backup", "Create backup");
+        let result = write_archive(
+            &app,
+            &op,
+            Path::new(&path),
+            &passphrase,
+            include_attachments,
+        );
+        op.finish(result.map_err(BridgeError::from))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Restore a backup into the active profile, once the user approves.
+/// `components` is a bitmask of COMPONENT_* values (default: everything in
+/// the archive). The archive is fully decrypted and validated before the
+/// sidecar is stopped.
+#[tauri::command]
+pub async fn restore_backup(
+    app: tauri::AppHandle,
+    path: String,
+    passphrase: String,
+    components: Option<u32>,
+) -> Result<RestoreSummary, BridgeError> {
+    if super::profiles::is_ephemeral() {
+        return Err(BridgeError::NotReady(
+            super::profiles::EPHEMERAL_UNAVAILABLE.to_string(),
+        ));
+    }
+    let components = components.unwrap_or(COMPONENT_ALL);
+    if components & COMPONENT_ALL == 0 {
+        return Err(BridgeError::InvalidArgument(
+            "No components selected".to_string(),
+        ));
+    }
+    super::approvals::require_approval(
+        &app,
+        "restore-backup",
+        None,
+        serde_json::json!({"path": path, "components": components}),
+        false,
+    )
+    .await?;
+    tauri::async_runtime::spawn_blocking(move || -> Result<RestoreSummary, BridgeError> {
+        let op = super::operations::start(&app, "backup", "Restore backup");
+        let result = restore(&app, &op, Path::new(&path), &passphrase, components);
+        op.finish(result)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Store (or clear) the passphrase used by scheduled auto-backups. It is kept
+/// sealed with `dpapi::protect`, separate from settings.json.
+#[tauri::command]
+pub fn set_auto_backup_passphrase(passphrase: Option<String>) -> Result<(), BridgeError> {
+    let path = auto_backup_key_path()?;
+    match passphrase {
+        Some(p) => {
+            validate_passphrase(&p)?;
+            fs::write(&path, super::dpapi::protect(p.as_bytes())?)
+                .map_err(|e| BridgeError::io("Cannot save passphrase", e))
+        }
+        None => match fs::remove_file(&path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                Err(BridgeError::io("Cannot remove passphrase", e))
+            }
+            _ => Ok(()),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classify_covers_profile_state_but_not_scratch_files() {
+        assert_eq!(classify("node-identity.json"), Some(COMPONENT_IDENTITY));
+        assert_eq!(
+            classify("identity-backups/a.json"),
+            Some(COMPONENT_IDENTITY)
+        );
+        assert_eq!(classify("settings.json"), Some(COMPONENT_SETTINGS));
+        assert_eq!(classify("messages.db"), Some(COMPONENT_HISTORY));
+        for state in [
+            "config.json",
+            "mutes.json",
+            "message-requests.json",
+            "send-policies.json",
+            "presentation.json",
+            "approvals.json",
+        ] {
+            assert_eq!(classify(state), Some(COMPONENT_STATE), "{}", state);
+        }
+        for skipped in [
+            "messages.db-wal",
+            "sidecar.log",
+            "sequence.json",
+            "focus.json",
+            "auto-backup.key",
+        ] {
+            assert_eq!(classify(skipped), None, "{}", skipped);
+        }
+    }
+}
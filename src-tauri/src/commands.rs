@@ -0,0 +1,454 @@
+// Command registry — a machine-readable catalog of the bridge commands the
+// frontend exposes as slash commands (`/dial`, `/export`, ...), plus a single
+// entry point that validates arguments against the catalog and dispatches to
+// the same handlers the direct Tauri commands use. Each entry carries its
+// own handler, so the catalog and dispatch can't drift apart.
+// Destructive commands keep their own safeguards: every handler behind a
+// `destructive` entry asks for approval (`approvals::require_approval`)
+// before it changes anything; the registry only forwards arguments.
+
+use std::future::Future;
+use std::pin::Pin;
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use super::error::BridgeError;
+use super::{backup, diagnostics, identity, profiles, safe_mode, settings};
+
+type Reply = Pin<Box<dyn Future<Output = Result<Value, BridgeError>> + Send>>;
+/// Runs a command with its validated arguments.
+type Run = fn(tauri::AppHandle, Map<String, Value>) -> Reply;
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ArgKind {
+    String,
+    Boolean,
+    Integer,
+    /// Any JSON value, passed through unchanged.
+    Json,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArgSpec {
+    pub name: &'static str,
+    pub kind: ArgKind,
+    pub required: bool,
+    pub description: &'static str,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CommandSpec {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub args: &'static [ArgSpec],
+    pub destructive: bool,
+    #[serde(skip)]
+    run: Run,
+}
+
+const fn req(name: &'static str, kind: ArgKind, description: &'static str) -> ArgSpec {
+    ArgSpec {
+        name,
+        kind,
+        required: true,
+        description,
+    }
+}
+
+const fn opt(name: &'static str, kind: ArgKind, description: &'static str) -> ArgSpec {
+    ArgSpec {
+        name,
+        kind,
+        required: false,
+        description,
+    }
+}
+
+// Argument names match the camelCase names of the direct commands.
+static REGISTRY: &[CommandSpec] = &[
+    CommandSpec {
+        name: "dial",
+        description: "Connect to a peer by invite code or multiaddr",
+        args: &[req("address", ArgKind::String, "Invite code or multiaddr")],
+        destructive: false,
+        run: |app, args| {
+            Box::pin(async move { to_json(super::p2p_dial(app, string(&args, "address")).await) })
+        },
+    },
+    CommandSpec {
+        name: "restart",
+        description: "Restart the P2P node",
+        args: &[opt(
+            "incognito",
+            ArgKind::Boolean,
+            "Use a throwaway identity",
+        )],
+        destructive: false,
+        run: |app, args| {
+            Box::pin(async move {
+                to_json(super::restart_p2p(
+                    app,
+                    opt_bool(&args, "incognito").unwrap_or(false),
+                ))
+            })
+        },
+    },
+    CommandSpec {
+        name: "check-connectivity",
+        description: "Run the NAT and relay connectivity check",
+        args: &[],
+        destructive: false,
+        run: |app, _| {
+            Box::pin(async move { to_json(diagnostics::run_connectivity_check(app).await) })
+        },
+    },
+    CommandSpec {
+        name: "profiles",
+        description: "List profiles",
+        args: &[],
+        destructive: false,
+        run: |_, _| Box::pin(async { to_json(profiles::list_profiles()) }),
+    },
+    CommandSpec {
+        name: "profile-create",
+        description: "Create a new profile",
+        args: &[req("name", ArgKind::String, "Profile name")],
+        destructive: false,
+        run: |_, args| {
+            Box::pin(async move { to_json(profiles::create_profile(string(&args, "name"))) })
+        },
+    },
+    CommandSpec {
+        name: "profile-switch",
+        description: "Switch to another profile",
+        args: &[req("name", ArgKind::String, "Profile name")],
+        destructive: false,
+        run: |app, args| {
+            Box::pin(
+                async move { to_json(profiles::switch_profile(app, string(&args, "name")).await) },
+            )
+        },
+    },
+    CommandSpec {
+        name: "profile-delete",
+        description: "Delete a profile and all of its data",
+        args: &[req("name", ArgKind::String, "Profile name")],
+        destructive: true,
+        run: |app, args| {
+            Box::pin(
+                async move { to_json(profiles::delete_profile(app, string(&args, "name")).await) },
+            )
+        },
+    },
+    CommandSpec {
+        name: "guest",
+        description: "Start a guest session with a throwaway identity",
+        args: &[],
+        destructive: false,
+        run: |app, _| {
+            Box::pin(async move { to_json(profiles::start_ephemeral_session(app).await) })
+        },
+    },
+    CommandSpec {
+        name: "guest-end",
+        description: "End the guest session and wipe its data",
+        args: &[],
+        destructive: true,
+        run: |app, _| Box::pin(async move { to_json(profiles::end_ephemeral_session(app).await) }),
+    },
+    CommandSpec {
+        name: "regenerate-identity",
+        description: "Replace the identity key (asks for approval first)",
+        args: &[opt(
+            "notifyContacts",
+            ArgKind::Boolean,
+            "Tell contacts about the new key",
+        )],
+        destructive: true,
+        run: |app, args| {
+            Box::pin(async move {
+                to_json(identity::regenerate_identity(app, opt_bool(&args, "notifyContacts")).await)
+            })
+        },
+    },
+    CommandSpec {
+        name: "identity-backups",
+        description: "List backups of previous identities",
+        args: &[],
+        destructive: false,
+        run: |_, _| Box::pin(async { to_json(identity::list_identity_backups()) }),
+    },
+    CommandSpec {
+        name: "identity-restore",
+        description: "Restore a previous identity from its backup",
+        args: &[req("name", ArgKind::String, "Backup name")],
+        destructive: true,
+        run: |app, args| {
+            Box::pin(async move {
+                to_json(identity::restore_identity_backup(app, string(&args, "name")).await)
+            })
+        },
+    },
+    CommandSpec {
+        name: "export",
+        description: "Write an encrypted backup of this profile",
+        args: &[
+            req("path", ArgKind::String, "Destination file"),
+            req("passphrase", ArgKind::String, "At least 8 characters"),
+            opt(
+                "includeAttachments",
+                ArgKind::Boolean,
+                "Include attachments",
+            ),
+        ],
+        destructive: false,
+        run: |app, args| {
+            Box::pin(async move {
+                to_json(
+                    backup::create_backup(
+                        app,
+                        string(&args, "path"),
+                        string(&args, "passphrase"),
+                        opt_bool(&args, "includeAttachments").unwrap_or(false),
+                    )
+                    .await,
+                )
+            })
+        },
+    },
+    CommandSpec {
+        name: "import",
+        description: "Restore this profile from an encrypted backup",
+        args: &[
+            req("path", ArgKind::String, "Backup file"),
+            req("passphrase", ArgKind::String, "Backup passphrase"),
+            opt(
+                "components",
+                ArgKind::Integer,
+                "Bitmask: 1 identity, 2 settings",
+            ),
+        ],
+        destructive: true,
+        run: |app, args| {
+            Box::pin(async move {
+                to_json(
+                    backup::restore_backup(
+                        app,
+                        string(&args, "path"),
+                        string(&args, "passphrase"),
+                        opt_u32(&args, "components"),
+                    )
+                    .await,
+                )
+            })
+        },
+    },
+    CommandSpec {
+        name: "settings",
+        description: "Show all settings",
+        args: &[],
+        destructive: false,
+        run: |_, _| Box::pin(async { to_json(Ok(settings::get_settings())) }),
+    },
+    CommandSpec {
+        name: "reset-settings",
+        description: "Restore all settings to their defaults",
+        args: &[],
+        destructive: true,
+        run: |app, _| Box::pin(async move { to_json(settings::reset_settings(app).await) }),
+    },
+    CommandSpec {
+        name: "leave-safe-mode",
+        description: "Leave safe mode and start the P2P node",
+        args: &[],
+        destructive: false,
+        run: |app, _| Box::pin(async move { to_json(safe_mode::leave_safe_mode(app)) }),
+    },
+    CommandSpec {
+        name: "set",
+        description: "Change a setting, e.g. attention.flashCount",
+        args: &[
+            req("key", ArgKind::String, "Dotted setting key"),
+            req("value", ArgKind::Json, "New value"),
+        ],
+        destructive: false,
+        run: |app, args| {
+            Box::pin(async move {
+                to_json(settings::set_setting(
+                    app,
+                    string(&args, "key"),
+                    args["value"].clone(),
+                ))
+            })
+        },
+    },
+];
+
+// ── Validation ───────────────────────────────────────────────────
+
+fn kind_matches(kind: ArgKind, value: &Value) -> bool {
+    match kind {
+        ArgKind::String => value.is_string(),
+        ArgKind::Boolean => value.is_boolean(),
+        ArgKind::Integer => value.is_u64(),
+        ArgKind::Json => true,
+    }
+}
+
+fn validate(spec: &CommandSpec, args: &Map<String, Value>) -> Result<(), BridgeError> {
+    let invalid = |message: String| Err(BridgeError::InvalidArgument(message));
+    if let Some(unknown) = args
+        .keys()
+        .find(|k| !spec.args.iter().any(|a| a.name == k.as_str()))
+    {
+        return invalid(format!("/{}: unknown argument '{}'", spec.name, unknown));
+    }
+    for a in spec.args {
+        match args.get(a.name) {
+            // `null` is a legitimate value for Json arguments (clearing a setting)
+            None if a.required => {
+                return invalid(format!("/{}: missing argument '{}'", spec.name, a.name));
+            }
+            Some(Value::Null) if a.required && a.kind != ArgKind::Json => {
+                return invalid(format!("/{}: missing argument '{}'", spec.name, a.name));
+            }
+            Some(v) if !v.is_null() && !kind_matches(a.kind, v) => {
+                return invalid(format!(
+                    "/{}: argument '{}' must be {}",
+                    spec.name,
+                    a.name,
+                    match a.kind {
+                        ArgKind::String => "a string",
+                        ArgKind::Boolean => "true or false",
+                        ArgKind::Integer => "a non-negative integer",
+                        ArgKind::Json => "JSON",
+                    }
+                ));
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+// Accessors for already-validated arguments.
+
+fn string(args: &Map<String, Value>, name: &str) -> String {
+    args[name].as_str().unwrap_or_default().to_string()
+}
+
+fn opt_bool(args: &Map<String, Value>, name: &str) -> Option<bool> {
+    args.get(name).and_then(Value::as_bool)
+}
+
+fn opt_u32(args: &Map<String, Value>, name: &str) -> Option<u32> {
+    args.get(name)
+        .and_then(Value::as_u64)
+        .map(|v| u32::try_from(v).unwrap_or(u32::MAX))
+}
+
+fn to_json<T: Serialize>(result: Result<T, BridgeError>) -> Result<Value, BridgeError> {
+    Ok(serde_json::to_value(result?).map_err(|e| e.to_string())?)
+}
+
+/// The registry entry for `name`.
+fn find(name: &str) -> Option<&'static CommandSpec> {
+    REGISTRY.iter().find(|c| c.name == name)
+}
+
+// ── Tauri commands ───────────────────────────────────────────────
+
+/// Catalog of commands invocable through `execute_registered_command`.
+#[tauri::command]
+pub fn get_command_registry() -> &'static [CommandSpec] {
+    REGISTRY
+}
+
+/// Validate `args_json` (a JSON object) against the registry entry for
+/// `name` and run the command. Returns the command's own result as JSON.
+#[tauri::command]
+pub async fn execute_registered_command(
+    app: tauri::AppHandle,
+    name: String,
+    args_json: String,
+) -> Result<Value, BridgeError> {
+    let spec =
+        find(&name).ok_or_else(|| BridgeError::NotFound(format!("Unknown command '/{}'", name)))?;
+    let args = if args_json.trim().is_empty() {
+        Map::new()
+    } else {
+        match serde_json::from_str(&args_json) {
+            Ok(Value::Object(map)) => map,
+            Ok(_) => {
+                return Err(BridgeError::InvalidArgument(format!(
+                    "/{}: arguments must be a JSON object",
+                    name
+                )))
+            }
+            Err(e) => {
+                return Err(BridgeError::InvalidArgument(format!(
+                    "/{}: invalid arguments: {}",
+                    name, e
+                )))
+            }
+        }
+    };
+    validate(spec, &args)?;
+    (spec.run)(app, args).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn every_entry_resolves_to_itself() {
+        for spec in REGISTRY {
+            let found = find(spec.name).expect(spec.name);
+            assert!(std::ptr::eq(found, spec), "/{} is listed twice", spec.name);
+            for (i, arg) in spec.args.iter().enumerate() {
+                assert!(
+                    !spec.args[..i].iter().any(|a| a.name == arg.name),
+                    "/{} lists '{}' twice",
+                    spec.name,
+                    arg.name
+                );
+            }
+        }
+        assert!(find("no-such-command").is_none());
+    }
+
+    #[test]
+    fn the_catalog_leaves_out_handlers() {
+        let catalog = serde_json::to_value(REGISTRY).unwrap();
+        let dial = &catalog[0];
+        assert_eq!(dial["name"], "dial");
+        assert_eq!(dial["args"][0]["kind"], "string");
+        assert!(dial.get("run").is_none());
+    }
+
+    #[test]
+    fn arguments_are_validated_against_the_entry() {
+        let set = find("set").unwrap();
+        let args = |v: Value| v.as_object().cloned().unwrap();
+        assert!(validate(set, &args(json!({"key": "a.b", "value": null}))).is_ok());
+        for bad in [
+            json!({"key": "a.b"}),
+            json!({"key": 1, "value": 2}),
+            json!({"key": "a.b", "value": 2, "extra": true}),
+        ] {
+            assert!(matches!(
+                validate(set, &args(bad)),
+                Err(BridgeError::InvalidArgument(_))
+            ));
+        }
+        let import = find("import").unwrap();
+        let components = json!({"path": "b", "passphrase": "p", "components": -1});
+        assert!(validate(import, &args(components)).is_err());
+    }
+}
@@ -0,0 +1,275 @@
+// Payload compression — message data larger than
+// `compression.thresholdBytes` is deflated and base64-wrapped
+// (`encoding: "deflate"`) before it goes to the sidecar, but only when every
+// recipient has said it can read that. Support is advertised on each
+// outbound payload (`caps`) and learned per peer from what it sends; the
+// sidecar passes both fields through. Inbound compressed data is inflated
+// first thing in the reader, before dedup, validation or emission, with the
+// output capped relative to the input so a small payload can't expand into
+// a memory bomb.
+
+use std::collections::HashSet;
+use std::io::{Read, Write};
+use std::sync::Mutex;
+
+use base64::Engine;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use serde_json::Value;
+
+use super::settings::CompressionSettings;
+
+/// Value of `encoding` on compressed payloads, and the capability for it.
+pub(crate) const DEFLATE: &str = "deflate";
+/// Inflated data may be at most this many times its compressed size...
+const MAX_RATIO: usize = 32;
+/// ...and never more than this.
+pub(crate) const MAX_INFLATED_BYTES: usize = 8 * 1024 * 1024;
+
+/// Peers that advertised deflate support this session.
+static CAPABLE: Mutex<Option<HashSet<String>>> = Mutex::new(None);
+/// Connected peers, as of the latest peer event.
+static CONNECTED: Mutex<Option<Vec<String>>> = Mutex::new(None);
+
+fn supports(peer: &str) -> bool {
+    CAPABLE
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .as_ref()
+        .is_some_and(|c| c.contains(peer))
+}
+
+fn inflate(encoded: &str) -> Result<String, String> {
+    let compressed = base64::engine::general_purpose::STANDARD
+        .decode(encoded)
+        .map_err(|e| format!("bad base64: {}", e))?;
+    let limit = compressed
+        .len()
+        .saturating_mul(MAX_RATIO)
+        .min(MAX_INFLATED_BYTES);
+    let mut inflated = Vec::new();
+    DeflateDecoder::new(compressed.as_slice())
+        .take(limit as u64 + 1)
+        .read_to_end(&mut inflated)
+        .map_err(|e| format!("corrupt deflate stream: {}", e))?;
+    if inflated.len() > limit {
+        return Err(format!(
+            "inflates past {} bytes from {}",
+            limit,
+            compressed.len()
+        ));
+    }
+    String::from_utf8(inflated).map_err(|e| format!("not UTF-8: {}", e))
+}
+
+/// None if compressing fails or the result would inflate past MAX_RATIO,
+/// which receivers refuse.
+fn deflate(data: &str) -> Option<String> {
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data.as_bytes()).ok()?;
+    let compressed = encoder.finish().ok()?;
+    if data.len() > compressed.len().saturating_mul(MAX_RATIO) {
+        return None;
+    }
+    Some(base64::engine::general_purpose::STANDARD.encode(compressed))
+}
+
+/// Called by the stdout reader for every event before anything else looks
+/// at it: learns peer support, and inflates compressed message data in
+/// place. Returns false for a payload that can't be inflated safely; it is
+/// dropped.
+pub(crate) fn admit(event: &mut Value) -> bool {
+    match event["type"].as_str() {
+        Some("peer:connect" | "peer:disconnect") => {
+            if let Some(peers) = event["peers"].as_array() {
+                let peers = peers
+                    .iter()
+                    .filter_map(|p| p.as_str().map(str::to_string))
+                    .collect();
+                *CONNECTED.lock().unwrap_or_else(|e| e.into_inner()) = Some(peers);
+            }
+            true
+        }
+        Some("message") => {
+            let advertised = event["caps"]
+                .as_array()
+                .is_some_and(|caps| caps.iter().any(|c| c.as_str() == Some(DEFLATE)));
+            if let (true, Some(from)) = (advertised, event["from"].as_str()) {
+                CAPABLE
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .get_or_insert_with(HashSet::new)
+                    .insert(from.to_string());
+            }
+            let Some(map) = event.as_object_mut() else {
+                return true;
+            };
+            map.remove("caps");
+            let Some(encoding) = map.remove("encoding") else {
+                return true;
+            };
+            if encoding.as_str() != Some(DEFLATE) {
+                eprintln!("Dropping message with unknown encoding {}", encoding);
+                return false;
+            }
+            match inflate(map.get("data").and_then(Value::as_str).unwrap_or_default()) {
+                Ok(data) => {
+                    map.insert("data".to_string(), Value::String(data));
+                    true
+                }
+                Err(e) => {
+                    eprintln!("Dropping compressed message: {}", e);
+                    false
+                }
+            }
+        }
+        _ => true,
+    }
+}
+
+/// Data to hand to the sidecar for a send, and its encoding. Compresses
+/// only above the threshold, when it actually saves space, and when the
+/// target (or, for a broadcast, every connected peer) supports it.
+pub(crate) fn encode_outbound(data: &str, target: Option<&str>) -> (String, Option<&'static str>) {
+    encode_with(&super::settings::current().compression, data, target)
+}
+
+fn encode_with(
+    settings: &CompressionSettings,
+    data: &str,
+    target: Option<&str>,
+) -> (String, Option<&'static str>) {
+    if !settings.enabled || data.len() < settings.threshold_bytes as usize {
+        return (data.to_string(), None);
+    }
+    let all_capable = match target {
+        Some(peer) => supports(peer),
+        None => CONNECTED
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .as_ref()
+            .is_some_and(|peers| !peers.is_empty() && peers.iter().all(|p| supports(p))),
+    };
+    if !all_capable {
+        return (data.to_string(), None);
+    }
+    match deflate(data) {
+        Some(compressed) if compressed.len() < data.len() => (compressed, Some(DEFLATE)),
+        _ => (data.to_string(), None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// About 8 KB of text that compresses like prose, well within MAX_RATIO.
+    fn large_text() -> String {
+        let words = [
+            "relay", "peer", "channel", "message", "sidecar", "node", "dial", "hole",
+        ];
+        let mut seed = 7u32;
+        (0..1200)
+            .map(|i| {
+                seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                format!("{}{}", words[(seed >> 16) as usize % words.len()], i % 97)
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    fn compressed_message(from: &str, data: &str) -> Value {
+        json!({"type": "message", "from": from, "encoding": DEFLATE, "data": deflate(data).unwrap()})
+    }
+
+    #[test]
+    fn deflated_data_inflates_back() {
+        let text = large_text();
+        let compressed = deflate(&text).unwrap();
+        assert!(compressed.len() < text.len());
+        assert_eq!(inflate(&compressed).unwrap(), text);
+    }
+
+    #[test]
+    fn inflation_is_capped_relative_to_the_input() {
+        // Zeros compress far better than MAX_RATIO
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&[b'0'; 1024 * 1024]).unwrap();
+        let bomb = base64::engine::general_purpose::STANDARD.encode(encoder.finish().unwrap());
+        assert!(inflate(&bomb).unwrap_err().contains("inflates past"));
+        assert!(inflate("not base64!").unwrap_err().contains("base64"));
+        let garbage = base64::engine::general_purpose::STANDARD.encode([0xff; 16]);
+        assert!(inflate(&garbage).is_err());
+    }
+
+    #[test]
+    fn inbound_messages_are_inflated_and_their_sender_learned() {
+        let text = large_text();
+        let mut event = compressed_message("peer-inbound", &text);
+        event["caps"] = json!([DEFLATE]);
+        assert!(admit(&mut event));
+        assert_eq!(event["data"], text.as_str());
+        assert!(event.get("encoding").is_none());
+        assert!(event.get("caps").is_none());
+        assert!(supports("peer-inbound"));
+    }
+
+    #[test]
+    fn undecodable_messages_are_dropped() {
+        let mut unknown = json!({"type": "message", "from": "p", "encoding": "zstd", "data": "x"});
+        assert!(!admit(&mut unknown));
+        let mut corrupt =
+            json!({"type": "message", "from": "p", "encoding": DEFLATE, "data": "%%"});
+        assert!(!admit(&mut corrupt));
+        let mut plain = json!({"type": "message", "from": "p", "data": "hi"});
+        assert!(admit(&mut plain));
+        assert!(!supports("p"));
+    }
+
+    #[test]
+    fn only_large_data_to_capable_peers_is_compressed() {
+        let settings = CompressionSettings::default();
+        let text = large_text();
+        let mut event = json!({"type": "message", "from": "peer-capable", "caps": [DEFLATE]});
+        admit(&mut event);
+
+        let (wire, encoding) = encode_with(&settings, &text, Some("peer-capable"));
+        assert_eq!(encoding, Some(DEFLATE));
+        assert_eq!(inflate(&wire).unwrap(), text);
+
+        assert_eq!(encode_with(&settings, &text, Some("peer-unknown")).1, None);
+        assert_eq!(
+            encode_with(&settings, "short", Some("peer-capable")).1,
+            None
+        );
+        // Receivers would refuse this as a bomb, so it goes uncompressed
+        let repetitive = "ha".repeat(64 * 1024);
+        assert_eq!(
+            encode_with(&settings, &repetitive, Some("peer-capable")).1,
+            None
+        );
+        let off = CompressionSettings {
+            enabled: false,
+            ..settings
+        };
+        assert_eq!(encode_with(&off, &text, Some("peer-capable")).1, None);
+    }
+
+    #[test]
+    fn broadcasts_are_compressed_only_if_every_peer_can_read_them() {
+        let settings = CompressionSettings::default();
+        let text = large_text();
+        for peer in ["peer-b1", "peer-b2"] {
+            admit(&mut json!({"type": "message", "from": peer, "caps": [DEFLATE]}));
+        }
+        admit(&mut json!({"type": "peer:connect", "peers": ["peer-b1", "peer-b2"]}));
+        assert_eq!(encode_with(&settings, &text, None).1, Some(DEFLATE));
+
+        admit(&mut json!({"type": "peer:connect", "peers": ["peer-b1", "peer-b3"]}));
+        assert_eq!(encode_with(&settings, &text, None).1, None);
+        admit(&mut json!({"type": "peer:disconnect", "peers": []}));
+        assert_eq!(encode_with(&settings, &text, None).1, None);
+    }
+}
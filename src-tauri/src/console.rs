@@ -0,0 +1,136 @@
+// Protocol console — a live view of the raw sidecar protocol for developers.
+// `console_subscribe` streams every line written to the sidecar's stdin and
+// read from its stdout as `protocol-trace` events until
+// `console_unsubscribe`; `console_send_raw` writes an arbitrary line.
+// Both need `developer.enabled`. With no subscriber, tracing is a single
+// atomic load per line.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde_json::{json, Value};
+
+use super::error::BridgeError;
+use super::sidecar::SidecarManager;
+
+/// Longest line sent in a trace event; the rest is cut off.
+const MAX_TRACE_CHARS: usize = 4096;
+const MASK: &str = "***";
+/// Keys whose values never leave the bridge, matched case-insensitively as
+/// substrings (so `privateKey` and `authToken` are both caught).
+const SENSITIVE_KEYS: &[&str] = &["privatekey", "secret", "passphrase", "password", "token"];
+
+static SUBSCRIBED: AtomicBool = AtomicBool::new(false);
+static SUBSCRIBER: Mutex<Option<tauri::AppHandle>> = Mutex::new(None);
+
+#[derive(Clone, Copy)]
+pub(crate) enum Direction {
+    Outbound,
+    Inbound,
+}
+
+fn is_sensitive(key: &str) -> bool {
+    let key = key.to_ascii_lowercase();
+    SENSITIVE_KEYS.iter().any(|s| key.contains(s))
+}
+
+/// Replace the values of sensitive keys, at any depth, with a mask.
+pub(crate) fn redact(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, v) in map.iter_mut() {
+                if is_sensitive(key) {
+                    *v = json!(MASK);
+                } else {
+                    redact(v);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(redact),
+        _ => {}
+    }
+}
+
+fn require_developer_mode() -> Result<(), BridgeError> {
+    if super::settings::current().developer.enabled {
+        Ok(())
+    } else {
+        Err(BridgeError::NotReady(
+            "The protocol console needs developer mode".to_string(),
+        ))
+    }
+}
+
+/// Called for every protocol line in either direction. `injected` marks
+/// lines written through `console_send_raw`.
+pub(crate) fn trace(direction: Direction, line: &str, injected: bool) {
+    if !SUBSCRIBED.load(Ordering::Relaxed) {
+        return;
+    }
+    let Some(app) = SUBSCRIBER.lock().unwrap_or_else(|e| e.into_inner()).clone() else {
+        return;
+    };
+    let text = match serde_json::from_str::<Value>(line) {
+        Ok(mut parsed) => {
+            redact(&mut parsed);
+            parsed.to_string()
+        }
+        Err(_) => line.to_string(),
+    };
+    let length = text.chars().count();
+    let line: String = text.chars().take(MAX_TRACE_CHARS).collect();
+    super::emit_p2p_event(
+        &app,
+        json!({
+            "type": "protocol-trace",
+            "direction": match direction {
+                Direction::Outbound => "out",
+                Direction::Inbound => "in",
+            },
+            "at": SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or(0),
+            "line": line,
+            "length": length,
+            "truncated": length > MAX_TRACE_CHARS,
+            "injected": injected,
+        }),
+    );
+}
+
+// ── Tauri commands ───────────────────────────────────────────────
+
+/// Start streaming protocol traffic as `protocol-trace` events.
+#[tauri::command]
+pub fn console_subscribe(app: tauri::AppHandle) -> Result<(), BridgeError> {
+    require_developer_mode()?;
+    *SUBSCRIBER.lock().unwrap_or_else(|e| e.into_inner()) = Some(app);
+    SUBSCRIBED.store(true, Ordering::SeqCst);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn console_unsubscribe() {
+    SUBSCRIBED.store(false, Ordering::SeqCst);
+    *SUBSCRIBER.lock().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+/// Write `json_line` to the sidecar's stdin as is. Nothing checks what it
+/// does — a malformed or hostile command can break the session or leak data.
+#[tauri::command]
+pub fn console_send_raw(
+    sidecar: tauri::State<'_, SidecarManager>,
+    json_line: String,
+) -> Result<(), BridgeError> {
+    require_developer_mode()?;
+    let line = json_line.trim();
+    if line.is_empty() || line.contains('\n') {
+        return Err(BridgeError::InvalidArgument(
+            "Expected exactly one line".to_string(),
+        ));
+    }
+    eprintln!("WARNING: raw protocol line sent from the console");
+    sidecar.write_line(line, true)
+}
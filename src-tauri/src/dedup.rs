@@ -0,0 +1,232 @@
+// Message ids and duplicate suppression.
+// Every outbound message gets a UUIDv7 id in `p2p_send` that travels on the
+// wire. Inbound messages are admitted once per id; messages from older peers
+// without one get a synthetic id from sender, timestamp and content. A
+// bounded recently-seen set drops redeliveries and peers echoing our own
+// messages back before they reach the frontend.
+
+use std::collections::hash_map::RandomState;
+use std::collections::{HashSet, VecDeque};
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Ids remembered for duplicate detection; the oldest are forgotten first.
+const RECENT_CAPACITY: usize = 4096;
+const MAX_ID_LEN: usize = 128;
+
+static RECENT: Mutex<Option<Recent>> = Mutex::new(None);
+static DUPLICATES_DROPPED: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Default)]
+struct Recent {
+    ids: HashSet<String>,
+    order: VecDeque<String>,
+}
+
+impl Recent {
+    /// Returns false if `id` was already present.
+    fn insert(&mut self, id: &str) -> bool {
+        if self.ids.contains(id) {
+            return false;
+        }
+        if self.order.len() == RECENT_CAPACITY {
+            if let Some(old) = self.order.pop_front() {
+                self.ids.remove(&old);
+            }
+        }
+        self.ids.insert(id.to_string());
+        self.order.push_back(id.to_string());
+        true
+    }
+}
+
+fn with_recent<T>(f: impl FnOnce(&mut Recent) -> T) -> T {
+    let mut guard = RECENT.lock().unwrap_or_else(|e| e.into_inner());
+    f(guard.get_or_insert_with(Recent::default))
+}
+
+/// A new UUIDv7: 48-bit millisecond timestamp, then random bits.
+pub(crate) fn new_message_id() -> String {
+    let ms = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0);
+    let mut b = [0u8; 16];
+    b[..6].copy_from_slice(&ms.to_be_bytes()[2..]);
+    if getrandom::getrandom(&mut b[6..]).is_err() {
+        // Fall back to the per-process random hasher keys
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u64(ms);
+        b[8..].copy_from_slice(&hasher.finish().to_le_bytes());
+    }
+    b[6] = (b[6] & 0x0f) | 0x70;
+    b[8] = (b[8] & 0x3f) | 0x80;
+    let hex: String = b.iter().map(|x| format!("{:02x}", x)).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+/// Remember an outbound id so a peer echoing it back is dropped.
+pub(crate) fn remember(id: &str) {
+    with_recent(|recent| recent.insert(id));
+}
+
+/// FNV-1a over `fields`, each followed by 0xff (which never occurs in
+/// UTF-8) so adjacent fields can't run together. Stable across platforms
+/// and Rust versions, unlike std's hashers; synthetic ids are stored in
+/// messages.db.
+fn stable_hash(fields: &[&[u8]]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for field in fields {
+        for byte in field.iter().chain(&[0xff]) {
+            hash ^= u64::from(*byte);
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    hash
+}
+
+/// Id for a message from a peer that doesn't send one. Deterministic, so
+/// every redelivery of the same message maps to the same id, in this and
+/// any later version.
+fn synthetic_id(from: &str, data: &str) -> String {
+    let parsed = serde_json::from_str::<serde_json::Value>(data).ok();
+    let hash = match parsed {
+        Some(ref msg) if msg.get("content").is_some() => {
+            let timestamp = msg["timestamp"]
+                .as_u64()
+                .map(|t| t.to_string())
+                .unwrap_or_default();
+            let content = msg["content"].as_str().unwrap_or_default();
+            stable_hash(&[
+                from.as_bytes(),
+                b"message",
+                timestamp.as_bytes(),
+                content.as_bytes(),
+            ])
+        }
+        _ => stable_hash(&[from.as_bytes(), b"raw", data.as_bytes()]),
+    };
+    format!("syn-{:016x}", hash)
+}
+
+/// Called by the stdout reader before anything else sees an event. Returns
+/// false for a duplicate message, which must be dropped. Admitted messages
+/// always carry an `id`.
+pub(crate) fn admit(event: &mut serde_json::Value) -> bool {
+    if event.get("type").and_then(|t| t.as_str()) != Some("message") {
+        return true;
+    }
+    let id = match event["id"].as_str() {
+        Some(id) if !id.is_empty() && id.len() <= MAX_ID_LEN => id.to_string(),
+        _ => synthetic_id(
+            event["from"].as_str().unwrap_or_default(),
+            event["data"].as_str().unwrap_or_default(),
+        ),
+    };
+    if !with_recent(|recent| recent.insert(&id)) {
+        DUPLICATES_DROPPED.fetch_add(1, Ordering::Relaxed);
+        return false;
+    }
+    event["id"] = serde_json::Value::String(id);
+    true
+}
+
+/// Duplicate messages dropped since the app started.
+pub(crate) fn duplicates_dropped() -> u64 {
+    DUPLICATES_DROPPED.load(Ordering::Relaxed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn message_ids_are_uuid_v7() {
+        let id = new_message_id();
+        let parts: Vec<&str> = id.split('-').collect();
+        assert_eq!(
+            parts.iter().map(|p| p.len()).collect::<Vec<_>>(),
+            [8, 4, 4, 4, 12]
+        );
+        assert!(parts[2].starts_with('7'));
+        assert!(matches!(parts[3].as_bytes()[0], b'8' | b'9' | b'a' | b'b'));
+        assert_ne!(id, new_message_id());
+    }
+
+    #[test]
+    fn recent_forgets_the_oldest_id_at_capacity() {
+        let mut recent = Recent::default();
+        for i in 0..RECENT_CAPACITY {
+            assert!(recent.insert(&i.to_string()));
+        }
+        assert!(!recent.insert("0"));
+        assert!(recent.insert("new"));
+        assert!(recent.insert("0"));
+        assert_eq!(recent.ids.len(), RECENT_CAPACITY);
+    }
+
+    #[test]
+    fn synthetic_ids_ignore_formatting_but_not_content() {
+        let a = synthetic_id("peer", r#"{"content":"hi","timestamp":5}"#);
+        let b = synthetic_id("peer", r#"{ "timestamp": 5, "content": "hi" }"#);
+        assert_eq!(a, b);
+        assert!(a.starts_with("syn-"));
+        assert_ne!(
+            a,
+            synthetic_id("other", r#"{"content":"hi","timestamp":5}"#)
+        );
+        assert_ne!(a, synthetic_id("peer", r#"{"content":"hi","timestamp":6}"#));
+    }
+
+    #[test]
+    fn synthetic_ids_are_pinned() {
+        // Stored in messages.db: a change here breaks dedup of history
+        assert_eq!(
+            synthetic_id("peer", r#"{"content":"hi","timestamp":5}"#),
+            "syn-ac48a11d683718ea"
+        );
+        assert_eq!(synthetic_id("peer", "not json"), "syn-067e86ffb8962d11");
+    }
+
+    #[test]
+    fn redelivered_and_echoed_messages_are_dropped() {
+        let mut first =
+            json!({"type": "message", "from": "p-dedup", "data": "x", "id": "dedup-test-1"});
+        let mut again = first.clone();
+        assert!(admit(&mut first));
+        assert!(!admit(&mut again));
+
+        remember("dedup-test-echo");
+        let mut echo =
+            json!({"type": "message", "from": "p-dedup", "data": "x", "id": "dedup-test-echo"});
+        assert!(!admit(&mut echo));
+
+        // Other events pass untouched
+        let mut other = json!({"type": "peer:connect", "id": "dedup-test-1"});
+        assert!(admit(&mut other));
+    }
+
+    #[test]
+    fn messages_without_a_usable_id_get_a_synthetic_one() {
+        let data = r#"{"content":"dedup-test","timestamp":1}"#;
+        let mut event = json!({"type": "message", "from": "p-dedup-syn", "id": "", "data": data});
+        assert!(admit(&mut event));
+        assert_eq!(event["id"], synthetic_id("p-dedup-syn", data));
+
+        let long = "x".repeat(MAX_ID_LEN + 1);
+        let mut event =
+            json!({"type": "message", "from": "p-dedup-long", "id": long, "data": data});
+        assert!(admit(&mut event));
+        assert!(event["id"].as_str().unwrap().starts_with("syn-"));
+    }
+}
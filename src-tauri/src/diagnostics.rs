@@ -0,0 +1,704 @@
+// Connectivity diagnostics — "why can't we connect?" check.
+// Runs a fixed sequence of probes (local addresses, STUN NAT detection, relay
+// reachability, a relay round-trip through the sidecar) within one overall
+// time budget and produces a structured report with plain-language
+// conclusions. Steps the budget doesn't reach are reported as skipped.
+
+use std::collections::hash_map::RandomState;
+use std::fs;
+use std::hash::{BuildHasher, Hasher};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream, ToSocketAddrs, UdpSocket};
+use std::sync::{mpsc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+
+use super::error::BridgeError;
+use super::operations::OpHandle;
+use super::protocol::SidecarCommand;
+
+// Same STUN servers the sidecar hands to WebRTC, so the NAT mapping we observe
+// is the one peers will see.
+const STUN_SERVERS: [&str; 2] = ["stun.l.google.com:19302", "stun1.l.google.com:19302"];
+
+// Relay endpoints (kept in sync with RELAY_WS_ADDR / RELAY_HTTP_URL in the sidecar)
+const RELAY_ENDPOINTS: [(&str, &str); 2] = [
+    ("concord-relay.fly.dev:443", "WebSocket transport"),
+    ("concord-relay.fly.dev:8080", "invite code API"),
+];
+
+const DNS_TIMEOUT: Duration = Duration::from_secs(3);
+const STUN_TIMEOUT: Duration = Duration::from_millis(2500);
+const TCP_TIMEOUT: Duration = Duration::from_secs(4);
+const SIDECAR_DIAG_TIMEOUT: Duration = Duration::from_secs(12);
+/// The whole check, all steps together.
+const CHECK_BUDGET: Duration = Duration::from_secs(30);
+/// A step isn't started with less time than this left.
+const MIN_STEP_BUDGET: Duration = Duration::from_secs(1);
+const REPORT_FILE: &str = "connectivity-report.json";
+
+const STUN_MAGIC_COOKIE: u32 = 0x2112_A442;
+
+static LAST_READY: Mutex<Option<serde_json::Value>> = Mutex::new(None);
+
+// ── Report types ─────────────────────────────────────────────────
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum StepStatus {
+    Pass,
+    Warn,
+    Fail,
+    Skipped,
+}
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CheckStep {
+    pub id: &'static str,
+    pub label: &'static str,
+    pub status: StepStatus,
+    pub duration_ms: u64,
+    pub detail: String,
+    pub data: serde_json::Value,
+}
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectivityReport {
+    pub started_at: u64,
+    pub duration_ms: u64,
+    pub steps: Vec<CheckStep>,
+    pub conclusions: Vec<String>,
+}
+
+enum NatType {
+    None,
+    Cone,
+    Symmetric,
+    Unknown,
+    UdpBlocked,
+}
+
+// ── Sidecar event tap ────────────────────────────────────────────
+
+/// Called by the stdout reader for every parsed sidecar event.
+pub(crate) fn observe_event(event: &serde_json::Value) {
+    if event.get("type").and_then(|t| t.as_str()) == Some("ready") {
+        if let Ok(mut guard) = LAST_READY.lock() {
+            *guard = Some(event.clone());
+        }
+    }
+}
+
+// ── Helpers ──────────────────────────────────────────────────────
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// `cap`, or less if `deadline` comes first.
+fn within(deadline: Instant, cap: Duration) -> Duration {
+    cap.min(deadline.saturating_duration_since(Instant::now()))
+}
+
+/// `to_socket_addrs` has no timeout of its own, so resolve on a helper thread.
+fn resolve(host_port: &str, timeout: Duration) -> Result<Vec<SocketAddr>, String> {
+    if timeout.is_zero() {
+        return Err(format!("No time left to look up {}", host_port));
+    }
+    let (tx, rx) = mpsc::channel();
+    let target = host_port.to_string();
+    thread::spawn(move || {
+        let _ = tx.send(target.to_socket_addrs().map(|a| a.collect::<Vec<_>>()));
+    });
+    match rx.recv_timeout(timeout) {
+        Ok(Ok(addrs)) if !addrs.is_empty() => Ok(addrs),
+        Ok(Ok(_)) => Err(format!("{} resolved to no addresses", host_port)),
+        Ok(Err(e)) => Err(format!("DNS lookup for {} failed: {}", host_port, e)),
+        Err(_) => Err(format!("DNS lookup for {} timed out", host_port)),
+    }
+}
+
+/// The address the OS would use for outbound traffic. Connecting a UDP
+/// socket only selects a route — no packet is sent.
+fn primary_local_ip() -> Option<IpAddr> {
+    let socket = UdpSocket::bind("0.0.0.0:0").ok()?;
+    socket.connect("8.8.8.8:80").ok()?;
+    let ip = socket.local_addr().ok()?.ip();
+    if ip.is_unspecified() {
+        None
+    } else {
+        Some(ip)
+    }
+}
+
+fn is_private(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => v4.is_private() || v4.is_loopback() || v4.is_link_local(),
+        IpAddr::V6(v6) => v6.is_loopback() || (v6.segments()[0] & 0xfe00) == 0xfc00,
+    }
+}
+
+fn transaction_id() -> [u8; 12] {
+    let mut id = [0u8; 12];
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(now_ms());
+    let a = hasher.finish();
+    hasher.write_u64(a);
+    let b = hasher.finish();
+    id[..8].copy_from_slice(&a.to_be_bytes());
+    id[8..].copy_from_slice(&b.to_be_bytes()[..4]);
+    id
+}
+
+/// Send a STUN Binding Request (RFC 5389) and return the reflexive address.
+fn stun_binding(
+    socket: &UdpSocket,
+    server: SocketAddr,
+    timeout: Duration,
+) -> Result<SocketAddr, String> {
+    let tid = transaction_id();
+    let mut request = Vec::with_capacity(20);
+    request.extend_from_slice(&0x0001u16.to_be_bytes());
+    request.extend_from_slice(&0u16.to_be_bytes());
+    request.extend_from_slice(&STUN_MAGIC_COOKIE.to_be_bytes());
+    request.extend_from_slice(&tid);
+
+    let deadline = Instant::now() + timeout;
+    let mut buf = [0u8; 512];
+    // One retransmit halfway through, since a single UDP datagram is easily lost
+    for attempt in 0..2 {
+        socket
+            .send_to(&request, server)
+            .map_err(|e| format!("send to {}: {}", server, e))?;
+        let attempt_deadline = if attempt == 0 {
+            Instant::now() + timeout / 2
+        } else {
+            deadline
+        };
+        loop {
+            let remaining = attempt_deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                break;
+            }
+            let _ = socket.set_read_timeout(Some(remaining));
+            match socket.recv_from(&mut buf) {
+                Ok((len, from)) if from == server => {
+                    if let Some(addr) = parse_binding_response(&buf[..len], &tid) {
+                        return Ok(addr);
+                    }
+                }
+                Ok(_) => continue,
+                Err(_) => break,
+            }
+        }
+    }
+    Err(format!("no STUN response from {}", server))
+}
+
+fn parse_binding_response(msg: &[u8], tid: &[u8; 12]) -> Option<SocketAddr> {
+    if msg.len() < 20 || msg[0..2] != [0x01, 0x01] || &msg[8..20] != tid {
+        return None;
+    }
+    let body_len = u16::from_be_bytes([msg[2], msg[3]]) as usize;
+    let body = msg.get(20..20 + body_len)?;
+    let mut fallback = None;
+    let mut i = 0;
+    while i + 4 <= body.len() {
+        let attr = u16::from_be_bytes([body[i], body[i + 1]]);
+        let len = u16::from_be_bytes([body[i + 2], body[i + 3]]) as usize;
+        let value = body.get(i + 4..i + 4 + len)?;
+        match attr {
+            0x0020 => return decode_address(value, true, tid),
+            0x0001 => fallback = decode_address(value, false, tid),
+            _ => {}
+        }
+        i += 4 + ((len + 3) & !3);
+    }
+    fallback
+}
+
+fn decode_address(value: &[u8], xored: bool, tid: &[u8; 12]) -> Option<SocketAddr> {
+    if value.len() < 8 {
+        return None;
+    }
+    let cookie = STUN_MAGIC_COOKIE.to_be_bytes();
+    let mut port = u16::from_be_bytes([value[2], value[3]]);
+    if xored {
+        port ^= (STUN_MAGIC_COOKIE >> 16) as u16;
+    }
+    match value[1] {
+        0x01 => {
+            let mut octets = [value[4], value[5], value[6], value[7]];
+            if xored {
+                for (o, c) in octets.iter_mut().zip(cookie.iter()) {
+                    *o ^= c;
+                }
+            }
+            Some(SocketAddr::new(IpAddr::V4(Ipv4Addr::from(octets)), port))
+        }
+        0x02 if value.len() >= 20 => {
+            let mut octets = [0u8; 16];
+            octets.copy_from_slice(&value[4..20]);
+            if xored {
+                let mut mask = [0u8; 16];
+                mask[..4].copy_from_slice(&cookie);
+                mask[4..].copy_from_slice(tid);
+                for (o, m) in octets.iter_mut().zip(mask.iter()) {
+                    *o ^= m;
+                }
+            }
+            Some(SocketAddr::new(IpAddr::V6(Ipv6Addr::from(octets)), port))
+        }
+        _ => None,
+    }
+}
+
+// ── Steps ────────────────────────────────────────────────────────
+
+struct StepOutcome {
+    status: StepStatus,
+    detail: String,
+    data: serde_json::Value,
+}
+
+fn outcome(status: StepStatus, detail: impl Into<String>, data: serde_json::Value) -> StepOutcome {
+    StepOutcome {
+        status,
+        detail: detail.into(),
+        data,
+    }
+}
+
+fn check_local_addresses() -> (StepOutcome, Option<IpAddr>) {
+    let local = primary_local_ip();
+    let ready = LAST_READY.lock().ok().and_then(|g| g.clone());
+    let data = serde_json::json!({
+        "primaryIp": local.map(|ip| ip.to_string()),
+        "sidecarAddress": ready.as_ref().and_then(|r| r.get("address").cloned()),
+        "sidecarLanAddress": ready.as_ref().and_then(|r| r.get("lanAddress").cloned()),
+        "listenPort": ready.as_ref().and_then(|r| r.get("port").cloned()),
+    });
+    let result = match local {
+        None => outcome(
+            StepStatus::Fail,
+            "No usable network interface — this machine appears to be offline.",
+            data,
+        ),
+        Some(ip) if is_private(&ip) => outcome(
+            StepStatus::Pass,
+            format!("Local address {} (private network, behind a router).", ip),
+            data,
+        ),
+        Some(ip) => outcome(
+            StepStatus::Pass,
+            format!("Local address {} (public).", ip),
+            data,
+        ),
+    };
+    (result, local)
+}
+
+fn check_nat(local: Option<IpAddr>, budget: Duration) -> (StepOutcome, NatType) {
+    let deadline = Instant::now() + budget;
+    let socket = match UdpSocket::bind("0.0.0.0:0") {
+        Ok(s) => s,
+        Err(e) => {
+            return (
+                outcome(
+                    StepStatus::Fail,
+                    format!("Could not open a UDP socket: {}", e),
+                    serde_json::Value::Null,
+                ),
+                NatType::Unknown,
+            )
+        }
+    };
+
+    let mut mapped = Vec::new();
+    let mut errors = Vec::new();
+    for server in STUN_SERVERS {
+        let addr = resolve(server, within(deadline, DNS_TIMEOUT)).and_then(|addrs| {
+            addrs
+                .into_iter()
+                .find(|a| a.is_ipv4())
+                .ok_or_else(|| format!("{} has no IPv4 address", server))
+        });
+        match addr.and_then(|a| stun_binding(&socket, a, within(deadline, STUN_TIMEOUT))) {
+            Ok(m) => mapped.push(m),
+            Err(e) => errors.push(e),
+        }
+    }
+
+    let data = serde_json::json!({
+        "mappedAddresses": mapped.iter().map(|a| a.to_string()).collect::<Vec<_>>(),
+        "errors": errors,
+    });
+    let nat = match (mapped.first(), mapped.get(1)) {
+        (None, _) => NatType::UdpBlocked,
+        (Some(a), _) if Some(a.ip()) == local => NatType::None,
+        (Some(a), Some(b)) if a == b => NatType::Cone,
+        (Some(_), Some(_)) => NatType::Symmetric,
+        (Some(_), None) => NatType::Unknown,
+    };
+    let result = match nat {
+        NatType::None => outcome(StepStatus::Pass, "No NAT detected.", data),
+        NatType::Cone => outcome(
+            StepStatus::Pass,
+            format!(
+                "Endpoint-independent NAT (public address {}).",
+                mapped[0].ip()
+            ),
+            data,
+        ),
+        NatType::Symmetric => outcome(
+            StepStatus::Warn,
+            "Symmetric NAT — the public port changes per destination.",
+            data,
+        ),
+        NatType::Unknown => outcome(
+            StepStatus::Warn,
+            "Only one STUN server answered; NAT type could not be determined.",
+            data,
+        ),
+        NatType::UdpBlocked => outcome(
+            StepStatus::Fail,
+            "No STUN server answered — outbound UDP appears to be blocked.",
+            data,
+        ),
+    };
+    (result, nat)
+}
+
+fn check_relays(budget: Duration) -> StepOutcome {
+    let deadline = Instant::now() + budget;
+    let mut results = Vec::new();
+    let mut reachable = 0;
+    for (endpoint, purpose) in RELAY_ENDPOINTS {
+        let started = Instant::now();
+        let res = resolve(endpoint, within(deadline, DNS_TIMEOUT)).and_then(|addrs| {
+            let mut last_err = String::new();
+            for addr in addrs {
+                let timeout = within(deadline, TCP_TIMEOUT);
+                if timeout.is_zero() {
+                    last_err = format!("{}: no time left to connect", addr);
+                    break;
+                }
+                match TcpStream::connect_timeout(&addr, timeout) {
+                    Ok(_) => return Ok(addr),
+                    Err(e) => last_err = format!("{}: {}", addr, e),
+                }
+            }
+            Err(last_err)
+        });
+        let ok = res.is_ok();
+        if ok {
+            reachable += 1;
+        }
+        results.push(serde_json::json!({
+            "endpoint": endpoint,
+            "purpose": purpose,
+            "reachable": ok,
+            "ms": started.elapsed().as_millis() as u64,
+            "error": res.err(),
+        }));
+    }
+    let data = serde_json::json!({ "endpoints": results });
+    if reachable == RELAY_ENDPOINTS.len() {
+        outcome(StepStatus::Pass, "Relay is reachable.", data)
+    } else if reachable == 0 {
+        outcome(
+            StepStatus::Fail,
+            "Relay is unreachable — a firewall or proxy may be blocking it.",
+            data,
+        )
+    } else {
+        outcome(StepStatus::Warn, "Relay is only partially reachable.", data)
+    }
+}
+
+fn check_relay_roundtrip(app: &tauri::AppHandle, budget: Duration) -> StepOutcome {
+    if !super::sidecar::manager(app).is_running() {
+        return outcome(
+            StepStatus::Skipped,
+            "P2P node is not running.",
+            serde_json::Value::Null,
+        );
+    }
+    let data = match super::request_sidecar_event(
+        app,
+        &SidecarCommand::Diagnose,
+        "diagnose_result",
+        SIDECAR_DIAG_TIMEOUT.min(budget),
+    ) {
+        Ok(d) => d,
+        Err(e) => {
+            return outcome(
+                StepStatus::Fail,
+                format!("P2P node did not answer the diagnostic request ({}).", e),
+                serde_json::Value::Null,
+            )
+        }
+    };
+
+    let connected = data["relayConnected"].as_bool().unwrap_or(false);
+    let reserved = data["relayReservation"].as_bool().unwrap_or(false);
+    let resolves = data["inviteCodeResolves"].as_bool();
+    if !connected {
+        outcome(
+            StepStatus::Fail,
+            "P2P node is not connected to the relay.",
+            data,
+        )
+    } else if !reserved {
+        outcome(
+            StepStatus::Fail,
+            "Connected to the relay, but no relay reservation — peers cannot reach you through it.",
+            data,
+        )
+    } else if resolves == Some(true) {
+        outcome(
+            StepStatus::Pass,
+            "Your invite code resolves back to this node through the relay.",
+            data,
+        )
+    } else {
+        outcome(
+            StepStatus::Warn,
+            "Relay reservation is active, but your invite code did not resolve back to this node.",
+            data,
+        )
+    }
+}
+
+/// A step the overall budget didn't reach.
+fn out_of_time() -> StepOutcome {
+    outcome(
+        StepStatus::Skipped,
+        "Not run: the check ran out of time.",
+        serde_json::json!({ "reason": "out-of-time" }),
+    )
+}
+
+fn ran_out_of_time(step: &CheckStep) -> bool {
+    step.status == StepStatus::Skipped && step.data["reason"] == "out-of-time"
+}
+
+fn conclusions(steps: &[CheckStep], nat: &NatType) -> Vec<String> {
+    let status = |id: &str| steps.iter().find(|s| s.id == id).map(|s| s.status);
+    let mut out = Vec::new();
+
+    if status("local-addresses") == Some(StepStatus::Fail) {
+        out.push("You appear to be offline. Check your network connection first.".to_string());
+        return out;
+    }
+    match nat {
+        NatType::None | NatType::Cone => out.push(
+            "Your NAT allows direct connections; most peers should be able to connect to you directly."
+                .to_string(),
+        ),
+        NatType::Symmetric => out.push(
+            "You are behind a symmetric NAT; direct connections will usually fail unless the other peer has an open NAT."
+                .to_string(),
+        ),
+        NatType::UdpBlocked => out.push(
+            "Outbound UDP is blocked; WebRTC connections will fail. Try a different network or ask your administrator to allow UDP."
+                .to_string(),
+        ),
+        NatType::Unknown => {}
+    }
+    if status("relay-reachability") == Some(StepStatus::Fail) {
+        out.push(
+            "The relay cannot be reached, so invite codes will not work. A firewall, proxy, or DNS filter is likely blocking it."
+                .to_string(),
+        );
+    }
+    let roundtrip = steps.iter().find(|s| s.id == "relay-roundtrip");
+    match status("relay-roundtrip") {
+        Some(StepStatus::Fail) => out.push(
+            "Peers cannot currently find you through the relay. Restarting the P2P node may help."
+                .to_string(),
+        ),
+        Some(StepStatus::Skipped) if !roundtrip.is_some_and(ran_out_of_time) => {
+            out.push("The P2P node is not running; start it and run the check again.".to_string())
+        }
+        _ => {}
+    }
+    if steps.iter().any(ran_out_of_time) {
+        out.push(
+            "The network was too slow to finish every step in time; the skipped steps were not checked."
+                .to_string(),
+        );
+    }
+    if out.is_empty() {
+        out.push("No connectivity problems detected.".to_string());
+    }
+    out
+}
+
+// ── Orchestration ────────────────────────────────────────────────
+
+fn run_check(app: &tauri::AppHandle, op: &OpHandle) -> Result<ConnectivityReport, String> {
+    const TOTAL: usize = 4;
+    let started_at = now_ms();
+    let started = Instant::now();
+    let deadline = started + CHECK_BUDGET;
+    let mut steps = Vec::with_capacity(TOTAL);
+    let mut nat = NatType::Unknown;
+    let mut local = None;
+
+    let plan: [(&'static str, &'static str); TOTAL] = [
+        ("local-addresses", "Local addresses"),
+        ("nat-type", "NAT type"),
+        ("relay-reachability", "Relay reachability"),
+        ("relay-roundtrip", "Relay round-trip"),
+    ];
+    for (index, (id, label)) in plan.into_iter().enumerate() {
+        op.checkpoint()?;
+        op.progress(id, index, TOTAL, None);
+        let step_started = Instant::now();
+        let budget = deadline.saturating_duration_since(step_started);
+        let result = match id {
+            _ if budget < MIN_STEP_BUDGET => out_of_time(),
+            "local-addresses" => {
+                let (r, ip) = check_local_addresses();
+                local = ip;
+                r
+            }
+            "nat-type" => {
+                let (r, n) = check_nat(local, budget);
+                nat = n;
+                r
+            }
+            "relay-reachability" => check_relays(budget),
+            _ => check_relay_roundtrip(app, budget),
+        };
+        let step = CheckStep {
+            id,
+            label,
+            status: result.status,
+            duration_ms: step_started.elapsed().as_millis() as u64,
+            detail: result.detail,
+            data: result.data,
+        };
+        op.progress(id, index + 1, TOTAL, serde_json::to_value(&step).ok());
+        steps.push(step);
+    }
+
+    let conclusions = conclusions(&steps, &nat);
+    Ok(ConnectivityReport {
+        started_at,
+        duration_ms: started.elapsed().as_millis() as u64,
+        steps,
+        conclusions,
+    })
+}
+
+// ── Tauri commands ───────────────────────────────────────────────
+
+/// Run the connectivity check as a `connectivity-check` operation; each
+/// step's result arrives as the `detail` of its `operation-progress` event.
+/// The final report is also saved for the diagnostics export.
+#[tauri::command]
+pub async fn run_connectivity_check(
+    app: tauri::AppHandle,
+) -> Result<ConnectivityReport, BridgeError> {
+    let handle = app.clone();
+    let report = tauri::async_runtime::spawn_blocking(move || {
+        let op = super::operations::start(&handle, "connectivity-check", "Connectivity check");
+        let result = run_check(&handle, &op);
+        op.finish(result.map_err(BridgeError::from))
+    })
+    .await
+    .map_err(|e| format!("Connectivity check failed: {}", e))??;
+
+    if let Ok(json) = serde_json::to_string_pretty(&report) {
+        // Falls back to the temp dir when the data directory is unusable
+        let dir = super::storage::writable_dir(super::app_data_dir().ok());
+        if let Err(e) = fs::write(dir.join(REPORT_FILE), &json) {
+            super::storage::note_failure("Cannot write connectivity report", &e);
+            let _ = fs::write(super::storage::writable_dir(None).join(REPORT_FILE), json);
+        }
+    }
+    super::emit_p2p_event(
+        &app,
+        serde_json::json!({"type": "connectivity-report", "report": &report}),
+    );
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn step(id: &'static str, result: StepOutcome) -> CheckStep {
+        CheckStep {
+            id,
+            label: id,
+            status: result.status,
+            duration_ms: 0,
+            detail: result.detail,
+            data: result.data,
+        }
+    }
+
+    #[test]
+    fn xor_mapped_address_is_decoded() {
+        let tid = [7u8; 12];
+        let mut msg = vec![0x01, 0x01, 0, 12];
+        msg.extend_from_slice(&STUN_MAGIC_COOKIE.to_be_bytes());
+        msg.extend_from_slice(&tid);
+        // XOR-MAPPED-ADDRESS, IPv4, 203.0.113.5:40000
+        msg.extend_from_slice(&[0x00, 0x20, 0, 8, 0, 0x01]);
+        msg.extend_from_slice(&(40000u16 ^ (STUN_MAGIC_COOKIE >> 16) as u16).to_be_bytes());
+        for (octet, c) in [203u8, 0, 113, 5]
+            .iter()
+            .zip(STUN_MAGIC_COOKIE.to_be_bytes())
+        {
+            msg.push(octet ^ c);
+        }
+        assert_eq!(
+            parse_binding_response(&msg, &tid),
+            Some("203.0.113.5:40000".parse().unwrap())
+        );
+        // Another transaction's response is ignored
+        assert_eq!(parse_binding_response(&msg, &[8u8; 12]), None);
+    }
+
+    #[test]
+    fn steps_get_no_more_than_the_deadline_allows() {
+        let now = Instant::now();
+        assert!(within(now + Duration::from_secs(60), DNS_TIMEOUT) <= DNS_TIMEOUT);
+        assert!(
+            within(now + Duration::from_millis(500), DNS_TIMEOUT) <= Duration::from_millis(500)
+        );
+        assert_eq!(within(now, DNS_TIMEOUT), Duration::ZERO);
+        assert!(resolve("localhost:80", Duration::ZERO).is_err());
+    }
+
+    #[test]
+    fn skipped_for_time_is_not_reported_as_a_stopped_node() {
+        let steps = [
+            step(
+                "local-addresses",
+                outcome(StepStatus::Pass, "", serde_json::Value::Null),
+            ),
+            step("relay-roundtrip", out_of_time()),
+        ];
+        let out = conclusions(&steps, &NatType::Unknown);
+        assert!(out.iter().any(|c| c.contains("too slow")));
+        assert!(!out.iter().any(|c| c.contains("not running")));
+
+        let steps = [step(
+            "relay-roundtrip",
+            outcome(StepStatus::Skipped, "", serde_json::Value::Null),
+        )];
+        let out = conclusions(&steps, &NatType::Unknown);
+        assert!(out.iter().any(|c| c.contains("not running")));
+    }
+}
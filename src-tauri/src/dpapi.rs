@@ -0,0 +1,177 @@
+// DPAPI — encrypt small secrets at rest, bound to the current Windows user.
+// Used for identity backups and stored passphrases. Elsewhere secrets are
+// sealed with XChaCha20-Poly1305 under a random key kept in secrets.key in
+// the data root, readable only by the user. That protects a sealed file
+// copied on its own, not one read together with the data directory.
+
+#[cfg(windows)]
+use std::ptr;
+
+#[cfg(windows)]
+use windows_sys::Win32::Foundation::LocalFree;
+#[cfg(windows)]
+use windows_sys::Win32::Security::Cryptography::{
+    CryptProtectData, CryptUnprotectData, CRYPTPROTECT_UI_FORBIDDEN, CRYPT_INTEGER_BLOB,
+};
+
+#[cfg(windows)]
+fn transform(input: &[u8], protect: bool) -> Result<Vec<u8>, String> {
+    let data_in = CRYPT_INTEGER_BLOB {
+        cbData: input.len() as u32,
+        pbData: input.as_ptr() as *mut u8,
+    };
+    let mut data_out = CRYPT_INTEGER_BLOB {
+        cbData: 0,
+        pbData: ptr::null_mut(),
+    };
+    // SAFETY: data_in points at `input` for the duration of the call, and
+    // data_out is allocated by the OS and released with LocalFree below.
+    let ok = unsafe {
+        if protect {
+            CryptProtectData(
+                &data_in,
+                ptr::null(),
+                ptr::null(),
+                ptr::null(),
+                ptr::null(),
+                CRYPTPROTECT_UI_FORBIDDEN,
+                &mut data_out,
+            )
+        } else {
+            CryptUnprotectData(
+                &data_in,
+                ptr::null_mut(),
+                ptr::null(),
+                ptr::null(),
+                ptr::null(),
+                CRYPTPROTECT_UI_FORBIDDEN,
+                &mut data_out,
+            )
+        }
+    };
+    if ok == 0 {
+        return Err(std::io::Error::last_os_error().to_string());
+    }
+    let out = unsafe {
+        let out = std::slice::from_raw_parts(data_out.pbData, data_out.cbData as usize).to_vec();
+        LocalFree(data_out.pbData as _);
+        out
+    };
+    Ok(out)
+}
+
+#[cfg(windows)]
+pub fn protect(plain: &[u8]) -> Result<Vec<u8>, String> {
+    transform(plain, true)
+}
+
+#[cfg(windows)]
+pub fn unprotect(sealed: &[u8]) -> Result<Vec<u8>, String> {
+    transform(sealed, false)
+}
+
+#[cfg(not(windows))]
+use chacha20poly1305::aead::{Aead, KeyInit};
+#[cfg(not(windows))]
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+
+#[cfg(not(windows))]
+const KEY_FILE: &str = "secrets.key";
+#[cfg(not(windows))]
+const KEY_LEN: usize = 32;
+#[cfg(not(windows))]
+const NONCE_LEN: usize = 24;
+
+/// The local sealing key, created on first use.
+#[cfg(not(windows))]
+fn local_key() -> Result<[u8; KEY_LEN], String> {
+    use std::fs;
+    use std::io::{ErrorKind, Write};
+
+    let path = super::data_root()?.join(KEY_FILE);
+    loop {
+        match fs::read(&path) {
+            Ok(bytes) => {
+                return bytes
+                    .try_into()
+                    .map_err(|_| format!("{} is corrupt", KEY_FILE))
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => {}
+            Err(e) => return Err(format!("Cannot read {}: {}", KEY_FILE, e)),
+        }
+        let mut key = [0u8; KEY_LEN];
+        getrandom::getrandom(&mut key).map_err(|e| e.to_string())?;
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        match options.open(&path) {
+            Ok(mut file) => {
+                file.write_all(&key)
+                    .map_err(|e| format!("Cannot write {}: {}", KEY_FILE, e))?;
+                return Ok(key);
+            }
+            // Another thread created it first; use theirs
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(format!("Cannot create {}: {}", KEY_FILE, e)),
+        }
+    }
+}
+
+/// `nonce | ciphertext` of `plain` under `key`.
+#[cfg(not(windows))]
+fn seal_with(key: &[u8; KEY_LEN], plain: &[u8]) -> Result<Vec<u8>, String> {
+    let mut nonce = [0u8; NONCE_LEN];
+    getrandom::getrandom(&mut nonce).map_err(|e| e.to_string())?;
+    let cipher = XChaCha20Poly1305::new(Key::from_slice(key));
+    let sealed = cipher
+        .encrypt(XNonce::from_slice(&nonce), plain)
+        .map_err(|_| "Encryption failed".to_string())?;
+    let mut out = nonce.to_vec();
+    out.extend(sealed);
+    Ok(out)
+}
+
+#[cfg(not(windows))]
+fn open_with(key: &[u8; KEY_LEN], sealed: &[u8]) -> Result<Vec<u8>, String> {
+    if sealed.len() < NONCE_LEN {
+        return Err("Sealed data is truncated".to_string());
+    }
+    let (nonce, data) = sealed.split_at(NONCE_LEN);
+    XChaCha20Poly1305::new(Key::from_slice(key))
+        .decrypt(XNonce::from_slice(nonce), data)
+        .map_err(|_| "Sealed with a different key or corrupt".to_string())
+}
+
+#[cfg(not(windows))]
+pub fn protect(plain: &[u8]) -> Result<Vec<u8>, String> {
+    seal_with(&local_key()?, plain)
+}
+
+#[cfg(not(windows))]
+pub fn unprotect(sealed: &[u8]) -> Result<Vec<u8>, String> {
+    open_with(&local_key()?, sealed)
+}
+
+#[cfg(all(test, not(windows)))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sealed_data_opens_with_the_same_key_only() {
+        let key = [7u8; KEY_LEN];
+        let sealed = seal_with(&key, b"secret").unwrap();
+        assert_ne!(&sealed[NONCE_LEN..], b"secret");
+        assert_eq!(open_with(&key, &sealed).unwrap(), b"secret");
+        assert!(open_with(&[8u8; KEY_LEN], &sealed).is_err());
+    }
+
+    #[test]
+    fn tampered_or_truncated_data_is_rejected() {
+        let key = [7u8; KEY_LEN];
+        let mut sealed = seal_with(&key, b"secret").unwrap();
+        *sealed.last_mut().unwrap() ^= 1;
+        assert!(open_with(&key, &sealed).is_err());
+        assert!(open_with(&key, &sealed[..NONCE_LEN - 1]).is_err());
+    }
+}
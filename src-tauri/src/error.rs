@@ -0,0 +1,234 @@
+// Bridge errors — what every command rejects with. Serialised as
+// `{ code, message, details }`; `code` is stable and meant for matching,
+// `message` is for people and may be reworded at any time.
+// Errors that haven't been classified yet (a few internal helpers still
+// return strings) arrive as `unclassified` with the old message unchanged.
+
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+use serde_json::{json, Value};
+
+#[derive(Debug, thiserror::Error)]
+pub enum BridgeError {
+    #[error("Sidecar not running")]
+    SidecarNotRunning,
+    #[error("Timed out waiting for {0}")]
+    Timeout(String),
+    #[error("Invalid address: {0}")]
+    InvalidAddress(String),
+    /// The operation needs something that isn't available yet or right now
+    /// (safe mode, a running switch, an ephemeral session, ...).
+    #[error("{0}")]
+    NotReady(String),
+    #[error("{context}: {source}")]
+    Io {
+        context: String,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("{0}")]
+    InvalidArgument(String),
+    #[error("{0}")]
+    NotFound(String),
+    /// The user declined an approval prompt, or it expired unanswered.
+    #[error("{0}")]
+    Denied(String),
+    /// The operation was cancelled through `cancel_operation`.
+    #[error("Cancelled")]
+    Cancelled,
+    /// A channel's send policy forbids the message; `rule` is the rule type.
+    #[error("{message}")]
+    PolicyViolation { rule: String, message: String },
+    /// The sidecar could not connect to the address.
+    #[error("{message}")]
+    DialFailed { address: String, message: String },
+    /// The sidecar is starting and too many commands are already waiting
+    /// for it; this one was dropped, not queued.
+    #[error("Too many commands waiting for the sidecar")]
+    QueueFull,
+    /// The payload is bigger than the bridge will send; sizes are in bytes.
+    #[error("Payload is {size} bytes; the limit is {limit}")]
+    PayloadTooLarge { size: u64, limit: u64 },
+    /// The operation ran too recently; try again after `retry_after_ms`.
+    #[error("Too soon; try again in {} s", retry_after_ms.div_ceil(1000))]
+    RateLimited { retry_after_ms: u64 },
+    #[error("{0}")]
+    Unclassified(String),
+}
+
+impl BridgeError {
+    pub fn io(context: impl Into<String>, source: std::io::Error) -> Self {
+        Self::Io {
+            context: context.into(),
+            source,
+        }
+    }
+
+    /// Stable identifier; never change an existing one.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::SidecarNotRunning => "sidecar-not-running",
+            Self::Timeout(_) => "timeout",
+            Self::InvalidAddress(_) => "invalid-address",
+            Self::NotReady(_) => "not-ready",
+            Self::Io { .. } => "io",
+            Self::InvalidArgument(_) => "invalid-argument",
+            Self::NotFound(_) => "not-found",
+            Self::Denied(_) => "approval-denied",
+            Self::Cancelled => "cancelled",
+            Self::PolicyViolation { .. } => "policy-violation",
+            Self::DialFailed { .. } => "dial-failed",
+            Self::QueueFull => "queue-full",
+            Self::PayloadTooLarge { .. } => "payload-too-large",
+            Self::RateLimited { .. } => "rate-limited",
+            Self::Unclassified(_) => "unclassified",
+        }
+    }
+
+    fn details(&self) -> Value {
+        match self {
+            Self::Timeout(waiting_for) => json!({ "waitingFor": waiting_for }),
+            Self::InvalidAddress(address) => json!({ "address": address }),
+            Self::PolicyViolation { rule, .. } => json!({ "rule": rule }),
+            Self::DialFailed { address, .. } => json!({ "address": address }),
+            Self::PayloadTooLarge { size, limit } => json!({ "size": size, "limit": limit }),
+            Self::RateLimited { retry_after_ms } => json!({ "retryAfterMs": retry_after_ms }),
+            Self::Io { context, source } => {
+                json!({ "context": context, "kind": format!("{:?}", source.kind()) })
+            }
+            _ => Value::Null,
+        }
+    }
+}
+
+impl Serialize for BridgeError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut s = serializer.serialize_struct("BridgeError", 3)?;
+        s.serialize_field("code", self.code())?;
+        s.serialize_field("message", &self.to_string())?;
+        s.serialize_field("details", &self.details())?;
+        s.end()
+    }
+}
+
+// Until every helper returns a BridgeError, string errors convert both ways.
+
+impl From<String> for BridgeError {
+    fn from(message: String) -> Self {
+        Self::Unclassified(message)
+    }
+}
+
+impl From<&str> for BridgeError {
+    fn from(message: &str) -> Self {
+        Self::Unclassified(message.to_string())
+    }
+}
+
+impl From<BridgeError> for String {
+    fn from(error: BridgeError) -> Self {
+        error.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// One of every variant. The match makes a new variant a compile error
+    /// here until its wire format is added to `wire_format_is_stable`.
+    fn every_variant() -> Vec<BridgeError> {
+        let all = vec![
+            BridgeError::SidecarNotRunning,
+            BridgeError::Timeout("ready".to_string()),
+            BridgeError::InvalidAddress("/ip4/x".to_string()),
+            BridgeError::NotReady("Safe mode".to_string()),
+            BridgeError::io(
+                "Cannot write config.json",
+                std::io::Error::new(std::io::ErrorKind::PermissionDenied, "denied"),
+            ),
+            BridgeError::InvalidArgument("Bad name".to_string()),
+            BridgeError::NotFound("No such profile".to_string()),
+            BridgeError::Denied("Declined".to_string()),
+            BridgeError::Cancelled,
+            BridgeError::PolicyViolation {
+                rule: "read-only".to_string(),
+                message: "Channel is read-only".to_string(),
+            },
+            BridgeError::DialFailed {
+                address: "/ip4/1.2.3.4/tcp/1".to_string(),
+                message: "Connection refused".to_string(),
+            },
+            BridgeError::QueueFull,
+            BridgeError::PayloadTooLarge {
+                size: 2048,
+                limit: 1024,
+            },
+            BridgeError::RateLimited {
+                retry_after_ms: 1500,
+            },
+            BridgeError::Unclassified("Something else".to_string()),
+        ];
+        for error in &all {
+            match error {
+                BridgeError::SidecarNotRunning
+                | BridgeError::Timeout(_)
+                | BridgeError::InvalidAddress(_)
+                | BridgeError::NotReady(_)
+                | BridgeError::Io { .. }
+                | BridgeError::InvalidArgument(_)
+                | BridgeError::NotFound(_)
+                | BridgeError::Denied(_)
+                | BridgeError::Cancelled
+                | BridgeError::PolicyViolation { .. }
+                | BridgeError::DialFailed { .. }
+                | BridgeError::QueueFull
+                | BridgeError::PayloadTooLarge { .. }
+                | BridgeError::RateLimited { .. }
+                | BridgeError::Unclassified(_) => {}
+            }
+        }
+        all
+    }
+
+    #[test]
+    fn wire_format_is_stable() {
+        let expected = [
+            json!({"code": "sidecar-not-running", "message": "Sidecar not running", "details": null}),
+            json!({"code": "timeout", "message": "Timed out waiting for ready",
+                   "details": {"waitingFor": "ready"}}),
+            json!({"code": "invalid-address", "message": "Invalid address: /ip4/x",
+                   "details": {"address": "/ip4/x"}}),
+            json!({"code": "not-ready", "message": "Safe mode", "details": null}),
+            json!({"code": "io", "message": "Cannot write config.json: denied",
+                   "details": {"context": "Cannot write config.json", "kind": "PermissionDenied"}}),
+            json!({"code": "invalid-argument", "message": "Bad name", "details": null}),
+            json!({"code": "not-found", "message": "No such profile", "details": null}),
+            json!({"code": "approval-denied", "message": "Declined", "details": null}),
+            json!({"code": "cancelled", "message": "Cancelled", "details": null}),
+            json!({"code": "policy-violation", "message": "Channel is read-only",
+                   "details": {"rule": "read-only"}}),
+            json!({"code": "dial-failed", "message": "Connection refused",
+                   "details": {"address": "/ip4/1.2.3.4/tcp/1"}}),
+            json!({"code": "queue-full", "message": "Too many commands waiting for the sidecar",
+                   "details": null}),
+            json!({"code": "payload-too-large", "message": "Payload is 2048 bytes; the limit is 1024",
+                   "details": {"size": 2048, "limit": 1024}}),
+            json!({"code": "rate-limited", "message": "Too soon; try again in 2 s",
+                   "details": {"retryAfterMs": 1500}}),
+            json!({"code": "unclassified", "message": "Something else", "details": null}),
+        ];
+        let all = every_variant();
+        assert_eq!(all.len(), expected.len());
+        for (error, expected) in all.iter().zip(expected) {
+            assert_eq!(serde_json::to_value(error).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn strings_convert_to_unclassified_and_back() {
+        let error = BridgeError::from("No identity");
+        assert_eq!(error.code(), "unclassified");
+        assert_eq!(String::from(error), "No identity");
+    }
+}
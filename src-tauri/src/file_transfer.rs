@@ -0,0 +1,235 @@
+// File transfer — `p2p_send_file` streams a file to a channel or peer as a
+// `file_offer`, one `file_chunk` per CHUNK_BYTES (base64, so every command
+// stays a single protocol line) and a `file_complete` carrying the CRC-32
+// of the content. Every chunk is its own write, so chat messages go out
+// between chunks instead of waiting for the whole file.
+//
+// Transfers run as operations (kind `file-transfer`) and also emit
+// `file-progress` with their transfer id. `p2p_cancel_transfer` stops one
+// at its next chunk and sends `file_abort`. Chunks are written straight to
+// the running sidecar, never queued in the outbox: a sidecar that stops
+// mid-transfer fails it, since its replacement never saw the offer.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+use std::sync::Mutex;
+
+use base64::Engine;
+use serde::Serialize;
+
+use super::error::BridgeError;
+use super::operations::OpHandle;
+use super::protocol::SidecarCommand;
+
+const CHUNK_BYTES: usize = 64 * 1024;
+const MAX_FILE_BYTES: u64 = 100 * 1024 * 1024;
+
+/// Running transfers: transfer id → operation id.
+static TRANSFERS: Mutex<Option<HashMap<String, String>>> = Mutex::new(None);
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileSent {
+    pub transfer_id: String,
+    pub name: String,
+    pub size: u64,
+    /// `crc32:` and the CRC-32 of the content in hex, as sent in
+    /// `file_complete`.
+    pub checksum: String,
+}
+
+fn transfers<T>(f: impl FnOnce(&mut HashMap<String, String>) -> T) -> T {
+    let mut transfers = TRANSFERS.lock().unwrap_or_else(|e| e.into_inner());
+    f(transfers.get_or_insert_with(HashMap::new))
+}
+
+struct Transfer<'a> {
+    app: &'a tauri::AppHandle,
+    op: &'a OpHandle,
+    id: String,
+    channel_id: String,
+    target_peer_id: Option<String>,
+}
+
+impl Transfer<'_> {
+    fn progress(&self, sent: u64, total: u64) {
+        self.op
+            .progress("sending", sent as usize, total as usize, None);
+        super::emit_p2p_event(
+            self.app,
+            serde_json::json!({
+                "type": "file-progress",
+                "transferId": self.id,
+                "sent": sent,
+                "total": total,
+            }),
+        );
+    }
+
+    fn write(&self, cmd: &SidecarCommand) -> Result<(), BridgeError> {
+        super::write_to_running_sidecar(self.app, cmd)
+    }
+
+    /// Best effort: tell the receivers to drop what they got.
+    fn abort(&self) {
+        let _ = self.write(&SidecarCommand::FileAbort {
+            transfer_id: self.id.clone(),
+            channel_id: self.channel_id.clone(),
+            target_peer_id: self.target_peer_id.clone(),
+        });
+    }
+
+    fn send(&self, path: &Path, name: String, size: u64) -> Result<FileSent, BridgeError> {
+        let mut file = fs::File::open(path).map_err(|e| BridgeError::io("Cannot open file", e))?;
+        let generation = super::supervisor::generation();
+        let chunks = size.div_ceil(CHUNK_BYTES as u64);
+        self.write(&SidecarCommand::FileOffer {
+            transfer_id: self.id.clone(),
+            channel_id: self.channel_id.clone(),
+            target_peer_id: self.target_peer_id.clone(),
+            name: name.clone(),
+            size,
+            chunks,
+        })?;
+        self.progress(0, size);
+
+        let mut crc = flate2::Crc::new();
+        let mut buf = vec![0u8; CHUNK_BYTES];
+        let mut sent = 0u64;
+        let mut last_percent = 0;
+        for index in 0..chunks {
+            if let Err(e) = self.op.checkpoint() {
+                self.abort();
+                return Err(e.into());
+            }
+            // Never more than announced, should the file grow meanwhile
+            let want = CHUNK_BYTES.min((size - sent) as usize);
+            let n = read_chunk(&mut file, &mut buf[..want]).map_err(|e| {
+                self.abort();
+                BridgeError::io("Cannot read file", e)
+            })?;
+            if n == 0 {
+                self.abort();
+                return Err("The file got shorter while it was being sent".into());
+            }
+            if super::supervisor::generation() != generation {
+                return Err("The sidecar stopped during the transfer".into());
+            }
+            crc.update(&buf[..n]);
+            self.write(&SidecarCommand::FileChunk {
+                transfer_id: self.id.clone(),
+                channel_id: self.channel_id.clone(),
+                target_peer_id: self.target_peer_id.clone(),
+                index,
+                data: base64::engine::general_purpose::STANDARD.encode(&buf[..n]),
+            })?;
+            sent += n as u64;
+            // At most one progress event per percent
+            let percent = sent * 100 / size;
+            if percent > last_percent || sent == size {
+                last_percent = percent;
+                self.progress(sent, size);
+            }
+        }
+
+        let checksum = format!("crc32:{:08x}", crc.sum());
+        self.write(&SidecarCommand::FileComplete {
+            transfer_id: self.id.clone(),
+            channel_id: self.channel_id.clone(),
+            target_peer_id: self.target_peer_id.clone(),
+            checksum: checksum.clone(),
+        })?;
+        Ok(FileSent {
+            transfer_id: self.id.clone(),
+            name,
+            size,
+            checksum,
+        })
+    }
+}
+
+/// Fill `buf` unless the file ends first; returns the bytes read.
+fn read_chunk(file: &mut fs::File, buf: &mut [u8]) -> std::io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match file.read(&mut buf[filled..])? {
+            0 => break,
+            n => filled += n,
+        }
+    }
+    Ok(filled)
+}
+
+// ── Tauri commands ───────────────────────────────────────────────
+
+/// Send a file to a channel, or only to `target_peer_id`, and resolve once
+/// the last chunk is written to the sidecar. The transfer id arrives with
+/// the first `file-progress` event. Rejects with `invalid-argument` for
+/// anything but a non-empty regular file, with `payload-too-large` above
+/// MAX_FILE_BYTES, with `policy-violation` when the channel's send policy
+/// forbids it (see send_policy.rs), and with `cancelled` after
+/// `p2p_cancel_transfer`.
+#[tauri::command]
+pub async fn p2p_send_file(
+    app: tauri::AppHandle,
+    channel_id: String,
+    file_path: String,
+    target_peer_id: Option<String>,
+) -> Result<FileSent, BridgeError> {
+    let path = Path::new(&file_path).to_path_buf();
+    let meta = fs::metadata(&path).map_err(|e| BridgeError::io("Cannot read file", e))?;
+    if !meta.is_file() {
+        return Err(BridgeError::InvalidArgument(format!(
+            "{} is not a file",
+            file_path
+        )));
+    }
+    if meta.len() == 0 {
+        return Err(BridgeError::InvalidArgument(format!(
+            "{} is empty",
+            file_path
+        )));
+    }
+    if meta.len() > MAX_FILE_BYTES {
+        return Err(BridgeError::PayloadTooLarge {
+            size: meta.len(),
+            limit: MAX_FILE_BYTES,
+        });
+    }
+    super::send_policy::check_file(&app, &channel_id, meta.len()).await?;
+    if !super::sidecar::manager(&app).is_running() {
+        return Err(BridgeError::SidecarNotRunning);
+    }
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "file".to_string());
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let op = super::operations::start(&app, "file-transfer", &format!("Send {}", name));
+        let transfer = Transfer {
+            app: &app,
+            op: &op,
+            id: super::dedup::new_message_id(),
+            channel_id,
+            target_peer_id,
+        };
+        transfers(|t| t.insert(transfer.id.clone(), op.id().to_string()));
+        let result = transfer.send(&path, name, meta.len());
+        transfers(|t| t.remove(&transfer.id));
+        op.finish(result)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Stop a running transfer at its next chunk; its `p2p_send_file` rejects
+/// with `cancelled`.
+#[tauri::command]
+pub fn p2p_cancel_transfer(app: tauri::AppHandle, transfer_id: String) -> Result<(), BridgeError> {
+    let op_id = transfers(|t| t.get(&transfer_id).cloned())
+        .ok_or_else(|| BridgeError::NotFound(format!("No transfer '{}'", transfer_id)))?;
+    super::operations::cancel_operation(app, op_id)
+}
@@ -0,0 +1,372 @@
+// Flood protection — per-peer inbound rate tracking. A peer that keeps
+// exceeding the message rate, repeats the same content, or sends oversized
+// payloads builds up a violation score. Past a threshold its messages are
+// queued and released in small batches, they skip attention cues and
+// screen-reader announcements, and `peer-flagged-flooding` reports why.
+// The score decays, so a peer that calms down is released on its own.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde_json::{json, Value};
+
+use super::settings::FloodSettings;
+
+const MAX_TRACKED_PEERS: usize = 1024;
+/// Held messages per throttled peer; the oldest are dropped first.
+const MAX_QUEUED_PER_PEER: usize = 256;
+const OVERSIZED_BYTES: usize = 64 * 1024;
+/// Score at which a peer is throttled; it is released below half of this.
+const THROTTLE_SCORE: f64 = 10.0;
+const SCORE_HALF_LIFE: Duration = Duration::from_secs(30);
+const RELEASE_INTERVAL: Duration = Duration::from_secs(5);
+const RELEASE_BATCH: usize = 5;
+
+static PEERS: Mutex<Option<HashMap<String, PeerState>>> = Mutex::new(None);
+static RELEASER_STARTED: AtomicBool = AtomicBool::new(false);
+
+struct PeerState {
+    tokens: f64,
+    score: f64,
+    updated: Instant,
+    last_content: u64,
+    repeats: u32,
+    received: u64,
+    rate_violations: u64,
+    repeat_violations: u64,
+    oversized: u64,
+    throttled: bool,
+    queue: VecDeque<Value>,
+    dropped: u64,
+}
+
+impl PeerState {
+    fn new(settings: &FloodSettings, now: Instant) -> Self {
+        Self {
+            tokens: settings.burst as f64,
+            score: 0.0,
+            updated: now,
+            last_content: 0,
+            repeats: 0,
+            received: 0,
+            rate_violations: 0,
+            repeat_violations: 0,
+            oversized: 0,
+            throttled: false,
+            queue: VecDeque::new(),
+            dropped: 0,
+        }
+    }
+
+    /// Refill the rate bucket and decay the score for the time since the
+    /// last update.
+    fn advance(&mut self, settings: &FloodSettings, now: Instant) {
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+        self.tokens =
+            (self.tokens + elapsed * settings.messages_per_sec as f64).min(settings.burst as f64);
+        self.score *= 0.5f64.powf(elapsed / SCORE_HALF_LIFE.as_secs_f64());
+        self.updated = now;
+    }
+
+    /// Count one inbound message against the peer. Returns true if this
+    /// message got it throttled.
+    fn record(
+        &mut self,
+        settings: &FloodSettings,
+        hash: u64,
+        oversized: bool,
+        now: Instant,
+    ) -> bool {
+        self.advance(settings, now);
+        self.received += 1;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+        } else {
+            self.rate_violations += 1;
+            self.score += 1.0;
+        }
+        if hash == self.last_content {
+            self.repeats += 1;
+            if self.repeats >= settings.max_repeats {
+                self.repeat_violations += 1;
+                self.score += 1.0;
+            }
+        } else {
+            self.last_content = hash;
+            self.repeats = 1;
+        }
+        if oversized {
+            self.oversized += 1;
+            self.score += 2.0;
+        }
+
+        let newly_flagged = !self.throttled && self.score >= THROTTLE_SCORE;
+        self.throttled |= newly_flagged;
+        newly_flagged
+    }
+
+    fn stats(&self, peer_id: &str) -> Value {
+        json!({
+            "peerId": peer_id,
+            "received": self.received,
+            "rateViolations": self.rate_violations,
+            "repeatViolations": self.repeat_violations,
+            "oversized": self.oversized,
+            "queued": self.queue.len(),
+            "dropped": self.dropped,
+        })
+    }
+}
+
+/// Hash of the message text, so resends with fresh ids and timestamps still
+/// count as repeats.
+fn content_hash(data: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    match serde_json::from_str::<Value>(data) {
+        Ok(msg) if msg["content"].is_string() => msg["content"].as_str().hash(&mut hasher),
+        _ => data.hash(&mut hasher),
+    }
+    hasher.finish()
+}
+
+fn with_peers<T>(f: impl FnOnce(&mut HashMap<String, PeerState>) -> T) -> T {
+    let mut guard = PEERS.lock().unwrap_or_else(|e| e.into_inner());
+    f(guard.get_or_insert_with(HashMap::new))
+}
+
+/// Make room for a new peer by forgetting the longest-idle unthrottled one.
+fn evict_one(peers: &mut HashMap<String, PeerState>) {
+    let idle = peers
+        .iter()
+        .filter(|(_, p)| !p.throttled)
+        .min_by_key(|(_, p)| p.updated)
+        .map(|(id, _)| id.clone());
+    if let Some(id) = idle {
+        peers.remove(&id);
+    }
+}
+
+/// Called by the stdout reader after dedup. Returns the event if it should
+/// carry on as usual, or None if it was queued because its sender is being
+/// throttled.
+pub(crate) fn admit(app: &tauri::AppHandle, event: Value) -> Option<Value> {
+    match event["type"].as_str() {
+        Some("message") => {}
+        Some("peer:disconnect") => {
+            // Keep a throttled peer's state so reconnecting doesn't reset it
+            if let Some(peer_id) = event["peerId"].as_str() {
+                with_peers(|peers| {
+                    if peers.get(peer_id).is_some_and(|p| !p.throttled) {
+                        peers.remove(peer_id);
+                    }
+                });
+            }
+            return Some(event);
+        }
+        _ => return Some(event),
+    }
+    let settings = super::settings::current().flood;
+    if !settings.enabled {
+        return Some(event);
+    }
+    let Some(from) = event["from"].as_str().map(str::to_string) else {
+        return Some(event);
+    };
+    let data = event["data"].as_str().unwrap_or_default();
+    let hash = content_hash(data);
+    let oversized = data.len() > OVERSIZED_BYTES;
+    let now = Instant::now();
+
+    let flagged = with_peers(|peers| {
+        if !peers.contains_key(&from) && peers.len() >= MAX_TRACKED_PEERS {
+            evict_one(peers);
+        }
+        let peer = peers
+            .entry(from.clone())
+            .or_insert_with(|| PeerState::new(&settings, now));
+        let newly_flagged = peer.record(&settings, hash, oversized, now);
+        if !peer.throttled {
+            return Err(event);
+        }
+        if peer.queue.len() == MAX_QUEUED_PER_PEER {
+            peer.queue.pop_front();
+            peer.dropped += 1;
+        }
+        peer.queue.push_back(event);
+        Ok(newly_flagged.then(|| peer.stats(&from)))
+    });
+
+    match flagged {
+        Err(event) => Some(event),
+        Ok(stats) => {
+            if let Some(mut stats) = stats {
+                eprintln!("Throttling flooding peer {}", from);
+                stats["type"] = json!("peer-flagged-flooding");
+                super::emit_p2p_event(app, stats);
+                start_releaser(app);
+            }
+            None
+        }
+    }
+}
+
+/// Every RELEASE_INTERVAL, emit a few held messages per throttled peer and
+/// release peers whose score has decayed.
+fn start_releaser(app: &tauri::AppHandle) {
+    if RELEASER_STARTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    let app = app.clone();
+    thread::spawn(move || loop {
+        thread::sleep(RELEASE_INTERVAL);
+        release_due(&app);
+    });
+}
+
+/// Turning flood protection off releases held messages right away rather
+/// than at the next interval. A settings subscriber.
+pub(crate) fn settings_changed(app: &tauri::AppHandle) {
+    if !super::settings::current().flood.enabled {
+        release_due(app);
+    }
+}
+
+/// Emit a batch of held messages per throttled peer, and everything held
+/// for peers that have calmed down.
+fn release_due(app: &tauri::AppHandle) {
+    let settings = super::settings::current().flood;
+    let now = Instant::now();
+    let mut released = Vec::new();
+    let mut cleared = Vec::new();
+    with_peers(|peers| {
+        for (peer_id, peer) in peers.iter_mut().filter(|(_, p)| p.throttled) {
+            peer.advance(&settings, now);
+            let calm = peer.score < THROTTLE_SCORE / 2.0 || !settings.enabled;
+            let batch = if calm {
+                peer.queue.len()
+            } else {
+                RELEASE_BATCH.min(peer.queue.len())
+            };
+            released.extend(peer.queue.drain(..batch));
+            if calm {
+                peer.throttled = false;
+                cleared.push(json!({
+                    "type": "peer-flooding-ended",
+                    "peerId": peer_id,
+                    "dropped": peer.dropped,
+                }));
+                peer.dropped = 0;
+            }
+        }
+    });
+    for event in released {
+        // Held messages skipped the screening that follows flood protection
+        let Some(event) = super::message_requests::admit(app, event) else {
+            continue;
+        };
+        super::sequence::observe_event(app, &event);
+        super::emit_p2p_event(app, event);
+    }
+    for event in cleared {
+        super::emit_p2p_event(app, event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings() -> FloodSettings {
+        FloodSettings::default()
+    }
+
+    #[test]
+    fn a_burst_within_the_allowance_is_not_penalised() {
+        let settings = settings();
+        let now = Instant::now();
+        let mut peer = PeerState::new(&settings, now);
+        for i in 0..settings.burst {
+            assert!(!peer.record(&settings, i as u64, false, now));
+        }
+        assert_eq!(peer.rate_violations, 0);
+        assert_eq!(peer.score, 0.0);
+    }
+
+    #[test]
+    fn sustained_flooding_gets_the_peer_throttled_once() {
+        let settings = settings();
+        let now = Instant::now();
+        let mut peer = PeerState::new(&settings, now);
+        let mut flagged = 0;
+        for i in 0..settings.burst as u64 + THROTTLE_SCORE as u64 + 5 {
+            flagged += peer.record(&settings, i, false, now) as u32;
+        }
+        assert_eq!(flagged, 1);
+        assert!(peer.throttled);
+        assert!(peer.rate_violations >= THROTTLE_SCORE as u64);
+    }
+
+    #[test]
+    fn repeats_and_oversized_payloads_add_to_the_score() {
+        let settings = settings();
+        let now = Instant::now();
+        let mut peer = PeerState::new(&settings, now);
+        for _ in 0..settings.max_repeats + 1 {
+            peer.record(&settings, 7, false, now);
+        }
+        assert_eq!(peer.repeat_violations, 2);
+        peer.record(&settings, 8, true, now);
+        assert_eq!(peer.oversized, 1);
+        assert_eq!(peer.score, 4.0);
+        assert_eq!(peer.repeats, 1);
+    }
+
+    #[test]
+    fn the_score_halves_and_tokens_refill_over_time() {
+        let settings = settings();
+        let start = Instant::now();
+        let mut peer = PeerState::new(&settings, start);
+        peer.tokens = 0.0;
+        peer.score = 8.0;
+        peer.advance(&settings, start + SCORE_HALF_LIFE);
+        assert!((peer.score - 4.0).abs() < 1e-9);
+        assert_eq!(peer.tokens, settings.burst as f64);
+    }
+
+    #[test]
+    fn resends_of_the_same_text_hash_alike() {
+        let a = content_hash(r#"{"content":"buy now","timestamp":1,"id":"a"}"#);
+        let b = content_hash(r#"{"content":"buy now","timestamp":2,"id":"b"}"#);
+        assert_eq!(a, b);
+        assert_ne!(a, content_hash(r#"{"content":"hello"}"#));
+        assert_eq!(content_hash("raw"), content_hash("raw"));
+    }
+
+    #[test]
+    fn eviction_spares_throttled_peers() {
+        let settings = settings();
+        let start = Instant::now();
+        let mut peers = HashMap::new();
+        let mut oldest = PeerState::new(&settings, start);
+        oldest.throttled = true;
+        peers.insert("throttled".to_string(), oldest);
+        peers.insert(
+            "idle".to_string(),
+            PeerState::new(&settings, start + Duration::from_secs(1)),
+        );
+        peers.insert(
+            "active".to_string(),
+            PeerState::new(&settings, start + Duration::from_secs(2)),
+        );
+        evict_one(&mut peers);
+        assert!(peers.contains_key("throttled"));
+        assert!(!peers.contains_key("idle"));
+        assert!(peers.contains_key("active"));
+    }
+}
@@ -0,0 +1,358 @@
+// Focus sessions — a time box with no badge, taskbar flashes or
+// announcements. Meanwhile the bridge counts messages per conversation and
+// collects mentions; when the session ends (on time or via
+// `end_focus_session`) it emits one `focus-digest` and flashes the taskbar
+// once. The session persists in focus.json, so a restart resumes it with
+// its remaining time, and messages are counted by id so nothing is counted
+// twice.
+
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::error::BridgeError;
+
+const FOCUS_FILE: &str = "focus.json";
+const EXPIRY_POLL: Duration = Duration::from_secs(5);
+const MAX_DURATION_SECS: u64 = 24 * 60 * 60;
+const MAX_MENTIONS: usize = 100;
+
+/// Session of the active profile, if one is running.
+static SESSION: Mutex<Option<(PathBuf, Option<FocusSession>)>> = Mutex::new(None);
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default, rename_all = "camelCase")]
+struct FocusSession {
+    started_at: u64,
+    ends_at: u64,
+    /// Messages per conversation.
+    conversations: BTreeMap<String, u32>,
+    mentions: Vec<Mention>,
+    /// Ids already counted; redeliveries after a restart are skipped.
+    counted: HashSet<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Mention {
+    pub conversation_id: String,
+    pub from: String,
+    pub message_id: String,
+    pub received_at: u64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FocusStatus {
+    pub started_at: u64,
+    pub ends_at: u64,
+}
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ConversationCount {
+    pub conversation_id: String,
+    pub count: u32,
+}
+
+/// What happened during a session. Message text is left out; the UI can
+/// look messages up by id.
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct FocusDigest {
+    pub started_at: u64,
+    pub ended_at: u64,
+    pub ended_early: bool,
+    pub total: u32,
+    pub conversations: Vec<ConversationCount>,
+    pub mentions: Vec<Mention>,
+}
+
+impl FocusSession {
+    /// Count a message event; false if its id was already counted.
+    fn record(&mut self, event: &Value, mention: bool, now: u64) -> bool {
+        let id = event["id"].as_str().unwrap_or_default().to_string();
+        if !self.counted.insert(id.clone()) {
+            return false;
+        }
+        let channel = event["channelId"].as_str().unwrap_or_default().to_string();
+        *self.conversations.entry(channel.clone()).or_default() += 1;
+        if mention && self.mentions.len() < MAX_MENTIONS {
+            self.mentions.push(Mention {
+                conversation_id: channel,
+                from: event["from"].as_str().unwrap_or_default().to_string(),
+                message_id: id,
+                received_at: event["receivedAt"].as_u64().unwrap_or(now),
+            });
+        }
+        true
+    }
+
+    /// Busiest conversations first.
+    fn into_digest(self, ended_at: u64, ended_early: bool) -> FocusDigest {
+        let mut conversations: Vec<ConversationCount> = self
+            .conversations
+            .into_iter()
+            .map(|(conversation_id, count)| ConversationCount {
+                conversation_id,
+                count,
+            })
+            .collect();
+        conversations.sort_by_key(|c| Reverse(c.count));
+        FocusDigest {
+            started_at: self.started_at,
+            ended_at,
+            ended_early,
+            total: conversations.iter().map(|c| c.count).sum(),
+            conversations,
+            mentions: self.mentions,
+        }
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+fn load(path: &Path) -> Option<FocusSession> {
+    let s = super::storage::read_to_string(path).ok()?;
+    serde_json::from_str(&s)
+        .map_err(|e| eprintln!("Ignoring malformed {}: {}", path.display(), e))
+        .ok()
+}
+
+fn save(path: &Path, session: &Option<FocusSession>) -> Result<(), BridgeError> {
+    let json = serde_json::to_string(session).map_err(|e| e.to_string())?;
+    super::storage::write(path, json).map_err(|e| BridgeError::io("Cannot save focus session", e))
+}
+
+/// Run `f` on the active profile's session, loading it after a profile switch.
+fn with_session<T>(
+    f: impl FnOnce(&Path, &mut Option<FocusSession>) -> T,
+) -> Result<T, BridgeError> {
+    let path = super::app_data_dir()?.join(FOCUS_FILE);
+    let mut guard = SESSION.lock().unwrap_or_else(|e| e.into_inner());
+    if !matches!(*guard, Some((ref cached, _)) if *cached == path) {
+        *guard = Some((path.clone(), load(&path)));
+    }
+    let Some((_, ref mut session)) = *guard else {
+        return Err("Focus state unavailable".into());
+    };
+    Ok(f(&path, session))
+}
+
+/// Whether a focus session is running; it silences the badge and every
+/// attention cue.
+pub(crate) fn active() -> bool {
+    let now = now_ms();
+    with_session(|_, session| session.as_ref().is_some_and(|s| s.ends_at > now)).unwrap_or(false)
+}
+
+/// Called by the stdout reader for every event while it is on its way to
+/// the frontend.
+pub(crate) fn observe_event(event: &Value) {
+    if event["type"].as_str() != Some("message") {
+        return;
+    }
+    let now = now_ms();
+    let result = with_session(|path, session| {
+        let Some(s) = session.as_mut().filter(|s| s.ends_at > now) else {
+            return Ok(());
+        };
+        let mention = super::attention::is_mention(event["data"].as_str().unwrap_or_default());
+        if !s.record(event, mention, now) {
+            return Ok(());
+        }
+        save(path, session)
+    });
+    if let Err(e) = result.and_then(|r| r) {
+        eprintln!("{}", e);
+    }
+}
+
+/// End the session, if any, and return its digest.
+fn finish(ended_early: bool) -> Result<Option<FocusDigest>, BridgeError> {
+    with_session(|path, session| {
+        let Some(s) = session.take() else {
+            return Ok(None);
+        };
+        save(path, session)?;
+        let ended_at = if ended_early { now_ms() } else { s.ends_at };
+        Ok(Some(s.into_digest(ended_at, ended_early)))
+    })?
+}
+
+fn deliver(app: &tauri::AppHandle, digest: &FocusDigest) {
+    let mut event = serde_json::to_value(digest).unwrap_or_default();
+    event["type"] = serde_json::json!("focus-digest");
+    super::emit_p2p_event(app, event);
+    if digest.total > 0 {
+        super::attention::flash_once(app);
+    }
+}
+
+/// End sessions as they expire, including one that expired while the app
+/// was closed. Sleeping first gives the frontend time to mount before a
+/// digest from the previous run arrives.
+pub(crate) fn start_timer(app: tauri::AppHandle) {
+    thread::spawn(move || loop {
+        thread::sleep(EXPIRY_POLL);
+        let now = now_ms();
+        let expired = with_session(|_, session| session.as_ref().is_some_and(|s| s.ends_at <= now))
+            .unwrap_or(false);
+        if expired {
+            match finish(false) {
+                Ok(Some(digest)) => deliver(&app, &digest),
+                Ok(None) => {}
+                Err(e) => eprintln!("{}", e),
+            }
+        }
+    });
+}
+
+// ── Tauri commands ───────────────────────────────────────────────
+
+/// Start a focus session of `duration_secs`. Starting one while another is
+/// running changes when it ends and keeps what it has collected.
+#[tauri::command]
+pub fn start_focus_session(duration_secs: u64) -> Result<FocusStatus, BridgeError> {
+    if !(1..=MAX_DURATION_SECS).contains(&duration_secs) {
+        return Err(BridgeError::InvalidArgument(format!(
+            "Focus duration must be between 1 and {} seconds",
+            MAX_DURATION_SECS
+        )));
+    }
+    let now = now_ms();
+    with_session(|path, session| {
+        let s = session.get_or_insert_with(|| FocusSession {
+            started_at: now,
+            ..Default::default()
+        });
+        s.ends_at = now + duration_secs * 1000;
+        let status = FocusStatus {
+            started_at: s.started_at,
+            ends_at: s.ends_at,
+        };
+        save(path, session).map(|()| status)
+    })?
+}
+
+/// End the running session now; its digest is emitted and returned.
+#[tauri::command]
+pub fn end_focus_session(app: tauri::AppHandle) -> Result<FocusDigest, BridgeError> {
+    let digest = finish(true)?
+        .ok_or_else(|| BridgeError::NotReady("No focus session is running".to_string()))?;
+    deliver(&app, &digest);
+    Ok(digest)
+}
+
+/// The running session, if any.
+#[tauri::command]
+pub fn focus_status() -> Result<Option<FocusStatus>, BridgeError> {
+    let now = now_ms();
+    with_session(|_, session| {
+        session
+            .as_ref()
+            .filter(|s| s.ends_at > now)
+            .map(|s| FocusStatus {
+                started_at: s.started_at,
+                ends_at: s.ends_at,
+            })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn message(id: &str, channel: &str) -> Value {
+        json!({"type": "message", "id": id, "channelId": channel, "from": "peer", "receivedAt": 7})
+    }
+
+    #[test]
+    fn messages_are_counted_once_per_id() {
+        let mut session = FocusSession::default();
+        assert!(session.record(&message("a", "general"), false, 0));
+        assert!(!session.record(&message("a", "general"), false, 0));
+        assert!(session.record(&message("b", "general"), false, 0));
+        assert_eq!(session.conversations["general"], 2);
+    }
+
+    #[test]
+    fn mentions_are_collected_up_to_the_cap() {
+        let mut session = FocusSession::default();
+        for i in 0..MAX_MENTIONS + 3 {
+            session.record(&message(&i.to_string(), "general"), true, 0);
+        }
+        assert_eq!(session.mentions.len(), MAX_MENTIONS);
+        assert_eq!(session.mentions[0].message_id, "0");
+        assert_eq!(session.mentions[0].received_at, 7);
+        assert_eq!(session.conversations["general"], MAX_MENTIONS as u32 + 3);
+    }
+
+    #[test]
+    fn the_digest_lists_the_busiest_conversations_first() {
+        let mut session = FocusSession {
+            started_at: 100,
+            ends_at: 200,
+            ..Default::default()
+        };
+        session.record(&message("1", "quiet"), false, 0);
+        for id in ["2", "3", "4"] {
+            session.record(&message(id, "busy"), false, 0);
+        }
+        session.record(&message("5", "dm:peer"), true, 0);
+        session.record(&message("6", "dm:peer"), false, 0);
+
+        let digest = session.into_digest(150, true);
+        assert_eq!(digest.started_at, 100);
+        assert_eq!(digest.ended_at, 150);
+        assert!(digest.ended_early);
+        assert_eq!(digest.total, 6);
+        let order: Vec<&str> = digest
+            .conversations
+            .iter()
+            .map(|c| c.conversation_id.as_str())
+            .collect();
+        assert_eq!(order, ["busy", "dm:peer", "quiet"]);
+        assert_eq!(digest.mentions.len(), 1);
+    }
+
+    #[test]
+    fn sessions_survive_a_save_and_load() {
+        let path = std::env::temp_dir().join(format!("concord-focus-{}.json", std::process::id()));
+        let mut session = FocusSession {
+            started_at: 1,
+            ends_at: 2,
+            ..Default::default()
+        };
+        session.record(&message("a", "general"), true, 0);
+        save(&path, &Some(session)).unwrap();
+
+        let mut loaded = load(&path).expect("saved session");
+        assert_eq!(loaded.ends_at, 2);
+        assert_eq!(loaded.mentions.len(), 1);
+        assert!(!loaded.record(&message("a", "general"), false, 0));
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn durations_are_validated() {
+        for secs in [0, MAX_DURATION_SECS + 1] {
+            assert!(matches!(
+                start_focus_session(secs),
+                Err(BridgeError::InvalidArgument(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,505 @@
+// Chat history — inbound `message` events and outbound `p2p_send`s are kept
+// in messages.db (SQLite) in the profile directory, so conversations survive
+// a restart. Recording never blocks the stdout reader: records go through a
+// channel to a writer thread, which also prunes everything past
+// `history.maxMessages`. Commands query with their own connection.
+// A message id is stored once, so redelivered or replayed messages are
+// ignored. Whatever replaces or moves the profile directory (restore,
+// profile switch) calls `suspend` first, which closes the writer's
+// connection and holds records until `resume`.
+//
+// The schema is versioned with `PRAGMA user_version`; MIGRATIONS run in
+// order on open, so a new column is a new entry, never an edit. Nothing is
+// recorded in ephemeral sessions or with `history.enabled` off.
+
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+
+use super::error::BridgeError;
+
+pub(crate) const DB_FILE: &str = "messages.db";
+/// Records between prunes.
+const PRUNE_EVERY: u32 = 500;
+/// Records held while suspended; later ones are dropped.
+const MAX_HELD: usize = 10_000;
+/// How long `suspend` waits for the writer to close the database.
+const SUSPEND_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_PAGE: u32 = 500;
+const MAX_SEARCH_RESULTS: u32 = 200;
+
+/// Schema steps; `user_version` is how many have run.
+const MIGRATIONS: &[&str] = &[
+    "CREATE TABLE messages (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        message_id TEXT,
+        channel_id TEXT NOT NULL,
+        peer_id TEXT,
+        direction TEXT NOT NULL,
+        timestamp INTEGER NOT NULL,
+        body TEXT NOT NULL
+    );
+    CREATE INDEX messages_channel_time ON messages (channel_id, timestamp);",
+    "DELETE FROM messages WHERE message_id IS NOT NULL AND id NOT IN
+        (SELECT MIN(id) FROM messages WHERE message_id IS NOT NULL GROUP BY message_id);
+    CREATE UNIQUE INDEX messages_message_id ON messages (message_id);",
+];
+
+enum WriterMsg {
+    Record(StoredMessage),
+    /// Close the database and hold records; acknowledged once closed.
+    Suspend(mpsc::Sender<()>),
+    Resume,
+}
+
+static WRITER: Mutex<Option<mpsc::Sender<WriterMsg>>> = Mutex::new(None);
+
+#[derive(Serialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    Inbound,
+    Outbound,
+}
+
+impl Direction {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Inbound => "inbound",
+            Self::Outbound => "outbound",
+        }
+    }
+}
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct StoredMessage {
+    /// Row id; 0 until stored.
+    pub id: i64,
+    /// The bridge message id, when the message had one.
+    pub message_id: Option<String>,
+    pub channel_id: String,
+    /// The sender of inbound messages, the recipient of outbound DMs.
+    pub peer_id: Option<String>,
+    pub direction: Direction,
+    /// When the bridge saw it, in ms since the epoch.
+    pub timestamp: i64,
+    pub body: String,
+}
+
+fn db_path() -> Result<PathBuf, BridgeError> {
+    Ok(super::app_data_dir()?.join(DB_FILE))
+}
+
+fn db_error(e: rusqlite::Error) -> BridgeError {
+    BridgeError::from(format!("Message history: {}", e))
+}
+
+fn open() -> Result<Connection, BridgeError> {
+    let mut conn = Connection::open(db_path()?).map_err(db_error)?;
+    // Readers don't block the writer thread
+    conn.pragma_update_and_check(None, "journal_mode", "WAL", |_| Ok(()))
+        .map_err(db_error)?;
+    migrate(&mut conn).map_err(db_error)?;
+    Ok(conn)
+}
+
+/// Run the migrations the database hasn't seen yet.
+fn migrate(conn: &mut Connection) -> rusqlite::Result<()> {
+    let version: i64 = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    let version = usize::try_from(version).unwrap_or(0);
+    if version < MIGRATIONS.len() {
+        let tx = conn.transaction()?;
+        for migration in &MIGRATIONS[version..] {
+            tx.execute_batch(migration)?;
+        }
+        tx.pragma_update(None, "user_version", MIGRATIONS.len() as i64)?;
+        tx.commit()?;
+    }
+    Ok(())
+}
+
+/// Store `m` unless its message id is already stored.
+fn insert(conn: &Connection, m: &StoredMessage) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT OR IGNORE INTO messages
+         (message_id, channel_id, peer_id, direction, timestamp, body)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            m.message_id,
+            m.channel_id,
+            m.peer_id,
+            m.direction.as_str(),
+            m.timestamp,
+            m.body
+        ],
+    )?;
+    Ok(())
+}
+
+fn prune(conn: &Connection, keep: u32) -> rusqlite::Result<()> {
+    let cutoff: Option<i64> = conn
+        .query_row(
+            "SELECT id FROM messages ORDER BY id DESC LIMIT 1 OFFSET ?1",
+            params![keep],
+            |row| row.get(0),
+        )
+        .optional()?;
+    if let Some(cutoff) = cutoff {
+        conn.execute("DELETE FROM messages WHERE id <= ?1", params![cutoff])?;
+    }
+    Ok(())
+}
+
+/// The writer thread's state: the open database, if any, and the records
+/// held while suspended.
+struct Writer {
+    conn: Option<(PathBuf, Connection)>,
+    since_prune: u32,
+    suspended: bool,
+    held: Vec<StoredMessage>,
+}
+
+impl Writer {
+    fn write(&mut self, message: StoredMessage) {
+        if self.suspended {
+            if self.held.len() < MAX_HELD {
+                self.held.push(message);
+            }
+            return;
+        }
+        let Ok(path) = db_path() else { return };
+        // Reopen whenever the profile directory moved
+        if !self.conn.as_ref().is_some_and(|(p, _)| *p == path) {
+            self.conn = match open() {
+                Ok(c) => Some((path, c)),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    None
+                }
+            };
+        }
+        let Some((_, ref db)) = self.conn else { return };
+        if let Err(e) = insert(db, &message) {
+            eprintln!("Message history: {}", e);
+            return;
+        }
+        self.since_prune += 1;
+        if self.since_prune >= PRUNE_EVERY {
+            self.since_prune = 0;
+            let keep = super::settings::current().history.max_messages;
+            if let Err(e) = prune(db, keep) {
+                eprintln!("Message history prune failed: {}", e);
+            }
+        }
+    }
+}
+
+/// Start the writer thread. The database is reopened whenever the active
+/// profile changes, and after every `resume`.
+pub(crate) fn start() {
+    let (tx, rx) = mpsc::channel::<WriterMsg>();
+    *WRITER.lock().unwrap_or_else(|e| e.into_inner()) = Some(tx);
+    thread::spawn(move || {
+        let mut writer = Writer {
+            conn: None,
+            since_prune: 0,
+            suspended: false,
+            held: Vec::new(),
+        };
+        for msg in rx {
+            match msg {
+                WriterMsg::Record(message) => writer.write(message),
+                WriterMsg::Suspend(ack) => {
+                    writer.suspended = true;
+                    // Closing checkpoints the WAL into messages.db
+                    writer.conn = None;
+                    let _ = ack.send(());
+                }
+                WriterMsg::Resume => {
+                    writer.suspended = false;
+                    for message in std::mem::take(&mut writer.held) {
+                        writer.write(message);
+                    }
+                }
+            }
+        }
+    });
+}
+
+fn send(msg: WriterMsg) -> bool {
+    match *WRITER.lock().unwrap_or_else(|e| e.into_inner()) {
+        Some(ref tx) => tx.send(msg).is_ok(),
+        None => false,
+    }
+}
+
+/// Close the database before the profile directory is replaced or moved,
+/// and hold new records until `resume`. Waits until the writer has let go.
+pub(crate) fn suspend() {
+    let (ack, closed) = mpsc::channel();
+    if send(WriterMsg::Suspend(ack)) && closed.recv_timeout(SUSPEND_TIMEOUT).is_err() {
+        eprintln!("Message history: the writer did not close the database in time");
+    }
+}
+
+/// Reopen the database (at the current profile's path) and write what was
+/// held.
+pub(crate) fn resume() {
+    send(WriterMsg::Resume);
+}
+
+/// Write a consistent copy of the database to `dest`, for backups; false
+/// if there is no database yet.
+pub(crate) fn snapshot(dest: &Path) -> Result<bool, BridgeError> {
+    if !db_path()?.exists() {
+        return Ok(false);
+    }
+    let conn = open()?;
+    conn.execute("VACUUM INTO ?1", params![dest.to_string_lossy()])
+        .map_err(db_error)?;
+    Ok(true)
+}
+
+fn record(message: StoredMessage) {
+    if !super::settings::current().history.enabled || super::profiles::is_ephemeral() {
+        return;
+    }
+    send(WriterMsg::Record(message));
+}
+
+/// Called by the stdout reader for every event it forwards; records
+/// inbound messages.
+pub(crate) fn observe_event(event: &serde_json::Value) {
+    if event["type"] != "message" {
+        return;
+    }
+    let Some(channel_id) = event["channelId"].as_str() else {
+        return;
+    };
+    let body = match event["data"].as_str() {
+        Some(data) => data.to_string(),
+        None => event["data"].to_string(),
+    };
+    record(StoredMessage {
+        id: 0,
+        message_id: event["id"].as_str().map(str::to_string),
+        channel_id: channel_id.to_string(),
+        peer_id: event["from"].as_str().map(str::to_string),
+        direction: Direction::Inbound,
+        timestamp: super::now_ms() as i64,
+        body,
+    });
+}
+
+/// Called by `p2p_send` once the message is handed to the sidecar.
+pub(crate) fn record_outbound(
+    message_id: &str,
+    channel_id: &str,
+    target: Option<&str>,
+    body: &str,
+) {
+    record(StoredMessage {
+        id: 0,
+        message_id: Some(message_id.to_string()),
+        channel_id: channel_id.to_string(),
+        peer_id: target.map(str::to_string),
+        direction: Direction::Outbound,
+        timestamp: super::now_ms() as i64,
+        body: body.to_string(),
+    });
+}
+
+fn read_row(row: &rusqlite::Row) -> rusqlite::Result<StoredMessage> {
+    let direction: String = row.get(4)?;
+    Ok(StoredMessage {
+        id: row.get(0)?,
+        message_id: row.get(1)?,
+        channel_id: row.get(2)?,
+        peer_id: row.get(3)?,
+        direction: if direction == "outbound" {
+            Direction::Outbound
+        } else {
+            Direction::Inbound
+        },
+        timestamp: row.get(5)?,
+        body: row.get(6)?,
+    })
+}
+
+const COLUMNS: &str = "id, message_id, channel_id, peer_id, direction, timestamp, body";
+
+/// Move stored messages recorded under the old peer id to the new one
+/// (identity regeneration): our own `dm:` conversation and the peer column.
+pub(crate) fn rekey_peer(old: &str, new: &str) -> Result<(), BridgeError> {
+    if !db_path()?.exists() {
+        return Ok(());
+    }
+    let conn = open()?;
+    conn.execute(
+        "UPDATE messages SET channel_id = ?2 WHERE channel_id = ?1",
+        params![format!("dm:{}", old), format!("dm:{}", new)],
+    )
+    .map_err(db_error)?;
+    conn.execute(
+        "UPDATE messages SET peer_id = ?2 WHERE peer_id = ?1",
+        params![old, new],
+    )
+    .map_err(db_error)?;
+    Ok(())
+}
+
+// ── Tauri commands ───────────────────────────────────────────────
+
+/// Up to `limit` (at most 500) messages of a channel sent or received
+/// before `before` (ms since the epoch; now when omitted), oldest first.
+#[tauri::command]
+pub async fn get_message_history(
+    channel_id: String,
+    before: Option<i64>,
+    limit: u32,
+) -> Result<Vec<StoredMessage>, BridgeError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let conn = open()?;
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT {} FROM messages
+                 WHERE channel_id = ?1 AND (?2 IS NULL OR timestamp < ?2)
+                 ORDER BY timestamp DESC, id DESC LIMIT ?3",
+                COLUMNS
+            ))
+            .map_err(db_error)?;
+        let mut messages = stmt
+            .query_map(
+                params![channel_id, before, limit.clamp(1, MAX_PAGE)],
+                read_row,
+            )
+            .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>())
+            .map_err(db_error)?;
+        messages.reverse();
+        Ok(messages)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Messages whose text contains `query` (case-insensitive for ASCII), in
+/// one channel or all of them, newest first; at most 200.
+#[tauri::command]
+pub async fn search_messages(
+    query: String,
+    channel_id: Option<String>,
+) -> Result<Vec<StoredMessage>, BridgeError> {
+    let query = query.trim().to_string();
+    if query.is_empty() {
+        return Err(BridgeError::InvalidArgument(
+            "Search for at least one character".to_string(),
+        ));
+    }
+    let pattern = format!(
+        "%{}%",
+        query
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_")
+    );
+    tauri::async_runtime::spawn_blocking(move || {
+        let conn = open()?;
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT {} FROM messages
+                 WHERE body LIKE ?1 ESCAPE '\\' AND (?2 IS NULL OR channel_id = ?2)
+                 ORDER BY timestamp DESC, id DESC LIMIT ?3",
+                COLUMNS
+            ))
+            .map_err(db_error)?;
+        let messages = stmt
+            .query_map(params![pattern, channel_id, MAX_SEARCH_RESULTS], read_row)
+            .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>())
+            .map_err(db_error)?;
+        Ok(messages)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(id: Option<&str>, body: &str) -> StoredMessage {
+        StoredMessage {
+            id: 0,
+            message_id: id.map(str::to_string),
+            channel_id: "general".to_string(),
+            peer_id: None,
+            direction: Direction::Inbound,
+            timestamp: 1,
+            body: body.to_string(),
+        }
+    }
+
+    fn count(conn: &Connection) -> i64 {
+        conn.query_row("SELECT COUNT(*) FROM messages", [], |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn a_message_id_is_stored_once() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrate(&mut conn).unwrap();
+        insert(&conn, &message(Some("m1"), "first")).unwrap();
+        insert(&conn, &message(Some("m1"), "replayed")).unwrap();
+        insert(&conn, &message(None, "no id")).unwrap();
+        insert(&conn, &message(None, "no id")).unwrap();
+        assert_eq!(count(&conn), 3);
+        let body: String = conn
+            .query_row(
+                "SELECT body FROM messages WHERE message_id = 'm1'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(body, "first");
+    }
+
+    #[test]
+    fn migrating_removes_existing_duplicates() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(MIGRATIONS[0]).unwrap();
+        conn.pragma_update(None, "user_version", 1).unwrap();
+        for body in ["a", "b"] {
+            conn.execute(
+                "INSERT INTO messages (message_id, channel_id, direction, timestamp, body)
+                 VALUES ('m1', 'general', 'inbound', 1, ?1)",
+                params![body],
+            )
+            .unwrap();
+        }
+        migrate(&mut conn).unwrap();
+        assert_eq!(count(&conn), 1);
+    }
+
+    #[test]
+    fn prune_keeps_the_newest() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrate(&mut conn).unwrap();
+        for i in 0..10 {
+            insert(&conn, &message(Some(&i.to_string()), "x")).unwrap();
+        }
+        prune(&conn, 4).unwrap();
+        assert_eq!(count(&conn), 4);
+        let oldest: String = conn
+            .query_row(
+                "SELECT message_id FROM messages ORDER BY id LIMIT 1",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(oldest, "6");
+    }
+}
@@ -0,0 +1,139 @@
+// Sidecar hot reload — in dev (the sidecar runs from scripts/, not the
+// bundle) watch the script and restart the sidecar when it changes, so edits
+// don't need a full app restart. Only active in debug builds or with
+// `developer.enabled`, which starts or stops the watch as it is toggled.
+// With `developer.autoRestart` off, a `sidecar-script-changed` event is
+// emitted instead and the UI can offer a restart.
+
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+/// Editors write in bursts (temp file, rename, touch); wait for quiet.
+const DEBOUNCE: Duration = Duration::from_millis(500);
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(2);
+
+static WATCHER: Mutex<Option<(PathBuf, RecommendedWatcher)>> = Mutex::new(None);
+/// Last dev script seen, so enabling developer mode can start watching it.
+static SCRIPT: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+fn enabled() -> bool {
+    cfg!(debug_assertions) || super::settings::current().developer.enabled
+}
+
+/// Start watching `script` unless it is already watched. Called by
+/// `start_sidecar` whenever it resolves the dev script.
+pub(crate) fn watch(app: &tauri::AppHandle, script: &Path) {
+    *SCRIPT.lock().unwrap_or_else(|e| e.into_inner()) = Some(script.to_path_buf());
+    if !enabled() {
+        return;
+    }
+    let mut guard = WATCHER.lock().unwrap_or_else(|e| e.into_inner());
+    if matches!(*guard, Some((ref watched, _)) if watched == script) {
+        return;
+    }
+    let Some(dir) = script.parent() else {
+        return;
+    };
+
+    let (tx, rx) = mpsc::channel::<Vec<PathBuf>>();
+    let name = script.file_name().map(|n| n.to_os_string());
+    let watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+        let Ok(event) = res else {
+            return;
+        };
+        if matches!(event.kind, EventKind::Access(_)) {
+            return;
+        }
+        // Watch the directory, not the file: editors that save by renaming
+        // a temp file over the script would otherwise end the watch
+        let paths: Vec<PathBuf> = event
+            .paths
+            .into_iter()
+            .filter(|p| p.file_name().map(|n| n.to_os_string()) == name)
+            .collect();
+        if !paths.is_empty() {
+            let _ = tx.send(paths);
+        }
+    });
+    let mut watcher = match watcher {
+        Ok(w) => w,
+        Err(e) => {
+            eprintln!("Cannot watch sidecar script: {}", e);
+            return;
+        }
+    };
+    if let Err(e) = watcher.watch(dir, RecursiveMode::NonRecursive) {
+        eprintln!("Cannot watch {}: {}", dir.display(), e);
+        return;
+    }
+    *guard = Some((script.to_path_buf(), watcher));
+
+    let app = app.clone();
+    thread::spawn(move || {
+        while let Ok(first) = rx.recv() {
+            let mut changed = first;
+            while let Ok(more) = rx.recv_timeout(DEBOUNCE) {
+                changed.extend(more);
+            }
+            changed.sort();
+            changed.dedup();
+            // A restart already under way will pick up the new script
+            if super::sidecar::manager(&app).is_starting() || super::profiles::switch_in_progress()
+            {
+                continue;
+            }
+            on_script_changed(&app, &changed);
+        }
+    });
+}
+
+/// Start or stop watching as developer mode is toggled. A settings
+/// subscriber.
+pub(crate) fn settings_changed(app: &tauri::AppHandle) {
+    if !enabled() {
+        // Dropping the watcher also ends its debounce thread
+        *WATCHER.lock().unwrap_or_else(|e| e.into_inner()) = None;
+        return;
+    }
+    let script = SCRIPT.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    if let Some(script) = script {
+        watch(app, &script);
+    }
+}
+
+fn on_script_changed(app: &tauri::AppHandle, changed: &[PathBuf]) {
+    let paths: Vec<String> = changed
+        .iter()
+        .map(|p| p.to_string_lossy().into_owned())
+        .collect();
+    if !super::settings::current().developer.auto_restart {
+        super::emit_p2p_event(
+            app,
+            serde_json::json!({"type": "sidecar-script-changed", "paths": paths}),
+        );
+        return;
+    }
+    let sidecar = super::sidecar::manager(app);
+    // Whoever holds the start lock will start the new script anyway
+    let Some(start) = sidecar.try_lock_start() else {
+        return;
+    };
+    eprintln!("Sidecar script changed; restarting");
+    sidecar.stop_gracefully(SHUTDOWN_GRACE);
+    let result = super::start_sidecar_holding(app.clone(), sidecar.incognito(), &start);
+    drop(start);
+    match result {
+        Ok(()) => super::emit_p2p_event(
+            app,
+            serde_json::json!({"type": "sidecar-hot-reloaded", "paths": paths}),
+        ),
+        Err(e) => super::emit_p2p_event(
+            app,
+            serde_json::json!({"type": "error", "message": format!("Hot reload failed: {}", e)}),
+        ),
+    }
+}
@@ -0,0 +1,404 @@
+// Identity regeneration — burn a compromised key.
+// An approval prompt, an encrypted backup of the old identity, key
+// generation in the sidecar (which can also notify contacts, signed with the
+// old key), per-conversation state moved to the new peer id, and a restart
+// under the new identity.
+
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+
+use super::error::BridgeError;
+use super::protocol::{SidecarCommand, SidecarEvent};
+
+const IDENTITY_FILE: &str = "node-identity.json";
+const BACKUP_DIR: &str = "identity-backups";
+const HISTORY_FILE: &str = "identity-history.json";
+const BACKUP_MAGIC: &[u8] = b"CONCORD-IDENTITY-BACKUP-1\n";
+const INVALID_BACKUP: &str = "Backup does not contain a valid identity";
+
+const REGENERATE_TIMEOUT: Duration = Duration::from_secs(15);
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(2);
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RegeneratedIdentity {
+    pub old_peer_id: String,
+    pub new_peer_id: String,
+    pub backup: String,
+    pub notified: u64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IdentityBackup {
+    pub name: String,
+    pub created_at: u64,
+    pub size: u64,
+}
+
+fn seal(plain: &[u8]) -> Result<Vec<u8>, BridgeError> {
+    let mut out = BACKUP_MAGIC.to_vec();
+    out.extend(super::dpapi::protect(plain).map_err(|e| format!("Cannot encrypt backup: {}", e))?);
+    Ok(out)
+}
+
+/// The identity in a backup; `invalid-argument` if it isn't one or was
+/// sealed on another machine or account.
+fn unseal(data: &[u8]) -> Result<Vec<u8>, BridgeError> {
+    let sealed = data
+        .strip_prefix(BACKUP_MAGIC)
+        .ok_or_else(|| BridgeError::InvalidArgument("Not a Concord identity backup".to_string()))?;
+    super::dpapi::unprotect(sealed)
+        .map_err(|e| BridgeError::InvalidArgument(format!("Cannot decrypt backup: {}", e)))
+}
+
+// ── Helpers ──────────────────────────────────────────────────────
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+fn identity_path() -> Result<PathBuf, BridgeError> {
+    Ok(super::sidecar_config::data_dir()?.join(IDENTITY_FILE))
+}
+
+fn backup_dir() -> Result<PathBuf, BridgeError> {
+    let dir = super::app_data_dir()?.join(BACKUP_DIR);
+    fs::create_dir_all(&dir).map_err(|e| BridgeError::io("Cannot create the backup folder", e))?;
+    Ok(dir)
+}
+
+/// Encrypt the current identity into the backup folder and read it back to
+/// make sure the backup actually restores before anything is destroyed.
+fn backup_current_identity() -> Result<String, BridgeError> {
+    let plain = fs::read(identity_path()?)
+        .map_err(|e| BridgeError::io("No persistent identity to back up", e))?;
+    let name = format!("identity-{}.bak", now_ms());
+    let path = backup_dir()?.join(&name);
+    fs::write(&path, seal(&plain)?).map_err(|e| BridgeError::io("Cannot write backup", e))?;
+
+    let restored = fs::read(&path).map_err(|e| BridgeError::io("Cannot read backup back", e))?;
+    if unseal(&restored)? != plain {
+        let _ = fs::remove_file(&path);
+        return Err(BridgeError::io(
+            "Identity backup did not verify — aborting",
+            io::Error::from(io::ErrorKind::InvalidData),
+        ));
+    }
+    Ok(name)
+}
+
+fn record_transition(
+    old_peer_id: &str,
+    new_peer_id: &str,
+    backup: &str,
+) -> Result<(), BridgeError> {
+    let path = super::app_data_dir()?.join(HISTORY_FILE);
+    let mut history: Vec<serde_json::Value> = fs::read_to_string(&path)
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default();
+    history.push(serde_json::json!({
+        "oldPeerId": old_peer_id,
+        "newPeerId": new_peer_id,
+        "backup": backup,
+        "at": now_ms(),
+    }));
+    let json = serde_json::to_string_pretty(&history).map_err(|e| e.to_string())?;
+    fs::write(&path, json).map_err(|e| BridgeError::io("Cannot record the identity change", e))
+}
+
+fn regenerate(app: tauri::AppHandle, notify: bool) -> Result<RegeneratedIdentity, BridgeError> {
+    let backup = backup_current_identity()?;
+    let reply = super::request_sidecar_event(
+        &app,
+        &SidecarCommand::RegenerateIdentity { notify },
+        "identity_regenerated",
+        REGENERATE_TIMEOUT,
+    )?;
+    let SidecarEvent::IdentityRegenerated {
+        ok,
+        old_peer_id,
+        new_peer_id,
+        notified,
+        error,
+        ..
+    } = SidecarEvent::parse(&reply).map_err(|e| e.to_string())?
+    else {
+        return Err("Unexpected reply from the sidecar".into());
+    };
+    if !ok {
+        return Err(error
+            .unwrap_or_else(|| "Sidecar could not regenerate the identity".to_string())
+            .into());
+    }
+    let old_peer_id = old_peer_id.unwrap_or_default();
+    let new_peer_id = new_peer_id.unwrap_or_default();
+    record_transition(&old_peer_id, &new_peer_id, &backup)?;
+    migrate_peer_state(&old_peer_id, &new_peer_id);
+
+    // The sidecar has written the new key; restart so it goes live.
+    let sidecar = super::sidecar::manager(&app);
+    sidecar.stop_gracefully(SHUTDOWN_GRACE);
+    super::start_sidecar(app.clone(), sidecar.incognito())?;
+
+    super::emit_p2p_event(
+        &app,
+        serde_json::json!({
+            "type": "identity-changed",
+            "oldPeerId": old_peer_id,
+            "newPeerId": new_peer_id,
+        }),
+    );
+    Ok(RegeneratedIdentity {
+        old_peer_id,
+        new_peer_id,
+        notified: notified.unwrap_or(0),
+        backup,
+    })
+}
+
+// ── Per-peer state ───────────────────────────────────────────────
+
+/// `key` with the old peer id replaced by the new one, or None if it
+/// doesn't mention the old one. Keys are a peer id, a `dm:<peer>`
+/// conversation, or several of those joined by U+001F (sequence.rs).
+pub(crate) fn rekeyed(key: &str, old: &str, new: &str) -> Option<String> {
+    let old_dm = format!("dm:{}", old);
+    let mut changed = false;
+    let parts: Vec<String> = key
+        .split('\u{1f}')
+        .map(|part| {
+            if part == old {
+                changed = true;
+                new.to_string()
+            } else if part == old_dm {
+                changed = true;
+                format!("dm:{}", new)
+            } else {
+                part.to_string()
+            }
+        })
+        .collect();
+    changed.then(|| parts.join("\u{1f}"))
+}
+
+/// Move the entries of `map` under the old peer id to the new one; true if
+/// any moved.
+pub(crate) fn rekey_map<V>(map: &mut HashMap<String, V>, old: &str, new: &str) -> bool {
+    let moves: Vec<(String, String)> = map
+        .keys()
+        .filter_map(|key| rekeyed(key, old, new).map(|to| (key.clone(), to)))
+        .collect();
+    for (from, to) in &moves {
+        if let Some(value) = map.remove(from) {
+            map.insert(to.clone(), value);
+        }
+    }
+    !moves.is_empty()
+}
+
+/// `rekey_map` for a set.
+pub(crate) fn rekey_set(set: &mut HashSet<String>, old: &str, new: &str) -> bool {
+    let moves: Vec<(String, String)> = set
+        .iter()
+        .filter_map(|key| rekeyed(key, old, new).map(|to| (key.clone(), to)))
+        .collect();
+    for (from, to) in &moves {
+        set.remove(from);
+        set.insert(to.clone());
+    }
+    !moves.is_empty()
+}
+
+/// Carry what the profile keeps per conversation over to the new peer id.
+/// Inbound DMs arrive on `dm:<our peer id>`, so mutes, send policies,
+/// contact overrides, held requests, sequence counters and stored history
+/// recorded under the old id would otherwise be orphaned. Best effort: the
+/// new key is already live, so a store that fails is reported and skipped.
+fn migrate_peer_state(old: &str, new: &str) {
+    if old.is_empty() || new.is_empty() || old == new {
+        return;
+    }
+    let results = [
+        ("mutes", super::mutes::rekey_peer(old, new)),
+        (
+            "message requests",
+            super::message_requests::rekey_peer(old, new),
+        ),
+        ("send policies", super::send_policy::rekey_peer(old, new)),
+        (
+            "contact overrides",
+            super::presentation::rekey_peer(old, new),
+        ),
+        ("sequence counters", super::sequence::rekey_peer(old, new)),
+        ("message history", super::history::rekey_peer(old, new)),
+    ];
+    for (store, result) in results {
+        if let Err(e) = result {
+            eprintln!("Cannot move {} to the new peer id: {}", store, e);
+        }
+    }
+}
+
+// ── Tauri commands ───────────────────────────────────────────────
+
+/// Replace this profile's identity with a new keypair, once the user approves.
+/// With `notify_contacts`, connected peers receive a notice signed by the old key.
+#[tauri::command]
+pub async fn regenerate_identity(
+    app: tauri::AppHandle,
+    notify_contacts: Option<bool>,
+) -> Result<RegeneratedIdentity, BridgeError> {
+    if super::profiles::is_ephemeral() {
+        return Err(BridgeError::NotReady(
+            super::profiles::EPHEMERAL_UNAVAILABLE.to_string(),
+        ));
+    }
+    if !super::sidecar::manager(&app).is_running() {
+        return Err(BridgeError::SidecarNotRunning);
+    }
+
+    let notify = notify_contacts.unwrap_or(false);
+    // Never rememberable: every regeneration is asked for
+    super::approvals::require_approval(
+        &app,
+        "regenerate-identity",
+        None,
+        serde_json::json!({"notifyContacts": notify}),
+        false,
+    )
+    .await?;
+    tauri::async_runtime::spawn_blocking(move || regenerate(app, notify))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// List encrypted identity backups for the active profile, newest first.
+#[tauri::command]
+pub fn list_identity_backups() -> Result<Vec<IdentityBackup>, BridgeError> {
+    let mut backups: Vec<IdentityBackup> = fs::read_dir(backup_dir()?)
+        .map_err(|e| BridgeError::io("Cannot list identity backups", e))?
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            let created_at = name
+                .strip_prefix("identity-")?
+                .strip_suffix(".bak")?
+                .parse()
+                .ok()?;
+            Some(IdentityBackup {
+                size: entry.metadata().ok()?.len(),
+                name,
+                created_at,
+            })
+        })
+        .collect();
+    backups.sort_by_key(|b| Reverse(b.created_at));
+    Ok(backups)
+}
+
+/// Restore an identity backup, once the user approves. The current identity
+/// is backed up first, so a restore can itself be undone.
+#[tauri::command]
+pub async fn restore_identity_backup(
+    app: tauri::AppHandle,
+    name: String,
+) -> Result<(), BridgeError> {
+    if super::profiles::is_ephemeral() {
+        return Err(BridgeError::NotReady(
+            super::profiles::EPHEMERAL_UNAVAILABLE.to_string(),
+        ));
+    }
+    if !list_identity_backups()?.iter().any(|b| b.name == name) {
+        return Err(BridgeError::NotFound(format!(
+            "Backup '{}' not found",
+            name
+        )));
+    }
+    let data = fs::read(backup_dir()?.join(&name))
+        .map_err(|e| BridgeError::io("Cannot read backup", e))?;
+    let plain = unseal(&data)?;
+    let parsed: serde_json::Value = serde_json::from_slice(&plain)
+        .map_err(|_| BridgeError::InvalidArgument(INVALID_BACKUP.to_string()))?;
+    if !parsed["privateKey"].is_string() {
+        return Err(BridgeError::InvalidArgument(INVALID_BACKUP.to_string()));
+    }
+    super::approvals::require_approval(
+        &app,
+        "restore-identity",
+        None,
+        serde_json::json!({"backup": name}),
+        false,
+    )
+    .await?;
+
+    tauri::async_runtime::spawn_blocking(move || -> Result<(), BridgeError> {
+        if identity_path()?.exists() {
+            backup_current_identity()?;
+        }
+        let sidecar = super::sidecar::manager(&app);
+        sidecar.stop_gracefully(SHUTDOWN_GRACE);
+        fs::write(identity_path()?, &plain)
+            .map_err(|e| BridgeError::io("Cannot write identity", e))?;
+        super::start_sidecar(app.clone(), sidecar.incognito())?;
+        super::emit_p2p_event(
+            &app,
+            serde_json::json!({"type": "identity-changed", "restoredFrom": name}),
+        );
+        Ok(())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rekeyed_replaces_peer_ids_and_dm_conversations() {
+        assert_eq!(rekeyed("old", "old", "new").as_deref(), Some("new"));
+        assert_eq!(rekeyed("dm:old", "old", "new").as_deref(), Some("dm:new"));
+        assert_eq!(
+            rekeyed("general\u{1f}old", "old", "new").as_deref(),
+            Some("general\u{1f}new")
+        );
+        assert_eq!(
+            rekeyed("dm:old\u{1f}peer", "old", "new").as_deref(),
+            Some("dm:new\u{1f}peer")
+        );
+        assert_eq!(rekeyed("dm:other", "old", "new"), None);
+        // Only whole ids, never a prefix of another peer's id
+        assert_eq!(rekeyed("dm:older", "old", "new"), None);
+        assert_eq!(rekeyed("general", "old", "new"), None);
+    }
+
+    #[test]
+    fn rekey_moves_only_matching_entries() {
+        let mut map: HashMap<String, u32> = [
+            ("dm:old".to_string(), 1),
+            ("dm:other".to_string(), 2),
+            ("general".to_string(), 3),
+        ]
+        .into();
+        assert!(rekey_map(&mut map, "old", "new"));
+        assert_eq!(map.get("dm:new"), Some(&1));
+        assert!(!map.contains_key("dm:old"));
+        assert_eq!(map.len(), 3);
+        assert!(!rekey_map(&mut map, "old", "new"));
+
+        let mut set: HashSet<String> = ["old".to_string(), "other".to_string()].into();
+        assert!(rekey_set(&mut set, "old", "new"));
+        assert!(set.contains("new") && set.contains("other") && !set.contains("old"));
+    }
+}
@@ -3,88 +3,344 @@
 
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+use std::collections::HashMap;
 use std::fs;
 use std::io::{BufRead, BufReader, Write};
+#[cfg(windows)]
 use std::os::windows::process::CommandExt;
-use std::path::PathBuf;
-use std::process::{Child, ChildStdin, Command, Stdio};
-use std::sync::Mutex;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{mpsc, Mutex};
 use std::thread;
 
-use tauri::Emitter;
-
+use tauri::Manager;
+
+use error::BridgeError;
+use protocol::{SidecarCommand, SidecarEvent};
+
+mod announce;
+mod approvals;
+mod attention;
+mod backup;
+mod commands;
+mod compression;
+mod console;
+mod dedup;
+mod diagnostics;
+mod dpapi;
+mod error;
+mod file_transfer;
+mod flood;
+mod focus;
+mod history;
+mod hot_reload;
+mod identity;
+mod maintenance;
+mod message_requests;
+mod mutes;
+mod operations;
+mod outbox;
+mod presentation;
+mod previews;
+mod profiles;
+mod protocol;
+mod safe_mode;
+mod send_policy;
+mod sequence;
+mod settings;
+mod shutdown;
+mod sidecar;
+mod sidecar_config;
+mod sidecar_log;
+mod sink;
+mod storage;
+mod supervisor;
+mod tray;
+mod workdir;
+
+#[cfg(windows)]
 const CREATE_NO_WINDOW: u32 = 0x08000000;
+/// The Node.js binary bundled with the app, and the one looked up on PATH.
+const NODE_BINARY: &str = if cfg!(windows) { "node.exe" } else { "node" };
+/// Where Node.js installers put it on macOS, which apps started from the
+/// Finder don't have on PATH.
+#[cfg(target_os = "macos")]
+const EXTRA_NODE_DIRS: &[&str] = &["/opt/homebrew/bin", "/usr/local/bin"];
+const FRONTEND_MOUNT_DELAY: std::time::Duration = std::time::Duration::from_secs(2);
+/// How long `p2p_restart_sidecar` lets the sidecar stop on its own.
+/// A crash after this long is no longer counted as a startup crash.
+const STARTUP_SETTLE: std::time::Duration = std::time::Duration::from_secs(30);
 
-// ── Global state ─────────────────────────────────────────────────
+// ── Helpers ──────────────────────────────────────────────────────
 
-static SIDECAR_CHILD: Mutex<Option<Child>> = Mutex::new(None);
-static SIDECAR_STDIN: Mutex<Option<ChildStdin>> = Mutex::new(None);
-static SIDECAR_INCOGNITO: Mutex<bool> = Mutex::new(false);
+/// The per-user application data directory: %APPDATA% on Windows.
+#[cfg(windows)]
+fn platform_data_dir() -> Option<PathBuf> {
+    std::env::var_os("APPDATA").map(PathBuf::from)
+}
 
-// ── Helpers ──────────────────────────────────────────────────────
+/// The per-user application data directory: ~/.local/share (or
+/// $XDG_DATA_HOME) on Linux, ~/Library/Application Support on macOS.
+#[cfg(not(windows))]
+fn platform_data_dir() -> Option<PathBuf> {
+    dirs::data_dir()
+}
 
-fn app_data_dir() -> Result<PathBuf, String> {
-    let appdata = std::env::var("APPDATA").map_err(|_| "APPDATA not set")?;
-    let dir = PathBuf::from(appdata).join("Concord");
-    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+/// Top-level data directory, shared by all profiles.
+fn data_root() -> Result<PathBuf, BridgeError> {
+    let base = platform_data_dir().ok_or_else(|| {
+        BridgeError::NotReady("Cannot locate the user data directory".to_string())
+    })?;
+    let dir = base.join("Concord");
+    ensure_dir(&dir, "Cannot create data directory")?;
     Ok(dir)
 }
 
-fn sidecar_log_path() -> Result<PathBuf, String> {
-    // Include PID so each instance gets its own log file
-    Ok(app_data_dir()?.join(format!("sidecar-{}.log", std::process::id())))
+/// Data directory of the active profile.
+fn app_data_dir() -> Result<PathBuf, BridgeError> {
+    let dir = profiles::active_profile_dir()?;
+    ensure_dir(&dir, "Cannot create profile directory")?;
+    Ok(dir)
 }
 
-fn kill_sidecar() {
-    if let Ok(mut guard) = SIDECAR_STDIN.lock() {
-        *guard = None;
+/// Create `dir` if needed. On a full or read-only disk the path is returned
+/// anyway and storage goes degraded, so startup carries on without
+/// persistence instead of failing.
+fn ensure_dir(dir: &Path, context: &str) -> Result<(), BridgeError> {
+    match fs::create_dir_all(dir) {
+        Ok(()) => Ok(()),
+        Err(e) if storage::is_storage_failure(&e) => {
+            storage::note_failure(context, &e);
+            Ok(())
+        }
+        Err(e) => Err(BridgeError::io(context, e)),
     }
-    if let Ok(mut guard) = SIDECAR_CHILD.lock() {
-        if let Some(ref mut child) = *guard {
-            let _ = child.kill();
-            let _ = child.wait();
+}
+
+fn sidecar_log_path() -> Result<PathBuf, BridgeError> {
+    Ok(app_data_dir()?.join(sidecar_log::LOG_FILE))
+}
+
+/// Where the sidecar log goes when the data directory can't take it.
+fn fallback_log_path() -> PathBuf {
+    storage::writable_dir(None).join(sidecar_log::LOG_FILE)
+}
+
+/// Add a line to the sidecar_debug.txt breadcrumb, which each start
+/// begins afresh.
+fn append_breadcrumb(line: &str) {
+    let _ = fs::OpenOptions::new()
+        .append(true)
+        .open(app_data_dir().unwrap_or_default().join("sidecar_debug.txt"))
+        .and_then(|mut f| writeln!(f, "{}", line));
+}
+
+fn now_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Emit a `p2p-event` to the frontend through the event sink. During an
+/// ephemeral session every event is tagged so the UI can never mistake it
+/// for a persistent one.
+fn emit_p2p_event(app: &tauri::AppHandle, mut event: serde_json::Value) {
+    if profiles::is_ephemeral() {
+        if let Some(obj) = event.as_object_mut() {
+            obj.insert("ephemeral".to_string(), serde_json::Value::Bool(true));
         }
-        *guard = None;
     }
+    sink::emit(app, event);
+}
+
+/// Write a command; every command gets a `requestId`, which the sidecar
+/// echoes on its reply, if it sends one.
+fn write_to_sidecar(app: &tauri::AppHandle, cmd: &SidecarCommand) -> Result<(), BridgeError> {
+    write_request(app, cmd, &next_request_id())
+}
+
+fn write_request(
+    app: &tauri::AppHandle,
+    cmd: &SidecarCommand,
+    request_id: &str,
+) -> Result<(), BridgeError> {
+    let line = protocol::encode(cmd, request_id).map_err(|e| e.to_string())?;
+    match sidecar::manager(app).write_line(&line, false) {
+        // Starting or restarting: hold it until the next sidecar is attached
+        Err(BridgeError::SidecarNotRunning) => outbox::enqueue(app, cmd, line),
+        result => result,
+    }
+}
+
+/// Write a command only if the sidecar is running right now; never queued.
+fn write_to_running_sidecar(
+    app: &tauri::AppHandle,
+    cmd: &SidecarCommand,
+) -> Result<(), BridgeError> {
+    let line = protocol::encode(cmd, &next_request_id()).map_err(|e| e.to_string())?;
+    sidecar::manager(app).write_line(&line, false)
+}
+
+// ── Sidecar requests ─────────────────────────────────────────────
+
+static NEXT_REQUEST_ID: AtomicU64 = AtomicU64::new(1);
+/// A command waiting for its reply: the sidecar generation it was sent to
+/// and where to deliver the reply.
+type PendingRequest = (u64, mpsc::Sender<serde_json::Value>);
+/// Commands waiting for a reply, by request id.
+static PENDING_REQUESTS: Mutex<Option<HashMap<String, PendingRequest>>> = Mutex::new(None);
+
+fn next_request_id() -> String {
+    format!("req-{}", NEXT_REQUEST_ID.fetch_add(1, Ordering::Relaxed))
 }
 
-fn write_to_sidecar(cmd: &serde_json::Value) -> Result<(), String> {
-    let json = serde_json::to_string(cmd).map_err(|e| e.to_string())?;
-    let mut guard = SIDECAR_STDIN
+/// Wait for the reply to `request_id`, which `route_reply` delivers.
+fn register_request(request_id: &str) -> mpsc::Receiver<serde_json::Value> {
+    let (tx, rx) = mpsc::channel();
+    PENDING_REQUESTS
         .lock()
-        .map_err(|e| format!("Mutex poisoned: {}", e))?;
-    if let Some(ref mut stdin) = *guard {
-        writeln!(stdin, "{}", json).map_err(|e| format!("Write to sidecar: {}", e))?;
-        stdin
-            .flush()
-            .map_err(|e| format!("Flush sidecar: {}", e))?;
-        Ok(())
-    } else {
-        Err("Sidecar not running".to_string())
+        .unwrap_or_else(|e| e.into_inner())
+        .get_or_insert_with(HashMap::new)
+        .insert(request_id.to_string(), (supervisor::generation(), tx));
+    rx
+}
+
+/// Hand a sidecar reply to the command waiting for `request_id`. The event
+/// is still emitted as usual; replies nobody waits for any more (timed out,
+/// or fire-and-forget) are only emitted.
+fn route_reply(request_id: Option<&str>, event: &serde_json::Value) {
+    let Some(request_id) = request_id else {
+        return;
+    };
+    let waiter = PENDING_REQUESTS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .as_mut()
+        .and_then(|pending| pending.remove(request_id));
+    if let Some((_, tx)) = waiter {
+        let _ = tx.send(event.clone());
+    }
+}
+
+/// The sidecar of `generation` is gone: fail its outstanding requests now
+/// instead of letting them time out.
+fn fail_pending_requests(generation: u64) {
+    if let Some(pending) = PENDING_REQUESTS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .as_mut()
+    {
+        // Dropping the sender wakes the waiter with a disconnect
+        pending.retain(|_, (g, _)| *g != generation);
+    }
+}
+
+/// Write `cmd` to the sidecar with a fresh `requestId` and block until the
+/// sidecar answers it with an event of type `reply_type`, or `timeout`
+/// elapses. An `error` reply (e.g. an unknown command) fails the request.
+fn request_sidecar_event(
+    app: &tauri::AppHandle,
+    cmd: &SidecarCommand,
+    reply_type: &'static str,
+    timeout: std::time::Duration,
+) -> Result<serde_json::Value, BridgeError> {
+    let request_id = next_request_id();
+    let rx = register_request(&request_id);
+    let result = write_request(app, cmd, &request_id).and_then(|_| {
+        rx.recv_timeout(timeout).map_err(|e| match e {
+            mpsc::RecvTimeoutError::Timeout => {
+                BridgeError::Timeout(format!("sidecar {}", reply_type))
+            }
+            mpsc::RecvTimeoutError::Disconnected => BridgeError::SidecarNotRunning,
+        })
+    });
+    // On error or timeout the request is still registered
+    if let Some(pending) = PENDING_REQUESTS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .as_mut()
+    {
+        pending.remove(&request_id);
+    }
+    let reply = result?;
+    match reply["type"].as_str() {
+        Some(kind) if kind == reply_type => Ok(reply),
+        _ => Err(reply["message"]
+            .as_str()
+            .unwrap_or("Unexpected reply from the sidecar")
+            .into()),
     }
 }
 
 // ── Core sidecar start logic (called from setup hook) ────────────
 
-fn start_sidecar(app: tauri::AppHandle, incognito: bool) -> Result<(), String> {
-    kill_sidecar();
+#[cfg(not(target_os = "macos"))]
+fn find_node_on_path() -> Option<PathBuf> {
+    which::which("node").ok()
+}
 
-    // Breadcrumb for debugging
-    let _ = fs::write(
-        app_data_dir()?.join("sidecar_debug.txt"),
-        format!("start_sidecar called at {:?}\n", std::time::SystemTime::now()),
-    );
+#[cfg(target_os = "macos")]
+fn find_node_on_path() -> Option<PathBuf> {
+    which::which("node").ok().or_else(|| {
+        EXTRA_NODE_DIRS
+            .iter()
+            .map(|dir| Path::new(dir).join(NODE_BINARY))
+            .find(|path| path.exists())
+    })
+}
+
+/// Start the sidecar, killing the running one, once no other start is
+/// under way.
+fn start_sidecar(app: tauri::AppHandle, incognito: bool) -> Result<(), BridgeError> {
+    let sidecar = sidecar::manager(&app);
+    let start = sidecar.lock_start();
+    start_sidecar_holding(app.clone(), incognito, &start)
+}
+
+/// `start_sidecar` for a caller that already holds the start lock, e.g.
+/// across stopping the old sidecar.
+fn start_sidecar_holding(
+    app: tauri::AppHandle,
+    incognito: bool,
+    _start: &sidecar::Starting<'_>,
+) -> Result<(), BridgeError> {
+    sidecar::manager(&app).kill();
+    let generation = supervisor::generation();
+
+    // Breadcrumb for debugging; never worth failing the start over
+    if let Ok(dir) = app_data_dir() {
+        let _ = fs::write(
+            dir.join("sidecar_debug.txt"),
+            format!("start_sidecar called at {:?}\n", std::time::SystemTime::now()),
+        );
+    }
 
     // Find sidecar script:
-    // 1) Production: bundled "p2p-sidecar-bundle.js" next to the exe
+    // 1) Production: bundled "p2p-sidecar-bundle.js" in the resource
+    //    directory (next to the exe on Windows and Linux AppImages,
+    //    Contents/Resources on macOS, /usr/lib/Concord for .deb installs)
     // 2) Dev: walk up from exe directory looking for scripts/p2p-sidecar.js
     let exe = std::env::current_exe().map_err(|e| e.to_string())?;
     let exe_dir = exe.parent().ok_or("no exe parent")?;
+    let mut resource_dirs = vec![exe_dir.to_path_buf()];
+    if let Ok(dir) = app.path().resource_dir() {
+        if dir != exe_dir {
+            resource_dirs.push(dir);
+        }
+    }
 
-    let bundled = exe_dir.join("p2p-sidecar-bundle.js");
-    let (sidecar_script, working_dir) = if bundled.exists() {
-        // Production: bundled file is next to the exe, use exe_dir as cwd
-        (bundled, exe_dir.to_path_buf())
+    let bundled = resource_dirs
+        .iter()
+        .map(|dir| dir.join("p2p-sidecar-bundle.js"))
+        .find(|path| path.exists());
+    // Both paths are absolute (they come from current_exe or the resource
+    // dir), so the sidecar doesn't depend on its cwd to find itself.
+    let (sidecar_script, app_root) = if let Some(bundled) = bundled {
+        // Production: node_modules is bundled alongside the script
+        let root = bundled.parent().unwrap_or(exe_dir).to_path_buf();
+        (bundled, root)
     } else {
         // Dev: walk up from exe directory to find the project root
         let script = exe_dir
@@ -108,69 +364,86 @@ fn start_sidecar(app: tauri::AppHandle, incognito: bool) -> Result<(), String> {
             .and_then(|p| p.parent())
             .ok_or("invalid sidecar script path")?
             .to_path_buf();
+        hot_reload::watch(&app, &script);
         (script, root)
     };
 
-    // Find Node.js: bundled node.exe next to the app first, then PATH
-    let bundled_node = exe_dir.join("node.exe");
-    let node = if bundled_node.exists() {
-        bundled_node
-    } else {
-        which::which("node").map_err(|_| {
-            "Node.js runtime not found. The bundled node.exe is missing and Node.js is not on PATH.".to_string()
-        })?
+    // Find Node.js: the bundled binary first, then PATH
+    let node = match resource_dirs
+        .iter()
+        .map(|dir| dir.join(NODE_BINARY))
+        .find(|path| path.exists())
+    {
+        Some(bundled_node) => bundled_node,
+        None => find_node_on_path().ok_or_else(|| {
+            BridgeError::NotFound(format!("Node.js runtime not found. The bundled {} is missing and Node.js is not on PATH.", NODE_BINARY))
+        })?,
     };
 
-    let log_path = sidecar_log_path()?;
-    let log_file =
-        fs::File::create(&log_path).map_err(|e| format!("Cannot create sidecar log: {}", e))?;
+    let mut log_path = sidecar_log_path()?;
+    if let Err(e) = sidecar_log::open(&log_path) {
+        storage::note_failure("Cannot create sidecar log", &e);
+        log_path = fallback_log_path();
+        sidecar_log::open(&log_path).map_err(|e| BridgeError::io("Cannot create sidecar log", e))?;
+    }
 
-    // Pass the app data directory so the sidecar can persist identity there
-    let data_dir = app_data_dir()?;
+    // Pass the data directory so the sidecar can persist identity there
+    let config = sidecar_config::load();
+    let data_dir = sidecar_config::data_dir()?;
 
     // NODE_PATH tells Node.js where to find native addons (node-datachannel)
     // that are marked external in the esbuild bundle.
-    // In production: node_modules is next to the exe (copied by bundle script).
-    // In dev: node_modules is in the project root (working_dir).
-    let node_path = working_dir.join("node_modules");
+    // In production: node_modules is next to the bundled script.
+    // In dev: node_modules is in the project root (app_root).
+    let node_path = app_root.join("node_modules");
+
+    // A contained, writable cwd for anything written to `./`; a full disk
+    // must not keep the sidecar from starting.
+    let working_dir = workdir::prepare().unwrap_or_else(|e| {
+        eprintln!("{}; running the sidecar in the temp dir", e);
+        storage::writable_dir(None)
+    });
 
+    // The bridge's own variables go last, so extraEnv can't replace them
     let mut cmd = Command::new(&node);
     cmd.arg(&sidecar_script)
+        .envs(config.env())
         .env("CONCORD_DATA_DIR", data_dir.to_string_lossy().as_ref())
         .env("NODE_PATH", node_path.to_string_lossy().as_ref())
         .current_dir(&working_dir)
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
-        .stderr(Stdio::from(log_file))
-        .creation_flags(CREATE_NO_WINDOW);
+        .stderr(Stdio::piped());
+    #[cfg(windows)]
+    cmd.creation_flags(CREATE_NO_WINDOW);
 
     if incognito {
         cmd.env("CONCORD_INCOGNITO", "1");
     }
 
-    // Store incognito state for later queries
-    if let Ok(mut guard) = SIDECAR_INCOGNITO.lock() {
-        *guard = incognito;
-    }
-
     let mut child = cmd
         .spawn()
-        .map_err(|e| format!("Failed to spawn sidecar: {}", e))?;
+        .map_err(|e| BridgeError::io("Failed to spawn sidecar", e))?;
 
     let stdin = child.stdin.take().ok_or("No stdin pipe")?;
     let stdout = child.stdout.take().ok_or("No stdout pipe")?;
+    let stderr = child.stderr.take().ok_or("No stderr pipe")?;
+
+    // Nothing can be queued from here until the flush below
+    let queue = outbox::hold();
+    sidecar::manager(&app).attach(sidecar::Spawned {
+        child,
+        stdin,
+        incognito,
+        script: sidecar_script.clone(),
+        node: node.clone(),
+        log_path,
+    });
 
-    {
-        let mut guard = SIDECAR_STDIN.lock().map_err(|e| format!("Mutex: {}", e))?;
-        *guard = Some(stdin);
-    }
-    {
-        let mut guard = SIDECAR_CHILD.lock().map_err(|e| format!("Mutex: {}", e))?;
-        *guard = Some(child);
-    }
-
-    // Background thread: read sidecar stdout and emit Tauri events
+    // Background thread: read sidecar stdout and emit Tauri events; when
+    // stdout ends, the supervisor decides whether to restart
     let app_handle = app.clone();
+    let started = std::time::Instant::now();
     thread::spawn(move || {
         let reader = BufReader::new(stdout);
         for line in reader.lines() {
@@ -180,122 +453,460 @@ fn start_sidecar(app: tauri::AppHandle, incognito: bool) -> Result<(), String> {
                     if trimmed.is_empty() {
                         continue;
                     }
-                    match serde_json::from_str::<serde_json::Value>(trimmed) {
-                        Ok(json) => {
-                            let _ = app_handle.emit("p2p-event", json);
+                    console::trace(console::Direction::Inbound, trimmed, false);
+                    // Unknown event types parse as `Other` and pass through;
+                    // text and malformed events are forwarded as log lines
+                    let parsed = match serde_json::from_str::<serde_json::Value>(trimmed) {
+                        Ok(json) => match SidecarEvent::parse(&json) {
+                            Ok(event) => Some((event, json)),
+                            Err(e) => {
+                                eprintln!("Malformed sidecar {} event: {}", json["type"], e);
+                                None
+                            }
+                        },
+                        Err(_) => None,
+                    };
+                    match parsed {
+                        Some((event, mut json)) => {
+                            if !compression::admit(&mut json) {
+                                continue;
+                            }
+                            if !dedup::admit(&mut json) {
+                                continue;
+                            }
+                            sequence::stamp_inbound(&mut json);
+                            let Some(json) = flood::admit(&app_handle, json) else {
+                                continue;
+                            };
+                            let Some(mut json) = message_requests::admit(&app_handle, json) else {
+                                continue;
+                            };
+                            presentation::annotate(&mut json);
+                            diagnostics::observe_event(&json);
+                            focus::observe_event(&json);
+                            attention::observe_event(&app_handle, &json);
+                            announce::observe_event(&app_handle, &json);
+                            previews::observe_event(&app_handle, &json);
+                            history::observe_event(&json);
+                            sequence::observe_event(&app_handle, &json);
+                            route_reply(event.request_id(), &json);
+                            emit_p2p_event(&app_handle, json);
                         }
-                        Err(_) => {
-                            let _ = app_handle.emit(
-                                "p2p-event",
+                        None => {
+                            emit_p2p_event(
+                                &app_handle,
                                 serde_json::json!({"type": "log", "message": trimmed}),
                             );
                         }
                     }
                 }
                 Err(e) => {
-                    let _ = app_handle.emit(
-                        "p2p-event",
+                    emit_p2p_event(
+                        &app_handle,
                         serde_json::json!({"type": "error", "message": format!("stdout read error: {}", e)}),
                     );
                     break;
                 }
             }
         }
-        let _ = app_handle.emit(
-            "p2p-event",
-            serde_json::json!({"type": "error", "message": "Sidecar process exited"}),
-        );
+        fail_pending_requests(generation);
+        supervisor::reader_ended(&app_handle, generation, started);
     });
 
-    // Append to breadcrumb
-    let _ = fs::OpenOptions::new()
-        .append(true)
-        .open(app_data_dir().unwrap_or_default().join("sidecar_debug.txt"))
-        .and_then(|mut f| {
-            use std::io::Write;
-            writeln!(f, "sidecar spawned OK, node={}, script={}", node.display(), sidecar_script.display())
-        });
+    sidecar_log::start_reader(app.clone(), stderr);
+    outbox::flush(&app, queue);
+
+    append_breadcrumb(&format!(
+        "sidecar spawned OK, node={}, script={}",
+        node.display(),
+        sidecar_script.display()
+    ));
 
     Ok(())
 }
 
 // ── Tauri commands ───────────────────────────────────────────────
 
-/// Send a chat message through the sidecar.
+/// Send a chat message through the sidecar and return its message id.
 /// If `target_peer_id` is provided, send only to that peer (DM).
-/// Otherwise broadcast to all connected peers.
+/// Otherwise broadcast to all connected peers. The channel's send policy
+/// is checked first (see send_policy.rs). Data over the size peers will
+/// inflate is rejected with `payload-too-large`.
 #[tauri::command]
-fn p2p_send(channel_id: String, data: String, target_peer_id: Option<String>) -> Result<(), String> {
-    let mut payload = serde_json::json!({
-        "cmd": "send",
-        "channelId": channel_id,
-        "data": data
-    });
-    if let Some(ref tid) = target_peer_id {
-        payload["targetPeerId"] = serde_json::json!(tid);
+async fn p2p_send(app: tauri::AppHandle, channel_id: String, data: String, target_peer_id: Option<String>) -> Result<String, BridgeError> {
+    if data.len() > compression::MAX_INFLATED_BYTES {
+        return Err(BridgeError::PayloadTooLarge {
+            size: data.len() as u64,
+            limit: compression::MAX_INFLATED_BYTES as u64,
+        });
     }
-    write_to_sidecar(&payload)
+    send_policy::check(&app, &channel_id, &data).await?;
+    maintenance::note_activity();
+    let id = dedup::new_message_id();
+    dedup::remember(&id);
+    let (wire_data, encoding) = compression::encode_outbound(&data, target_peer_id.as_deref());
+    sequence::send_in_order(&channel_id, target_peer_id.as_deref(), |seq| {
+        write_to_sidecar(&app, &SidecarCommand::Send {
+            channel_id: channel_id.clone(),
+            data: wire_data,
+            seq,
+            id: id.clone(),
+            caps: vec![compression::DEFLATE.to_string()],
+            target_peer_id: target_peer_id.clone(),
+            encoding: encoding.map(str::to_string),
+        })
+    })?;
+    if let Some(ref peer) = target_peer_id {
+        message_requests::mark_accepted(peer);
+    }
+    previews::observe_outbound(&app, &channel_id, &data);
+    history::record_outbound(&id, &channel_id, target_peer_id.as_deref(), &data);
+    Ok(id)
+}
+
+/// Same shapes the sidecar accepts: an invite code (XXXX-XXXX) or a multiaddr.
+fn is_dialable(address: &str) -> bool {
+    let is_invite_code = address.len() == 9
+        && address.char_indices().all(|(i, c)| {
+            if i == 4 {
+                c == '-'
+            } else {
+                c.is_ascii_alphanumeric()
+            }
+        });
+    is_invite_code || address.starts_with('/')
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DialResult {
+    address: String,
+    /// The dialled peer, for invite codes.
+    peer_id: Option<String>,
+    /// Connected peers after the dial.
+    peers: Vec<String>,
 }
 
-/// Tell the sidecar to dial a remote peer.
+/// Dial a remote peer and wait for the outcome: rejects with `dial-failed`
+/// when the sidecar can't connect, or `timeout` after `sidecar.dialTimeoutSecs`.
+/// The `dial_result` event is emitted either way.
 #[tauri::command]
-fn p2p_dial(address: String) -> Result<(), String> {
-    write_to_sidecar(&serde_json::json!({
-        "cmd": "dial",
-        "address": address
-    }))
+async fn p2p_dial(app: tauri::AppHandle, address: String) -> Result<DialResult, BridgeError> {
+    if !is_dialable(address.trim()) {
+        return Err(BridgeError::InvalidAddress(address));
+    }
+    let timeout =
+        std::time::Duration::from_secs(u64::from(settings::current().sidecar.dial_timeout_secs));
+    let cmd = SidecarCommand::Dial {
+        address: address.clone(),
+    };
+    let reply = tauri::async_runtime::spawn_blocking(move || {
+        request_sidecar_event(&app, &cmd, "dial_result", timeout)
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+    let SidecarEvent::DialResult {
+        ok,
+        error,
+        peer_id,
+        peers,
+        ..
+    } = SidecarEvent::parse(&reply).map_err(|e| e.to_string())?
+    else {
+        return Err("Unexpected reply from the sidecar".into());
+    };
+    if !ok {
+        return Err(BridgeError::DialFailed {
+            address,
+            message: error.unwrap_or_else(|| "Dial failed".to_string()),
+        });
+    }
+    Ok(DialResult {
+        address,
+        peer_id,
+        peers: peers.unwrap_or_default(),
+    })
 }
 
 /// Restart the sidecar with optional incognito mode.
 /// When incognito, the sidecar uses an ephemeral identity.
 #[tauri::command]
-fn restart_p2p(app: tauri::AppHandle, incognito: bool) -> Result<(), String> {
+fn restart_p2p(app: tauri::AppHandle, incognito: bool) -> Result<(), BridgeError> {
     start_sidecar(app, incognito)
 }
 
-/// Read the sidecar stderr log for debugging.
+/// Stop the sidecar cleanly (stdin closed first so it can flush its state,
+/// killed after a grace period) and start it again with the same mode.
+/// Emits `sidecar-restarting` and then `sidecar-started`. Rejects with
+/// `not-ready` while another start or restart is still running.
 #[tauri::command]
-fn get_sidecar_log() -> Result<String, String> {
-    let path = sidecar_log_path()?;
-    match fs::read_to_string(&path) {
-        Ok(s) => Ok(s),
-        Err(_) => Ok(String::new()),
+async fn p2p_restart_sidecar(
+    app: tauri::AppHandle,
+) -> Result<sidecar::SidecarStatus, BridgeError> {
+    let handle = app.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let sidecar = sidecar::manager(&handle);
+        let Some(start) = sidecar.try_lock_start() else {
+            return Err(BridgeError::NotReady(
+                "A sidecar start or restart is already in progress".to_string(),
+            ));
+        };
+        emit_p2p_event(&handle, serde_json::json!({"type": "sidecar-restarting"}));
+        sidecar.stop_gracefully(settings::current().sidecar.shutdown_grace());
+        let incognito = sidecar.incognito();
+        let result = start_sidecar_holding(handle.clone(), incognito, &start);
+        if result.is_err() {
+            outbox::mark_down(&handle);
+        }
+        result
+    })
+    .await
+    .map_err(|e| BridgeError::from(e.to_string()))
+    .and_then(|r| r)?;
+    let status = p2p_sidecar_status(app.state());
+    emit_p2p_event(
+        &app,
+        serde_json::json!({"type": "sidecar-started", "pid": status.pid}),
+    );
+    Ok(status)
+}
+
+/// What the bridge knows about the sidecar process.
+#[tauri::command]
+fn p2p_sidecar_status(
+    sidecar: tauri::State<'_, sidecar::SidecarManager>,
+) -> sidecar::SidecarStatus {
+    sidecar.status()
+}
+
+/// Read the sidecar stderr log for debugging: the last `tail_lines` lines,
+/// or both the current and the rotated file when omitted.
+#[tauri::command]
+fn get_sidecar_log(
+    sidecar: tauri::State<'_, sidecar::SidecarManager>,
+    tail_lines: Option<usize>,
+) -> Result<String, BridgeError> {
+    let path = match sidecar.log_path() {
+        Some(path) => path,
+        None => sidecar_log_path()?,
+    };
+    let log = sidecar_log::read(&path, tail_lines);
+    if log.is_empty() {
+        return Ok(sidecar_log::read(&fallback_log_path(), tail_lines));
     }
+    Ok(log)
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AppInfo {
+    version: String,
+    profile: String,
+    data_dir: String,
+    incognito: bool,
+    ephemeral: bool,
+    bridge_manages_title: bool,
+    /// The previous run ended without a clean shutdown (crash or kill).
+    unclean_previous_shutdown: bool,
+    /// Why safe mode engaged, if the app is running in it.
+    safe_mode: Option<String>,
+    /// Redelivered or echoed messages dropped since launch.
+    duplicates_dropped: u64,
+    /// Every process exit code, by name.
+    exit_codes: std::collections::BTreeMap<&'static str, i32>,
+    /// Why persistence is paused, if the data directory's disk is full or
+    /// read-only.
+    storage_degraded: Option<String>,
+}
+
+/// Basic information about the running app and active profile.
+#[tauri::command]
+fn get_app_info(app: tauri::AppHandle) -> Result<AppInfo, BridgeError> {
+    Ok(AppInfo {
+        version: app.package_info().version.to_string(),
+        profile: profiles::active_profile()?,
+        data_dir: app_data_dir()?.to_string_lossy().into_owned(),
+        incognito: sidecar::manager(&app).incognito(),
+        ephemeral: profiles::is_ephemeral(),
+        bridge_manages_title: attention::bridge_manages_title(),
+        unclean_previous_shutdown: shutdown::previous_unclean(),
+        safe_mode: safe_mode::reason(),
+        duplicates_dropped: dedup::duplicates_dropped(),
+        exit_codes: shutdown::exit_codes(),
+        storage_degraded: storage::degraded_reason(),
+    })
 }
 
 // ── App entry point ──────────────────────────────────────────────
 
+/// Start the sidecar and background subsystems. Skipped in safe mode until
+/// `leave_safe_mode`.
+fn start_subsystems(app: tauri::AppHandle, sidecar_delay: std::time::Duration) {
+    // Auto-start the P2P sidecar when the app opens (normal mode).
+    // Short delay gives the frontend time to mount and attach event listeners
+    // so the initial `ready` and `invite_code` events are not missed.
+    let handle = app.clone();
+    thread::spawn(move || {
+        thread::sleep(sidecar_delay);
+        if let Err(e) = start_sidecar(handle.clone(), false) {
+            eprintln!("Sidecar start failed: {}", e);
+            outbox::mark_down(&handle);
+            emit_p2p_event(
+                &handle,
+                serde_json::json!({"type": "error", "message": format!("Sidecar start failed: {}", e)}),
+            );
+        }
+    });
+    maintenance::start(app.clone());
+    history::start();
+    previews::start_worker(app);
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
+        .manage(sidecar::SidecarManager::new())
         .plugin(tauri_plugin_shell::init())
         .plugin(tauri_plugin_updater::Builder::new().build())
         .plugin(tauri_plugin_process::init())
         .setup(|app| {
-            // Auto-start the P2P sidecar when the app opens (normal mode).
-            // Short delay gives the frontend time to mount and attach event listeners
-            // so the initial `ready` and `invite_code` events are not missed.
+            storage::init(app.handle());
+            shutdown::begin_session();
+            if let Ok(dir) = app_data_dir() {
+                sidecar_log::remove_stale(&dir);
+            }
+            sidecar_log::remove_stale(&storage::writable_dir(None));
+            announce::mark_launched();
+            mutes::start_timer(app.handle().clone());
+            focus::start_timer(app.handle().clone());
+            if let Err(e) = tray::init(app.handle()) {
+                eprintln!("Cannot create tray icon: {}", e);
+            }
+            if let Some(window) = app.get_webview_window("main") {
+                shutdown::hook_session_end(&window);
+            }
+
+            let handle = app.handle().clone();
+            if safe_mode::detect() {
+                // Same delay as the sidecar start, so the recovery screen's
+                // listener is attached before the event arrives.
+                thread::spawn(move || {
+                    thread::sleep(FRONTEND_MOUNT_DELAY);
+                    safe_mode::announce(&handle);
+                });
+            } else {
+                start_subsystems(handle, FRONTEND_MOUNT_DELAY);
+            }
             let handle = app.handle().clone();
             thread::spawn(move || {
-                thread::sleep(std::time::Duration::from_secs(2));
-                if let Err(e) = start_sidecar(handle.clone(), false) {
-                    eprintln!("Sidecar start failed: {}", e);
-                    let _ = handle.emit(
-                        "p2p-event",
-                        serde_json::json!({"type": "error", "message": format!("Sidecar start failed: {}", e)}),
-                    );
-                }
+                thread::sleep(FRONTEND_MOUNT_DELAY);
+                storage::announce(&handle);
+            });
+            thread::spawn(|| {
+                thread::sleep(STARTUP_SETTLE);
+                shutdown::startup_settled();
             });
             Ok(())
         })
+        .on_window_event(|window, event| match event {
+            tauri::WindowEvent::Focused(focused) => {
+                attention::on_focus_changed(window.app_handle(), *focused);
+                announce::on_focus_changed(window.app_handle(), *focused);
+                maintenance::note_activity();
+            }
+            // Nobody is left to answer
+            tauri::WindowEvent::Destroyed if window.label() == "main" => approvals::deny_all(),
+            _ => {}
+        })
+        .on_page_load(|webview, payload| {
+            // A reload loses the prompts and progress bars on screen; show
+            // them again once the frontend's listener is back.
+            if payload.event() == tauri::webview::PageLoadEvent::Finished {
+                let handle = webview.app_handle().clone();
+                thread::spawn(move || {
+                    thread::sleep(FRONTEND_MOUNT_DELAY);
+                    approvals::reemit_pending(&handle);
+                    operations::reemit_active(&handle);
+                });
+            }
+        })
         .invoke_handler(tauri::generate_handler![
             p2p_send,
             p2p_dial,
             get_sidecar_log,
             restart_p2p,
+            p2p_restart_sidecar,
+            p2p_sidecar_status,
+            sidecar_config::get_sidecar_config,
+            sidecar_config::set_sidecar_config,
+            file_transfer::p2p_send_file,
+            file_transfer::p2p_cancel_transfer,
+            history::get_message_history,
+            history::search_messages,
+            get_app_info,
+            approvals::pending_approvals,
+            approvals::resolve_approval,
+            approvals::clear_remembered_approvals,
+            diagnostics::run_connectivity_check,
+            profiles::list_profiles,
+            profiles::create_profile,
+            profiles::delete_profile,
+            profiles::switch_profile,
+            profiles::start_ephemeral_session,
+            profiles::end_ephemeral_session,
+            identity::regenerate_identity,
+            identity::list_identity_backups,
+            identity::restore_identity_backup,
+            mutes::mute_conversation,
+            mutes::unmute_conversation,
+            mutes::list_muted,
+            focus::start_focus_session,
+            focus::end_focus_session,
+            focus::focus_status,
+            send_policy::set_channel_policy,
+            send_policy::get_channel_policy,
+            presentation::get_presentation_hints,
+            presentation::set_presentation_override,
+            settings::export_settings,
+            settings::import_settings,
+            operations::list_operations,
+            operations::cancel_operation,
+            maintenance::run_maintenance_now,
+            maintenance::get_maintenance_status,
+            message_requests::list_message_requests,
+            message_requests::respond_message_request,
+            backup::create_backup,
+            backup::restore_backup,
+            backup::set_auto_backup_passphrase,
+            commands::get_command_registry,
+            commands::execute_registered_command,
+            console::console_subscribe,
+            console::console_unsubscribe,
+            console::console_send_raw,
+            safe_mode::leave_safe_mode,
+            settings::get_settings,
+            settings::set_setting,
+            settings::reset_settings,
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .unwrap_or_else(|e| {
+            let code = match e {
+                tauri::Error::Setup(_) => shutdown::ExitCode::SetupFailed,
+                _ => shutdown::ExitCode::RuntimeFailed,
+            };
+            shutdown::exit(code, &e.to_string())
+        })
+        .run(|app, event| {
+            // Stop the sidecar while the last window closes, not after the
+            // loop ends; Exit is also reached by the updater's restart request
+            match event {
+                tauri::RunEvent::ExitRequested { .. } => {
+                    shutdown::run(Some(app), "exit-requested")
+                }
+                tauri::RunEvent::Exit => shutdown::run(Some(app), "exit"),
+                _ => {}
+            }
+        });
 
-    kill_sidecar();
+    shutdown::exit(shutdown::ExitCode::Normal, "event loop ended");
 }
@@ -0,0 +1,414 @@
+// Maintenance — heavy background jobs (today: the scheduled backup) run
+// here, one at a time, only while nobody is using the machine: no keyboard
+// or mouse input for `maintenance.idleMinutes`, inside `maintenance.window`
+// when one is set, and on mains power with `maintenance.requireAc`. Jobs
+// work in steps and keep their own progress between them; when the user
+// comes back mid-pass the scheduler stops after the current step and the
+// job picks up where it left off on a later pass. `run_maintenance_now`
+// ignores the conditions.
+//
+// Idle time comes from the system on Windows. Elsewhere it is the time since
+// the app was last used (window focus changes and sends), so a user busy in
+// other programs looks idle to us. A job that fails is retried after a
+// backoff that doubles with each consecutive failure, up to MAX_BACKOFF.
+
+use std::collections::HashMap;
+#[cfg(not(windows))]
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+
+use super::error::BridgeError;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(60);
+const FIRST_BACKOFF: Duration = Duration::from_secs(5 * 60);
+const MAX_BACKOFF: Duration = Duration::from_secs(6 * 60 * 60);
+
+/// Registered jobs, in the order a pass runs them.
+const JOBS: &[&dyn Job] = &[&super::backup::AutoBackup];
+
+/// Whether a pass is running; passes never overlap.
+static RUNNING: AtomicBool = AtomicBool::new(false);
+static HISTORY: Mutex<Option<HashMap<&'static str, History>>> = Mutex::new(None);
+/// Last use of the app, in ms since the epoch (0: not since launch).
+#[cfg(not(windows))]
+static LAST_ACTIVITY: AtomicU64 = AtomicU64::new(0);
+
+pub(crate) enum Step {
+    /// More work remains; the scheduler may yield before the next step.
+    More,
+    Done,
+}
+
+/// A heavy background job. Anything a job needs to resume after yielding
+/// it must persist itself at the end of each step.
+pub(crate) trait Job: Sync {
+    fn name(&self) -> &'static str;
+    /// Whether the job has work to do, including an unfinished run.
+    fn pending(&self) -> bool;
+    /// Do the next piece of work.
+    fn step(&self, app: &tauri::AppHandle) -> Result<Step, String>;
+}
+
+#[derive(Clone, Default)]
+struct History {
+    last_run_at: Option<u64>,
+    last_error: Option<String>,
+    interrupted: bool,
+    /// Consecutive failed runs.
+    failures: u32,
+    /// No scheduled run before this, after a failure.
+    retry_at: Option<u64>,
+}
+
+/// How a job's turn in a pass ended.
+#[derive(Debug, PartialEq)]
+enum Outcome {
+    Finished,
+    Failed(String),
+    /// The conditions stopped holding between steps.
+    Interrupted,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JobStatus {
+    pub name: &'static str,
+    pub pending: bool,
+    /// When the job last finished a run, successfully or not.
+    pub last_run_at: Option<u64>,
+    /// The error of the last run, if it failed.
+    pub last_error: Option<String>,
+    /// Whether the last pass yielded before the job finished.
+    pub interrupted: bool,
+    /// After a failure: scheduled passes skip the job until then.
+    pub retry_at: Option<u64>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MaintenanceStatus {
+    pub running: bool,
+    /// Whether the idle, window and power conditions hold right now.
+    pub conditions_met: bool,
+    pub jobs: Vec<JobStatus>,
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+fn record(job: &'static str, f: impl FnOnce(&mut History)) {
+    let mut history = HISTORY.lock().unwrap_or_else(|e| e.into_inner());
+    f(history
+        .get_or_insert_with(HashMap::new)
+        .entry(job)
+        .or_default());
+}
+
+#[cfg(windows)]
+fn idle_for() -> Option<Duration> {
+    use windows_sys::Win32::System::SystemInformation::GetTickCount;
+    use windows_sys::Win32::UI::Input::KeyboardAndMouse::{GetLastInputInfo, LASTINPUTINFO};
+    let mut info = LASTINPUTINFO {
+        cbSize: std::mem::size_of::<LASTINPUTINFO>() as u32,
+        dwTime: 0,
+    };
+    // SAFETY: GetLastInputInfo only writes into the provided struct, whose
+    // size is set as required.
+    if unsafe { GetLastInputInfo(&mut info) } == 0 {
+        return None;
+    }
+    // Both are 32-bit tick counts; wrapping_sub survives the 49-day rollover
+    let ticks = unsafe { GetTickCount() };
+    Some(Duration::from_millis(u64::from(
+        ticks.wrapping_sub(info.dwTime),
+    )))
+}
+
+#[cfg(not(windows))]
+fn idle_for() -> Option<Duration> {
+    let last = match LAST_ACTIVITY.load(Ordering::Relaxed) {
+        0 => launched_at(),
+        last => last,
+    };
+    Some(Duration::from_millis(now_ms().saturating_sub(last)))
+}
+
+#[cfg(not(windows))]
+fn launched_at() -> u64 {
+    static LAUNCHED_AT: std::sync::OnceLock<u64> = std::sync::OnceLock::new();
+    *LAUNCHED_AT.get_or_init(now_ms)
+}
+
+/// Called when the user does something in the app; the idle source where
+/// the system doesn't provide one.
+#[cfg(not(windows))]
+pub(crate) fn note_activity() {
+    LAST_ACTIVITY.store(now_ms(), Ordering::Relaxed);
+}
+
+#[cfg(windows)]
+pub(crate) fn note_activity() {}
+
+#[cfg(windows)]
+fn on_battery() -> bool {
+    use windows_sys::Win32::System::Power::{GetSystemPowerStatus, SYSTEM_POWER_STATUS};
+    // SAFETY: GetSystemPowerStatus only writes into the provided struct.
+    let status = unsafe {
+        let mut status: SYSTEM_POWER_STATUS = std::mem::zeroed();
+        if GetSystemPowerStatus(&mut status) == 0 {
+            return false;
+        }
+        status
+    };
+    // 0 = offline; 1 = online and 255 = unknown don't hold jobs back
+    status.ACLineStatus == 0
+}
+
+#[cfg(not(windows))]
+fn on_battery() -> bool {
+    false
+}
+
+/// Whether background jobs may run now. Conditions that can't be checked
+/// on this platform count as met.
+fn conditions_met() -> bool {
+    let cfg = super::settings::current().maintenance;
+    let idle_needed = Duration::from_secs(u64::from(cfg.idle_minutes) * 60);
+    if idle_for().is_some_and(|idle| idle < idle_needed) {
+        return false;
+    }
+    if cfg.require_ac && on_battery() {
+        return false;
+    }
+    match (cfg.window, super::attention::local_minute_of_day()) {
+        (Some(window), Some(minute)) => window.contains(minute),
+        _ => true,
+    }
+}
+
+/// Delay before retrying a job that failed `failures` times in a row.
+fn backoff(failures: u32) -> Duration {
+    let doublings = failures.saturating_sub(1).min(16);
+    FIRST_BACKOFF
+        .saturating_mul(1 << doublings)
+        .min(MAX_BACKOFF)
+}
+
+/// Whether a failed job is still waiting out its backoff.
+fn backing_off(job: &'static str, now: u64) -> bool {
+    HISTORY
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .as_ref()
+        .and_then(|h| h.get(job))
+        .and_then(|h| h.retry_at)
+        .is_some_and(|at| now < at)
+}
+
+/// Step a job while it has work and `may_continue` holds between steps.
+/// None if there was nothing to do.
+fn drive(
+    pending: &dyn Fn() -> bool,
+    may_continue: &dyn Fn() -> bool,
+    step: &mut dyn FnMut() -> Result<Step, String>,
+) -> Option<Outcome> {
+    let mut stepped = false;
+    while pending() {
+        if !may_continue() {
+            return Some(Outcome::Interrupted);
+        }
+        stepped = true;
+        match step() {
+            Ok(Step::More) => {}
+            // One run per job per pass, even if it is still due afterwards
+            Ok(Step::Done) => return Some(Outcome::Finished),
+            Err(e) => return Some(Outcome::Failed(e)),
+        }
+    }
+    // The work ran out without a final Done
+    stepped.then_some(Outcome::Finished)
+}
+
+fn record_outcome(job: &'static str, outcome: &Outcome, now: u64) {
+    record(job, |h| match outcome {
+        Outcome::Finished => {
+            *h = History {
+                last_run_at: Some(now),
+                ..History::default()
+            }
+        }
+        Outcome::Failed(e) => {
+            let failures = h.failures + 1;
+            *h = History {
+                last_run_at: Some(now),
+                last_error: Some(e.clone()),
+                interrupted: false,
+                failures,
+                retry_at: Some(now + backoff(failures).as_millis() as u64),
+            }
+        }
+        Outcome::Interrupted => h.interrupted = true,
+    });
+}
+
+/// Run every pending job. Unless `forced`, skips jobs backing off after a
+/// failure and stops between steps as soon as the conditions no longer
+/// hold. Returns false if a pass was already running.
+fn run_pass(app: &tauri::AppHandle, forced: bool) -> bool {
+    if RUNNING.swap(true, Ordering::SeqCst) {
+        return false;
+    }
+    for job in JOBS {
+        if !forced && backing_off(job.name(), now_ms()) {
+            continue;
+        }
+        let may_continue = || forced || conditions_met();
+        let Some(outcome) = drive(&|| job.pending(), &may_continue, &mut || job.step(app)) else {
+            continue;
+        };
+        if let Outcome::Failed(ref e) = outcome {
+            eprintln!("Maintenance job {} failed: {}", job.name(), e);
+            super::emit_p2p_event(
+                app,
+                serde_json::json!({
+                    "type": "error",
+                    "message": format!("Maintenance job {} failed: {}", job.name(), e),
+                }),
+            );
+        }
+        record_outcome(job.name(), &outcome, now_ms());
+        if outcome == Outcome::Interrupted {
+            break;
+        }
+    }
+    RUNNING.store(false, Ordering::SeqCst);
+    true
+}
+
+/// Start the scheduler thread. It checks every POLL_INTERVAL, starting one
+/// interval after launch so startup isn't slowed.
+pub(crate) fn start(app: tauri::AppHandle) {
+    thread::spawn(move || loop {
+        thread::sleep(POLL_INTERVAL);
+        let due = |job: &&dyn Job| job.pending() && !backing_off(job.name(), now_ms());
+        if JOBS.iter().any(due) && conditions_met() {
+            run_pass(&app, false);
+        }
+    });
+}
+
+fn status() -> MaintenanceStatus {
+    let history = HISTORY
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+        .unwrap_or_default();
+    MaintenanceStatus {
+        running: RUNNING.load(Ordering::SeqCst),
+        conditions_met: conditions_met(),
+        jobs: JOBS
+            .iter()
+            .map(|job| {
+                let h = history.get(job.name()).cloned().unwrap_or_default();
+                JobStatus {
+                    name: job.name(),
+                    pending: job.pending(),
+                    last_run_at: h.last_run_at,
+                    last_error: h.last_error,
+                    interrupted: h.interrupted,
+                    retry_at: h.retry_at,
+                }
+            })
+            .collect(),
+    }
+}
+
+// ── Tauri commands ───────────────────────────────────────────────
+
+/// Run every pending job now, whatever the idle, window and power
+/// conditions, and return the resulting status.
+#[tauri::command]
+pub async fn run_maintenance_now(app: tauri::AppHandle) -> Result<MaintenanceStatus, BridgeError> {
+    let ran = tauri::async_runtime::spawn_blocking(move || run_pass(&app, true))
+        .await
+        .map_err(|e| e.to_string())?;
+    if !ran {
+        return Err(BridgeError::NotReady(
+            "Maintenance is already running".to_string(),
+        ));
+    }
+    Ok(status())
+}
+
+#[tauri::command]
+pub fn get_maintenance_status() -> MaintenanceStatus {
+    status()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        assert_eq!(backoff(1), FIRST_BACKOFF);
+        assert_eq!(backoff(2), FIRST_BACKOFF * 2);
+        assert_eq!(backoff(3), FIRST_BACKOFF * 4);
+        assert_eq!(backoff(40), MAX_BACKOFF);
+    }
+
+    #[test]
+    fn drive_steps_until_done() {
+        let steps = Cell::new(0);
+        let outcome = drive(&|| true, &|| true, &mut || {
+            steps.set(steps.get() + 1);
+            Ok(if steps.get() < 3 {
+                Step::More
+            } else {
+                Step::Done
+            })
+        });
+        assert_eq!(outcome, Some(Outcome::Finished));
+        assert_eq!(steps.get(), 3);
+    }
+
+    #[test]
+    fn drive_yields_between_steps_and_reports_failures() {
+        let steps = Cell::new(0);
+        let outcome = drive(&|| true, &|| steps.get() < 2, &mut || {
+            steps.set(steps.get() + 1);
+            Ok(Step::More)
+        });
+        assert_eq!(outcome, Some(Outcome::Interrupted));
+        assert_eq!(steps.get(), 2);
+
+        let outcome = drive(&|| true, &|| true, &mut || Err("disk full".to_string()));
+        assert_eq!(outcome, Some(Outcome::Failed("disk full".to_string())));
+        assert_eq!(drive(&|| false, &|| true, &mut || Ok(Step::Done)), None);
+    }
+
+    #[test]
+    fn failures_back_off_until_a_run_succeeds() {
+        let job = "test-backoff";
+        record_outcome(job, &Outcome::Failed("a".to_string()), 1000);
+        let first = 1000 + FIRST_BACKOFF.as_millis() as u64;
+        assert!(backing_off(job, first - 1));
+        assert!(!backing_off(job, first));
+
+        record_outcome(job, &Outcome::Failed("b".to_string()), first);
+        assert!(backing_off(job, first + FIRST_BACKOFF.as_millis() as u64));
+
+        record_outcome(job, &Outcome::Finished, first + 1);
+        assert!(!backing_off(job, first + 2));
+    }
+}
@@ -0,0 +1,306 @@
+// Message requests — a DM from a peer we've never talked to is held back
+// instead of landing in the conversation list with a notification. Peers we
+// have DMed, seen in a shared channel, or accepted get through as before.
+// Held messages are stored (message-requests.json) until the user accepts
+// (they are delivered as if just received), declines (future DMs are
+// dropped silently) or blocks the peer (all of its messages are dropped).
+// `privacy.messageRequests` turns the screening off; blocks still apply.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use super::error::BridgeError;
+
+pub(crate) const REQUESTS_FILE: &str = "message-requests.json";
+/// Held messages per requesting peer; the oldest are dropped first.
+const MAX_HELD_PER_PEER: usize = 50;
+const MAX_PENDING_PEERS: usize = 200;
+
+/// Request state of the active profile.
+static STATE: Mutex<Option<(PathBuf, RequestState)>> = Mutex::new(None);
+/// Peers seen posting in a group channel this session. Sharing a channel
+/// makes a DM expected, not unsolicited.
+static SHARED: Mutex<Option<HashSet<String>>> = Mutex::new(None);
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default, rename_all = "camelCase")]
+struct RequestState {
+    accepted: HashSet<String>,
+    declined: HashSet<String>,
+    blocked: HashSet<String>,
+    pending: HashMap<String, PendingRequest>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default, rename_all = "camelCase")]
+struct PendingRequest {
+    first_at: u64,
+    messages: Vec<Value>,
+    dropped: u64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageRequest {
+    pub peer_id: String,
+    pub first_at: u64,
+    /// Held message events, oldest first.
+    pub messages: Vec<Value>,
+    /// Messages discarded because the per-peer cap was reached.
+    pub dropped: u64,
+}
+
+enum Decision {
+    Deliver,
+    Drop,
+    Held { first: bool, held: usize },
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum RequestResponse {
+    Accept,
+    Decline,
+    Block,
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+fn load(path: &Path) -> RequestState {
+    match super::storage::read_to_string(path) {
+        Ok(s) => serde_json::from_str(&s).unwrap_or_else(|e| {
+            eprintln!("Ignoring malformed {}: {}", path.display(), e);
+            RequestState::default()
+        }),
+        Err(_) => RequestState::default(),
+    }
+}
+
+fn save(path: &Path, state: &RequestState) -> Result<(), BridgeError> {
+    let json = serde_json::to_string(state).map_err(|e| e.to_string())?;
+    super::storage::write(path, json)
+        .map_err(|e| BridgeError::io("Cannot save message requests", e))
+}
+
+/// Drop the cached request state so the next use reads them from disk (after a
+/// restore replaced the file at the same path).
+pub(crate) fn invalidate() {
+    *STATE.lock().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+/// Run `f` on the active profile's state, loading it after a profile switch.
+fn with_state<T>(f: impl FnOnce(&Path, &mut RequestState) -> T) -> Result<T, BridgeError> {
+    let path = super::app_data_dir()?.join(REQUESTS_FILE);
+    let mut guard = STATE.lock().unwrap_or_else(|e| e.into_inner());
+    if !matches!(*guard, Some((ref cached, _)) if *cached == path) {
+        *guard = Some((path.clone(), load(&path)));
+    }
+    let Some((_, ref mut state)) = *guard else {
+        return Err("Message request state unavailable".into());
+    };
+    Ok(f(&path, state))
+}
+
+/// Called by `p2p_send`: DMing a peer accepts its DMs.
+pub(crate) fn mark_accepted(peer_id: &str) {
+    let result = with_state(|path, state| {
+        if state.accepted.contains(peer_id) {
+            return Ok(());
+        }
+        state.accepted.insert(peer_id.to_string());
+        state.declined.remove(peer_id);
+        save(path, state)
+    });
+    if let Err(e) = result.and_then(|r| r) {
+        eprintln!("{}", e);
+    }
+}
+
+/// Called by the stdout reader after flood protection. Returns the event if
+/// it should carry on as usual, or None if it was held as a request or
+/// dropped.
+pub(crate) fn admit(app: &tauri::AppHandle, event: Value) -> Option<Value> {
+    if event["type"].as_str() != Some("message") {
+        return Some(event);
+    }
+    let Some(from) = event["from"].as_str().map(str::to_string) else {
+        return Some(event);
+    };
+    let channel = event["channelId"].as_str().unwrap_or_default();
+    let is_dm = channel.starts_with("dm:");
+    if !is_dm {
+        SHARED
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get_or_insert_with(HashSet::new)
+            .insert(from.clone());
+    }
+    let screening = super::settings::current().privacy.message_requests;
+    let shared = || {
+        SHARED
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .as_ref()
+            .is_some_and(|s| s.contains(&from))
+    };
+
+    let decision = with_state(|path, state| {
+        if state.blocked.contains(&from) {
+            return Decision::Drop;
+        }
+        if !is_dm || !screening || state.accepted.contains(&from) || shared() {
+            return Decision::Deliver;
+        }
+        if state.declined.contains(&from) {
+            return Decision::Drop;
+        }
+        if !state.pending.contains_key(&from) && state.pending.len() >= MAX_PENDING_PEERS {
+            // Forget the oldest request rather than growing without bound
+            let oldest = state
+                .pending
+                .iter()
+                .min_by_key(|(_, r)| r.first_at)
+                .map(|(id, _)| id.clone());
+            if let Some(oldest) = oldest {
+                state.pending.remove(&oldest);
+            }
+        }
+        let request = state
+            .pending
+            .entry(from.clone())
+            .or_insert_with(|| PendingRequest {
+                first_at: now_ms(),
+                ..Default::default()
+            });
+        let first = request.messages.is_empty() && request.dropped == 0;
+        if request.messages.len() == MAX_HELD_PER_PEER {
+            request.messages.remove(0);
+            request.dropped += 1;
+        }
+        request.messages.push(event.clone());
+        let held = request.messages.len();
+        if let Err(e) = save(path, state) {
+            eprintln!("{}", e);
+        }
+        Decision::Held { first, held }
+    })
+    // Without the state there is nothing to screen against; don't lose it
+    .unwrap_or(Decision::Deliver);
+
+    match decision {
+        Decision::Deliver => Some(event),
+        Decision::Drop => None,
+        Decision::Held { first, held } => {
+            super::emit_p2p_event(
+                app,
+                json!({
+                    "type": "message-request",
+                    "peerId": from,
+                    "held": held,
+                    "first": first,
+                }),
+            );
+            None
+        }
+    }
+}
+
+/// Move request state recorded under the old peer id to the new one
+/// (identity regeneration), including the channel of held DMs.
+pub(crate) fn rekey_peer(old: &str, new: &str) -> Result<(), BridgeError> {
+    use super::identity::{rekey_map, rekey_set, rekeyed};
+    with_state(|path, state| {
+        let mut changed = rekey_set(&mut state.accepted, old, new);
+        changed |= rekey_set(&mut state.declined, old, new);
+        changed |= rekey_set(&mut state.blocked, old, new);
+        changed |= rekey_map(&mut state.pending, old, new);
+        for message in state.pending.values_mut().flat_map(|r| &mut r.messages) {
+            if let Some(channel) = message["channelId"]
+                .as_str()
+                .and_then(|c| rekeyed(c, old, new))
+            {
+                message["channelId"] = json!(channel);
+                changed = true;
+            }
+        }
+        if changed {
+            save(path, state)?;
+        }
+        Ok(())
+    })?
+}
+
+// ── Tauri commands ───────────────────────────────────────────────
+
+/// Pending message requests, oldest first.
+#[tauri::command]
+pub fn list_message_requests() -> Result<Vec<MessageRequest>, BridgeError> {
+    let mut requests = with_state(|_, state| {
+        state
+            .pending
+            .iter()
+            .map(|(peer_id, r)| MessageRequest {
+                peer_id: peer_id.clone(),
+                first_at: r.first_at,
+                messages: r.messages.clone(),
+                dropped: r.dropped,
+            })
+            .collect::<Vec<_>>()
+    })?;
+    requests.sort_by_key(|r| r.first_at);
+    Ok(requests)
+}
+
+/// Accept, decline or block `peer_id`. Accepting delivers the held
+/// messages as `message` events; declining and blocking discard them.
+/// Works for peers without a pending request too, e.g. to block ahead of
+/// time.
+#[tauri::command]
+pub fn respond_message_request(
+    app: tauri::AppHandle,
+    peer_id: String,
+    response: RequestResponse,
+) -> Result<(), BridgeError> {
+    let held = with_state(|path, state| {
+        let held = state.pending.remove(&peer_id).unwrap_or_default();
+        state.accepted.remove(&peer_id);
+        state.declined.remove(&peer_id);
+        state.blocked.remove(&peer_id);
+        match response {
+            RequestResponse::Accept => state.accepted.insert(peer_id.clone()),
+            RequestResponse::Decline => state.declined.insert(peer_id.clone()),
+            RequestResponse::Block => state.blocked.insert(peer_id.clone()),
+        };
+        save(path, state).map(|()| held.messages)
+    })??;
+
+    if response == RequestResponse::Accept {
+        for event in held {
+            super::sequence::observe_event(&app, &event);
+            super::emit_p2p_event(&app, event);
+        }
+    }
+    super::emit_p2p_event(
+        &app,
+        json!({
+            "type": "message-request-resolved",
+            "peerId": peer_id,
+            "response": match response {
+                RequestResponse::Accept => "accept",
+                RequestResponse::Decline => "decline",
+                RequestResponse::Block => "block",
+            },
+        }),
+    );
+    Ok(())
+}
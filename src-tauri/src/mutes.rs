@@ -0,0 +1,196 @@
+// Conversation mutes — "mute for 1 hour / 8 hours / until I turn it back on".
+// A muted conversation (keyed by channel id, `dm:<peer>` for DMs) doesn't
+// count towards the unread title, flash the taskbar or get announced. This
+// is on top of DND: either one silences a message. Mutes persist with their
+// expiry time in mutes.json, so a timed mute keeps its remaining time across
+// restarts; a timer lifts expired mutes and emits `mute-expired`.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use super::error::BridgeError;
+
+pub(crate) const MUTES_FILE: &str = "mutes.json";
+const EXPIRY_POLL: Duration = Duration::from_secs(5);
+
+/// Mutes of the active profile, keyed by conversation.
+static MUTES: Mutex<Option<(PathBuf, MuteState)>> = Mutex::new(None);
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default, rename_all = "camelCase")]
+struct MuteState {
+    /// Conversation → expiry (unix ms), or None until unmuted.
+    muted: HashMap<String, Option<u64>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MutedConversation {
+    pub conversation_id: String,
+    /// Unix ms when the mute lifts; null until unmuted by hand.
+    pub until: Option<u64>,
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+fn load(path: &Path) -> MuteState {
+    match super::storage::read_to_string(path) {
+        Ok(s) => serde_json::from_str(&s).unwrap_or_else(|e| {
+            eprintln!("Ignoring malformed {}: {}", path.display(), e);
+            MuteState::default()
+        }),
+        Err(_) => MuteState::default(),
+    }
+}
+
+fn save(path: &Path, state: &MuteState) -> Result<(), BridgeError> {
+    let json = serde_json::to_string_pretty(state).map_err(|e| e.to_string())?;
+    super::storage::write(path, json).map_err(|e| BridgeError::io("Cannot save mutes", e))
+}
+
+/// Drop the cached mutes so the next use reads them from disk (after a
+/// restore replaced the file at the same path).
+pub(crate) fn invalidate() {
+    *MUTES.lock().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+/// Run `f` on the active profile's mutes, loading them after a profile switch.
+fn with_mutes<T>(f: impl FnOnce(&Path, &mut MuteState) -> T) -> Result<T, BridgeError> {
+    let path = super::app_data_dir()?.join(MUTES_FILE);
+    let mut guard = MUTES.lock().unwrap_or_else(|e| e.into_inner());
+    if !matches!(*guard, Some((ref cached, _)) if *cached == path) {
+        *guard = Some((path.clone(), load(&path)));
+    }
+    let Some((_, ref mut state)) = *guard else {
+        return Err("Mute state unavailable".into());
+    };
+    Ok(f(&path, state))
+}
+
+/// Whether messages in `conversation_id` should stay quiet right now.
+pub(crate) fn is_muted(conversation_id: &str) -> bool {
+    let now = now_ms();
+    with_mutes(|_, state| {
+        state
+            .muted
+            .get(conversation_id)
+            .is_some_and(|until| until.map_or(true, |t| t > now))
+    })
+    .unwrap_or(false)
+}
+
+/// Lift mutes whose time is up and return their conversations.
+fn take_expired() -> Vec<String> {
+    let now = now_ms();
+    with_mutes(|path, state| {
+        let expired: Vec<String> = state
+            .muted
+            .iter()
+            .filter(|(_, until)| until.is_some_and(|t| t <= now))
+            .map(|(id, _)| id.clone())
+            .collect();
+        if !expired.is_empty() {
+            for id in &expired {
+                state.muted.remove(id);
+            }
+            if let Err(e) = save(path, state) {
+                eprintln!("{}", e);
+            }
+        }
+        expired
+    })
+    .unwrap_or_default()
+}
+
+/// Emit `mute-expired` as timed mutes run out, including ones that ran out
+/// while the app was closed.
+pub(crate) fn start_timer(app: tauri::AppHandle) {
+    thread::spawn(move || loop {
+        for id in take_expired() {
+            super::emit_p2p_event(
+                &app,
+                serde_json::json!({"type": "mute-expired", "conversationId": id}),
+            );
+        }
+        thread::sleep(EXPIRY_POLL);
+    });
+}
+
+/// Move mutes recorded under the old peer id to the new one (identity
+/// regeneration).
+pub(crate) fn rekey_peer(old: &str, new: &str) -> Result<(), BridgeError> {
+    with_mutes(|path, state| {
+        if super::identity::rekey_map(&mut state.muted, old, new) {
+            save(path, state)?;
+        }
+        Ok(())
+    })?
+}
+
+// ── Tauri commands ───────────────────────────────────────────────
+
+/// Mute a conversation for `duration_secs`, or until unmuted if omitted.
+/// Muting an already muted conversation replaces its expiry.
+#[tauri::command]
+pub fn mute_conversation(
+    id: String,
+    duration_secs: Option<u64>,
+) -> Result<MutedConversation, BridgeError> {
+    if id.trim().is_empty() {
+        return Err(BridgeError::InvalidArgument(
+            "Conversation id must not be empty".to_string(),
+        ));
+    }
+    if duration_secs == Some(0) {
+        return Err(BridgeError::InvalidArgument(
+            "Mute duration must be at least one second".to_string(),
+        ));
+    }
+    let until = duration_secs.map(|secs| now_ms().saturating_add(secs.saturating_mul(1000)));
+    with_mutes(|path, state| {
+        state.muted.insert(id.clone(), until);
+        save(path, state)
+    })??;
+    Ok(MutedConversation {
+        conversation_id: id,
+        until,
+    })
+}
+
+/// Unmute a conversation. Unmuting one that isn't muted is not an error.
+#[tauri::command]
+pub fn unmute_conversation(id: String) -> Result<(), BridgeError> {
+    with_mutes(|path, state| match state.muted.remove(&id) {
+        Some(_) => save(path, state),
+        None => Ok(()),
+    })?
+}
+
+/// Muted conversations of the active profile, for the settings UI.
+#[tauri::command]
+pub fn list_muted() -> Result<Vec<MutedConversation>, BridgeError> {
+    let now = now_ms();
+    let mut muted = with_mutes(|_, state| {
+        state
+            .muted
+            .iter()
+            .filter(|(_, until)| until.map_or(true, |t| t > now))
+            .map(|(id, until)| MutedConversation {
+                conversation_id: id.clone(),
+                until: *until,
+            })
+            .collect::<Vec<_>>()
+    })?;
+    muted.sort_by(|a, b| a.conversation_id.cmp(&b.conversation_id));
+    Ok(muted)
+}
@@ -0,0 +1,283 @@
+// Long-running operations — backups, restores and connectivity checks run
+// through here instead of keeping their own busy flags and progress events.
+// Each operation gets an id, reports state changes and progress as
+// `operation-progress`, can be asked to stop with `cancel_operation` (it
+// stops at its next checkpoint), and stays in `list_operations` with its
+// outcome and error for the rest of the session. Kinds listed in LIMITS
+// queue extra operations until a slot frees up. After a reload the
+// frontend gets the unfinished ones re-emitted.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Condvar, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+use serde_json::Value;
+
+use super::error::BridgeError;
+
+/// Operations of a kind that may run at once; unlisted kinds are unlimited.
+const LIMITS: &[(&str, usize)] = &[("backup", 1), ("connectivity-check", 1)];
+/// Finished operations kept for inspection; the oldest go first.
+const MAX_FINISHED: usize = 50;
+
+static OPERATIONS: Mutex<Vec<Operation>> = Mutex::new(Vec::new());
+/// Signalled whenever an operation finishes or is cancelled, for the queue.
+static CHANGED: Condvar = Condvar::new();
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum OperationState {
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+impl OperationState {
+    fn is_active(self) -> bool {
+        matches!(self, Self::Queued | Self::Running)
+    }
+}
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Operation {
+    pub id: String,
+    pub kind: String,
+    pub description: String,
+    pub state: OperationState,
+    pub phase: Option<String>,
+    pub done: usize,
+    pub total: usize,
+    /// Kind-specific detail of the latest step, e.g. a check result.
+    pub detail: Option<Value>,
+    pub started_at: u64,
+    pub finished_at: Option<u64>,
+    /// The error it failed with, as commands report it.
+    pub error: Option<Value>,
+    pub cancel_requested: bool,
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+fn limit_of(kind: &str) -> usize {
+    LIMITS
+        .iter()
+        .find(|(k, _)| *k == kind)
+        .map_or(usize::MAX, |(_, n)| *n)
+}
+
+fn emit(app: &tauri::AppHandle, op: &Operation) {
+    let mut event = serde_json::to_value(op).unwrap_or_default();
+    event["type"] = serde_json::json!("operation-progress");
+    super::emit_p2p_event(app, event);
+}
+
+/// A registered operation, held by the code doing the work. Dropping it
+/// without `finish` (e.g. on a panic) marks the operation failed.
+pub(crate) struct OpHandle {
+    id: String,
+    app: tauri::AppHandle,
+}
+
+/// Register an operation and wait for a slot of its kind. Blocks while
+/// queued, so call it from a blocking task or thread.
+pub(crate) fn start(app: &tauri::AppHandle, kind: &str, description: &str) -> OpHandle {
+    let id = format!("op-{}", NEXT_ID.fetch_add(1, Ordering::SeqCst));
+    let mut op = Operation {
+        id: id.clone(),
+        kind: kind.to_string(),
+        description: description.to_string(),
+        state: OperationState::Queued,
+        phase: None,
+        done: 0,
+        total: 0,
+        detail: None,
+        started_at: now_ms(),
+        finished_at: None,
+        error: None,
+        cancel_requested: false,
+    };
+    let limit = limit_of(kind);
+    let mut ops = OPERATIONS.lock().unwrap_or_else(|e| e.into_inner());
+    let running = |ops: &[Operation]| {
+        ops.iter()
+            .filter(|o| o.kind == kind && o.state == OperationState::Running)
+            .count()
+    };
+    if running(&ops) >= limit {
+        ops.push(op.clone());
+        emit(app, &op);
+        loop {
+            ops = CHANGED.wait(ops).unwrap_or_else(|e| e.into_inner());
+            let cancelled = ops.iter().any(|o| o.id == id && o.cancel_requested);
+            if cancelled || running(&ops) < limit {
+                break;
+            }
+        }
+        // A cancelled operation still starts; its first checkpoint ends it
+        let Some(queued) = ops.iter_mut().find(|o| o.id == id) else {
+            return OpHandle {
+                id,
+                app: app.clone(),
+            };
+        };
+        queued.state = OperationState::Running;
+        op = queued.clone();
+    } else {
+        op.state = OperationState::Running;
+        ops.push(op.clone());
+    }
+    drop(ops);
+    emit(app, &op);
+    OpHandle {
+        id,
+        app: app.clone(),
+    }
+}
+
+impl OpHandle {
+    pub(crate) fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Update the operation and emit the change, if it is still listed.
+    fn update(&self, f: impl FnOnce(&mut Operation)) {
+        let snapshot = {
+            let mut ops = OPERATIONS.lock().unwrap_or_else(|e| e.into_inner());
+            ops.iter_mut().find(|o| o.id == self.id).map(|op| {
+                f(op);
+                op.clone()
+            })
+        };
+        if let Some(op) = snapshot {
+            emit(&self.app, &op);
+        }
+    }
+
+    pub(crate) fn progress(&self, phase: &str, done: usize, total: usize, detail: Option<Value>) {
+        self.update(|op| {
+            op.phase = Some(phase.to_string());
+            op.done = done;
+            op.total = total;
+            op.detail = detail;
+        });
+    }
+
+    fn cancel_requested(&self) -> bool {
+        OPERATIONS
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .any(|o| o.id == self.id && o.cancel_requested)
+    }
+
+    /// A point where the work can stop safely: errors if cancellation was
+    /// requested.
+    pub(crate) fn checkpoint(&self) -> Result<(), String> {
+        if self.cancel_requested() {
+            return Err("Cancelled".to_string());
+        }
+        Ok(())
+    }
+
+    /// Record the outcome. An error after a cancellation request counts as
+    /// cancelled and is returned as `BridgeError::Cancelled`.
+    pub(crate) fn finish<T>(self, result: Result<T, BridgeError>) -> Result<T, BridgeError> {
+        let result = match result {
+            Err(_) if self.cancel_requested() => Err(BridgeError::Cancelled),
+            other => other,
+        };
+        let (state, error) = match &result {
+            Ok(_) => (OperationState::Succeeded, None),
+            Err(BridgeError::Cancelled) => (OperationState::Cancelled, None),
+            Err(e) => (OperationState::Failed, serde_json::to_value(e).ok()),
+        };
+        self.end(state, error);
+        result
+    }
+
+    fn end(&self, state: OperationState, error: Option<Value>) {
+        self.update(|op| {
+            op.state = state;
+            op.finished_at = Some(now_ms());
+            op.error = error;
+        });
+        let mut ops = OPERATIONS.lock().unwrap_or_else(|e| e.into_inner());
+        let finished = ops.iter().filter(|o| !o.state.is_active()).count();
+        if finished > MAX_FINISHED {
+            if let Some(oldest) = ops.iter().position(|o| !o.state.is_active()) {
+                ops.remove(oldest);
+            }
+        }
+        CHANGED.notify_all();
+    }
+}
+
+impl Drop for OpHandle {
+    fn drop(&mut self) {
+        let active = OPERATIONS
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .any(|o| o.id == self.id && o.state.is_active());
+        if active {
+            let error = serde_json::to_value(BridgeError::from("Operation ended unexpectedly"));
+            self.end(OperationState::Failed, error.ok());
+        }
+    }
+}
+
+/// Re-emit unfinished operations for a frontend that has just (re)loaded.
+pub(crate) fn reemit_active(app: &tauri::AppHandle) {
+    let active: Vec<Operation> = OPERATIONS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .filter(|o| o.state.is_active())
+        .cloned()
+        .collect();
+    for op in &active {
+        emit(app, op);
+    }
+}
+
+// ── Tauri commands ───────────────────────────────────────────────
+
+/// Operations of this session, oldest first, finished ones included.
+#[tauri::command]
+pub fn list_operations() -> Vec<Operation> {
+    OPERATIONS.lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// Ask an operation to stop. It ends as `cancelled` at its next
+/// checkpoint; a queued one does so before any work.
+#[tauri::command]
+pub fn cancel_operation(app: tauri::AppHandle, id: String) -> Result<(), BridgeError> {
+    let snapshot = {
+        let mut ops = OPERATIONS.lock().unwrap_or_else(|e| e.into_inner());
+        let op = ops
+            .iter_mut()
+            .find(|o| o.id == id)
+            .ok_or_else(|| BridgeError::NotFound(format!("No operation '{}'", id)))?;
+        if !op.state.is_active() {
+            return Err(BridgeError::NotReady(format!(
+                "Operation '{}' has already finished",
+                id
+            )));
+        }
+        op.cancel_requested = true;
+        op.clone()
+    };
+    CHANGED.notify_all();
+    emit(&app, &snapshot);
+    Ok(())
+}
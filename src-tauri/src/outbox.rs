@@ -0,0 +1,152 @@
+// Outbox — commands written while the sidecar is starting or restarting
+// (the first seconds after launch, a crash-restart, a profile switch) wait
+// here instead of failing, and are written in order as soon as the next
+// sidecar is attached. `queue-flushed` reports how many went out.
+//
+// Nothing is queued once the sidecar is known to be down for good (its
+// start failed, or the supervisor gave up), during shutdown, or in safe
+// mode; those writes fail with `sidecar-not-running` as before. A full
+// queue rejects with `queue-full`. Queued messages that are dropped later,
+// because they waited past MAX_AGE or the sidecar failed, are reported by
+// id in `queue-dropped`. A second dial to an address already queued is
+// rejected rather than queued twice.
+//
+// `start_sidecar` holds the queue from just before the attach until the
+// flush, so a command is either queued before the flush or, once the
+// sidecar is attached, written directly; none can slip in between and wait
+// for the next start.
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use super::error::BridgeError;
+use super::protocol::SidecarCommand;
+
+const MAX_QUEUED: usize = 256;
+/// Queued commands older than this are dropped instead of written.
+const MAX_AGE: Duration = Duration::from_secs(60);
+
+static QUEUE: Mutex<VecDeque<Queued>> = Mutex::new(VecDeque::new());
+/// The sidecar failed to come up; writes fail until the next start.
+static DOWN: AtomicBool = AtomicBool::new(false);
+
+struct Queued {
+    line: String,
+    /// The `dial` address, for deduplication.
+    dial: Option<String>,
+    /// The message id of a `send`, for `queue-dropped`.
+    message_id: Option<String>,
+    queued_at: Instant,
+}
+
+/// The queue, held across attaching a new sidecar; see `hold`.
+pub(crate) struct Held(MutexGuard<'static, VecDeque<Queued>>);
+
+/// Hold the queue until `flush`. Taken by `start_sidecar` before it
+/// attaches the new sidecar.
+pub(crate) fn hold() -> Held {
+    Held(QUEUE.lock().unwrap_or_else(|e| e.into_inner()))
+}
+
+/// Hold `line` (the serialised `cmd`) until the sidecar is attached. Called
+/// by `write_to_sidecar` when the sidecar isn't running; written directly
+/// instead if it has been attached (and the queue flushed) since.
+pub(crate) fn enqueue(
+    app: &tauri::AppHandle,
+    cmd: &SidecarCommand,
+    line: String,
+) -> Result<(), BridgeError> {
+    if DOWN.load(Ordering::SeqCst) || super::shutdown::in_progress() || super::safe_mode::active() {
+        return Err(BridgeError::SidecarNotRunning);
+    }
+    let dial = match cmd {
+        SidecarCommand::Dial { address } => Some(address.trim().to_string()),
+        _ => None,
+    };
+    let mut queue = QUEUE.lock().unwrap_or_else(|e| e.into_inner());
+    let sidecar = super::sidecar::manager(app);
+    if sidecar.is_running() {
+        return sidecar.write_line(&line, false);
+    }
+    if let Some(ref address) = dial {
+        if queue.iter().any(|q| q.dial.as_ref() == Some(address)) {
+            return Err(BridgeError::NotReady(format!(
+                "A dial to {} is already waiting for the sidecar",
+                address
+            )));
+        }
+    }
+    if queue.len() >= MAX_QUEUED {
+        return Err(BridgeError::QueueFull);
+    }
+    queue.push_back(Queued {
+        line,
+        dial,
+        message_id: match cmd {
+            SidecarCommand::Send { id, .. } => Some(id.clone()),
+            _ => None,
+        },
+        queued_at: Instant::now(),
+    });
+    Ok(())
+}
+
+fn emit_dropped(app: &tauri::AppHandle, dropped: Vec<Queued>, reason: &str) {
+    if dropped.is_empty() {
+        return;
+    }
+    let message_ids: Vec<String> = dropped.into_iter().filter_map(|q| q.message_id).collect();
+    super::emit_p2p_event(
+        app,
+        serde_json::json!({
+            "type": "queue-dropped",
+            "reason": reason,
+            "messageIds": message_ids,
+        }),
+    );
+}
+
+/// Write everything queued, oldest first, and release the queue. Called
+/// right after a new sidecar is attached; it reads stdin once it is up, so
+/// nothing is lost to its startup.
+pub(crate) fn flush(app: &tauri::AppHandle, held: Held) {
+    DOWN.store(false, Ordering::SeqCst);
+    let Held(mut queue) = held;
+    let mut expired = Vec::new();
+    let mut written = 0;
+    while let Some(queued) = queue.pop_front() {
+        if queued.queued_at.elapsed() > MAX_AGE {
+            expired.push(queued);
+            continue;
+        }
+        if let Err(e) = super::sidecar::manager(app).write_line(&queued.line, false) {
+            // Gone again already; keep the rest for the next start
+            eprintln!("Outbox flush stopped: {}", e);
+            queue.push_front(queued);
+            break;
+        }
+        written += 1;
+    }
+    drop(queue);
+    if written > 0 {
+        super::emit_p2p_event(
+            app,
+            serde_json::json!({"type": "queue-flushed", "count": written}),
+        );
+    }
+    emit_dropped(app, expired, "expired");
+}
+
+/// The sidecar won't come up without the user's help: drop what is queued
+/// and stop queueing until the next start.
+pub(crate) fn mark_down(app: &tauri::AppHandle) {
+    DOWN.store(true, Ordering::SeqCst);
+    let dropped: Vec<Queued> = QUEUE
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .drain(..)
+        .collect();
+    emit_dropped(app, dropped, "sidecar-failed");
+}
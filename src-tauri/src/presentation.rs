@@ -0,0 +1,331 @@
+// Presentation hints — the colour and initials a peer or channel is drawn
+// with, decided by the bridge so every window (and every session) agrees.
+// A hue comes from a fixed hash of the id; when two recently active peers
+// would land on nearly the same hue, the one later in id order is moved
+// along by the golden angle until they are apart. User overrides persist
+// in presentation.json. `message` and `peer:connect`/`peer:disconnect`
+// events carry the sender's hint as `presentation`.
+//
+// The hash and its seed are part of the UI's look: changing them recolours
+// everyone, so bump HINT_VERSION and migrate overrides if you ever must.
+
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::error::BridgeError;
+
+pub(crate) const PRESENTATION_FILE: &str = "presentation.json";
+/// Version of the hue derivation, reported with every hint.
+const HINT_VERSION: u32 = 1;
+const HUE_SEED: &[u8] = b"concord-hue-v1";
+/// Hues closer than this among recently active peers are spread apart.
+const MIN_HUE_GAP: u16 = 24;
+/// Golden angle, in degrees; successive steps stay far from each other.
+const SPREAD_STEP: u16 = 137;
+const MAX_RECENT: usize = 24;
+/// libp2p Ed25519 peer ids all start with this, so it says nothing.
+const PEER_ID_PREFIX: &str = "12D3KooW";
+
+/// Overrides of the active profile, keyed by id.
+static OVERRIDES: Mutex<Option<(PathBuf, HashMap<String, HintOverride>)>> = Mutex::new(None);
+/// Peers that sent a message recently, most recent last.
+static RECENT: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default, rename_all = "camelCase")]
+pub struct HintOverride {
+    /// 0..360
+    pub hue: Option<u16>,
+    /// One or two characters.
+    pub initials: Option<String>,
+}
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PresentationHint {
+    pub id: String,
+    pub hue: u16,
+    pub initials: String,
+    /// Whether the user set the hue or initials by hand.
+    pub overridden: bool,
+    pub version: u32,
+}
+
+/// FNV-1a over the seed and the id; stable across platforms and Rust
+/// versions, unlike std's hashers.
+fn stable_hash(id: &str) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in HUE_SEED.iter().chain(id.as_bytes()) {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+fn base_hue(id: &str) -> u16 {
+    (stable_hash(id) % 360) as u16
+}
+
+fn hue_distance(a: u16, b: u16) -> u16 {
+    let d = a.abs_diff(b);
+    d.min(360 - d)
+}
+
+/// Hues of the recently active peers after spreading. Peers are placed in
+/// id order so the outcome doesn't depend on who spoke first.
+fn spread_hues(recent: &[String]) -> HashMap<String, u16> {
+    let mut ids: Vec<&String> = recent.iter().collect();
+    ids.sort();
+    ids.dedup();
+    let mut placed: HashMap<String, u16> = HashMap::new();
+    for id in ids {
+        let mut hue = base_hue(id);
+        // 360 / MIN_HUE_GAP attempts cover the wheel; past that, accept a clash
+        for _ in 0..(360 / MIN_HUE_GAP) {
+            if placed
+                .values()
+                .all(|h| hue_distance(*h, hue) >= MIN_HUE_GAP)
+            {
+                break;
+            }
+            hue = (hue + SPREAD_STEP) % 360;
+        }
+        placed.insert(id.clone(), hue);
+    }
+    placed
+}
+
+/// Up to two letters: the start of the significant part of a peer id, or
+/// the first letters of a channel's first two words.
+fn initials_of(id: &str) -> String {
+    if let Some(rest) = id.strip_prefix(PEER_ID_PREFIX) {
+        return rest
+            .chars()
+            .filter(char::is_ascii_alphanumeric)
+            .take(2)
+            .collect::<String>()
+            .to_uppercase();
+    }
+    let name = id.strip_prefix("dm:").unwrap_or(id);
+    let words: Vec<&str> = name
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .collect();
+    let initials: String = match words.as_slice() {
+        [] => "?".to_string(),
+        [only] => only.chars().take(2).collect(),
+        [first, second, ..] => first
+            .chars()
+            .take(1)
+            .chain(second.chars().take(1))
+            .collect(),
+    };
+    initials.to_uppercase()
+}
+
+fn load(path: &Path) -> HashMap<String, HintOverride> {
+    match super::storage::read_to_string(path) {
+        Ok(s) => serde_json::from_str(&s).unwrap_or_else(|e| {
+            eprintln!("Ignoring malformed {}: {}", path.display(), e);
+            HashMap::new()
+        }),
+        Err(_) => HashMap::new(),
+    }
+}
+
+fn save(path: &Path, overrides: &HashMap<String, HintOverride>) -> Result<(), BridgeError> {
+    let json = serde_json::to_string_pretty(overrides).map_err(|e| e.to_string())?;
+    super::storage::write(path, json)
+        .map_err(|e| BridgeError::io("Cannot save presentation overrides", e))
+}
+
+/// Drop the cached overrides so the next use reads them from disk (after a
+/// restore replaced the file at the same path).
+pub(crate) fn invalidate() {
+    *OVERRIDES.lock().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+/// Run `f` on the active profile's overrides, loading them after a profile
+/// switch.
+fn with_overrides<T>(
+    f: impl FnOnce(&Path, &mut HashMap<String, HintOverride>) -> T,
+) -> Result<T, BridgeError> {
+    let path = super::app_data_dir()?.join(PRESENTATION_FILE);
+    let mut guard = OVERRIDES.lock().unwrap_or_else(|e| e.into_inner());
+    if !matches!(*guard, Some((ref cached, _)) if *cached == path) {
+        *guard = Some((path.clone(), load(&path)));
+    }
+    let Some((_, ref mut overrides)) = *guard else {
+        return Err("Presentation state unavailable".into());
+    };
+    Ok(f(&path, overrides))
+}
+
+fn hints_for(ids: &[String]) -> Vec<PresentationHint> {
+    let recent: Vec<String> = RECENT
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .cloned()
+        .collect();
+    let spread = spread_hues(&recent);
+    let overrides = with_overrides(|_, o| o.clone()).unwrap_or_default();
+    ids.iter()
+        .map(|id| {
+            let o = overrides.get(id).cloned().unwrap_or_default();
+            PresentationHint {
+                id: id.clone(),
+                hue: o
+                    .hue
+                    .or_else(|| spread.get(id).copied())
+                    .unwrap_or_else(|| base_hue(id)),
+                initials: o.initials.clone().unwrap_or_else(|| initials_of(id)),
+                overridden: o.hue.is_some() || o.initials.is_some(),
+                version: HINT_VERSION,
+            }
+        })
+        .collect()
+}
+
+/// Called by the stdout reader: note who is active and attach the sender's
+/// hint to messages and peer events.
+pub(crate) fn annotate(event: &mut Value) {
+    let id = match event["type"].as_str() {
+        Some("message") => event["from"].as_str(),
+        Some("peer:connect" | "peer:disconnect") => event["peerId"].as_str(),
+        _ => None,
+    };
+    let Some(id) = id.map(str::to_string) else {
+        return;
+    };
+    if event["type"].as_str() == Some("message") {
+        let mut recent = RECENT.lock().unwrap_or_else(|e| e.into_inner());
+        recent.retain(|r| *r != id);
+        recent.push_back(id.clone());
+        if recent.len() > MAX_RECENT {
+            recent.pop_front();
+        }
+    }
+    if let Some(hint) = hints_for(&[id]).pop() {
+        event["presentation"] = serde_json::to_value(hint).unwrap_or_default();
+    }
+}
+
+/// Move overrides recorded under the old peer id to the new one (identity
+/// regeneration).
+pub(crate) fn rekey_peer(old: &str, new: &str) -> Result<(), BridgeError> {
+    with_overrides(|path, overrides| {
+        if super::identity::rekey_map(overrides, old, new) {
+            save(path, overrides)?;
+        }
+        Ok(())
+    })?
+}
+
+// ── Tauri commands ───────────────────────────────────────────────
+
+/// Hints for peer and channel ids, in the order given.
+#[tauri::command]
+pub fn get_presentation_hints(ids: Vec<String>) -> Vec<PresentationHint> {
+    hints_for(&ids)
+}
+
+/// Set or clear (`hint` = null) the user's override for `id`, and emit
+/// `presentation-changed` so every window redraws it.
+#[tauri::command]
+pub fn set_presentation_override(
+    app: tauri::AppHandle,
+    id: String,
+    hint: Option<HintOverride>,
+) -> Result<PresentationHint, BridgeError> {
+    if let Some(ref h) = hint {
+        if h.hue.is_some_and(|hue| hue >= 360) {
+            return Err(BridgeError::InvalidArgument(
+                "Hue must be between 0 and 359".to_string(),
+            ));
+        }
+        if h.initials
+            .as_ref()
+            .is_some_and(|i| !(1..=2).contains(&i.chars().count()))
+        {
+            return Err(BridgeError::InvalidArgument(
+                "Initials must be one or two characters".to_string(),
+            ));
+        }
+    }
+    with_overrides(|path, overrides| {
+        match hint {
+            Some(h) if h.hue.is_some() || h.initials.is_some() => {
+                overrides.insert(id.clone(), h);
+            }
+            _ => {
+                overrides.remove(&id);
+            }
+        }
+        save(path, overrides)
+    })??;
+    let hint = hints_for(std::slice::from_ref(&id))
+        .pop()
+        .ok_or(BridgeError::NotFound(id))?;
+    let mut event = serde_json::json!({"type": "presentation-changed"});
+    event["hint"] = serde_json::to_value(&hint).unwrap_or_default();
+    super::emit_p2p_event(&app, event);
+    Ok(hint)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hues_are_pinned() {
+        // Changing these recolours every user; see HINT_VERSION
+        assert_eq!(stable_hash("general"), 0x46e0_390d_92dc_399a);
+        assert_eq!(base_hue("general"), 26);
+        assert_eq!(base_hue("12D3KooWAbc"), 180);
+        assert_eq!(base_hue("dm:alice"), 183);
+    }
+
+    #[test]
+    fn hue_distance_wraps_around_the_wheel() {
+        assert_eq!(hue_distance(10, 350), 20);
+        assert_eq!(hue_distance(350, 10), 20);
+        assert_eq!(hue_distance(0, 180), 180);
+        assert_eq!(hue_distance(90, 90), 0);
+    }
+
+    #[test]
+    fn clashing_hues_are_spread_the_same_way_in_any_order() {
+        let a = "12D3KooWAbc".to_string();
+        let b = "dm:alice".to_string();
+        let spread = spread_hues(&[b.clone(), a.clone(), b.clone()]);
+        assert_eq!(spread[&a], 180);
+        assert_eq!(spread[&b], (183 + SPREAD_STEP) % 360);
+        assert_eq!(spread, spread_hues(&[a, b]));
+    }
+
+    #[test]
+    fn a_handful_of_active_peers_never_clash() {
+        let recent: Vec<String> = (0..5).map(|i| format!("peer-{}", i)).collect();
+        let hues: Vec<u16> = spread_hues(&recent).into_values().collect();
+        for (i, a) in hues.iter().enumerate() {
+            for b in &hues[i + 1..] {
+                assert!(hue_distance(*a, *b) >= MIN_HUE_GAP);
+            }
+        }
+    }
+
+    #[test]
+    fn initials_skip_the_peer_id_prefix_and_use_words() {
+        assert_eq!(initials_of("12D3KooWxy9abc"), "XY");
+        assert_eq!(initials_of("12D3KooW-z"), "Z");
+        assert_eq!(initials_of("general"), "GE");
+        assert_eq!(initials_of("dev-ops team"), "DO");
+        assert_eq!(initials_of("dm:alice"), "AL");
+        assert_eq!(initials_of("#!"), "?");
+    }
+}
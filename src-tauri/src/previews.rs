@@ -0,0 +1,604 @@
+// Link previews — fetched by the bridge instead of the webview, so a URL
+// posted in a channel does not reach every reader's IP.
+// A single worker takes URLs from a bounded queue, fetches each page under
+// strict limits (size, time, redirects, no private addresses), extracts the
+// OpenGraph/title metadata and emits a follow-up `preview-ready` event.
+// Results, including failures, are cached in memory by URL hash.
+
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io::{self, Read};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs};
+use std::sync::mpsc::{self, SyncSender, TrySendError};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use base64::Engine;
+use serde::Serialize;
+use url::{Host, Url};
+
+use super::settings::{LinkPreviewMode, PrivacySettings};
+
+const QUEUE_CAPACITY: usize = 32;
+const MAX_URLS_PER_MESSAGE: usize = 3;
+const MAX_REDIRECTS: usize = 3;
+const FETCH_TIMEOUT: Duration = Duration::from_secs(8);
+const USER_AGENT: &str = "Concord-LinkPreview/1";
+
+// Hostile-content limits: only the head of a page is read, and the scanner
+// gives up after a fixed number of tags.
+const MAX_PAGE_BYTES: u64 = 512 * 1024;
+const MAX_IMAGE_BYTES: u64 = 256 * 1024;
+const MAX_TAGS: usize = 2000;
+const MAX_ATTRS: usize = 32;
+const MAX_FIELD_CHARS: usize = 300;
+const IMAGE_TYPES: [&str; 4] = ["image/png", "image/jpeg", "image/gif", "image/webp"];
+
+const CACHE_TTL: Duration = Duration::from_secs(6 * 60 * 60);
+const CACHE_MAX_ENTRIES: usize = 500;
+
+static QUEUE: Mutex<Option<SyncSender<Job>>> = Mutex::new(None);
+/// When a URL was fetched, and its preview (`None` for a failed fetch).
+type CacheEntry = (Instant, Option<LinkPreview>);
+static CACHE: Mutex<Option<HashMap<u64, CacheEntry>>> = Mutex::new(None);
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct LinkPreview {
+    pub url: String,
+    pub final_url: String,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub site_name: Option<String>,
+    /// Thumbnail as a `data:` URL, so the webview never contacts the host.
+    pub image: Option<String>,
+}
+
+struct Job {
+    url: Url,
+    channel_id: String,
+    from: Option<String>,
+}
+
+#[derive(Default)]
+struct PageMeta {
+    title: Option<String>,
+    description: Option<String>,
+    site_name: Option<String>,
+    image: Option<String>,
+}
+
+// ── Address policy ───────────────────────────────────────────────
+
+/// The IPv4 address inside an IPv6 one that routes to it: IPv4-mapped
+/// `::ffff:a.b.c.d`, IPv4-compatible `::a.b.c.d`, NAT64 `64:ff9b::a.b.c.d`
+/// or 6to4 `2002:aabb:ccdd::/48`.
+fn embedded_ipv4(v6: &Ipv6Addr) -> Option<Ipv4Addr> {
+    if let Some(v4) = v6.to_ipv4_mapped() {
+        return Some(v4);
+    }
+    let join = |hi: u16, lo: u16| Ipv4Addr::from((u32::from(hi) << 16) | u32::from(lo));
+    match v6.segments() {
+        [0, 0, 0, 0, 0, 0, hi, lo] | [0x64, 0xff9b, 0, 0, 0, 0, hi, lo] => Some(join(hi, lo)),
+        [0x2002, hi, lo, ..] => Some(join(hi, lo)),
+        _ => None,
+    }
+}
+
+fn is_blocked_ip(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => {
+            let o = v4.octets();
+            v4.is_private()
+                || v4.is_loopback()
+                || v4.is_link_local()
+                || v4.is_unspecified()
+                || v4.is_broadcast()
+                || v4.is_multicast()
+                || v4.is_documentation()
+                || o[0] == 0
+                // Carrier-grade NAT, 100.64.0.0/10
+                || (o[0] == 100 && (o[1] & 0xc0) == 64)
+        }
+        IpAddr::V6(v6) => {
+            if let Some(v4) = embedded_ipv4(v6) {
+                return is_blocked_ip(&IpAddr::V4(v4));
+            }
+            let first = v6.segments()[0];
+            v6.is_loopback()
+                || v6.is_unspecified()
+                || v6.is_multicast()
+                || (first & 0xfe00) == 0xfc00
+                || (first & 0xffc0) == 0xfe80
+        }
+    }
+}
+
+/// Reject anything but plain http(s) URLs to public hosts.
+fn check_url(url: &Url) -> Result<(), String> {
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(format!("Unsupported scheme '{}'", url.scheme()));
+    }
+    if !url.username().is_empty() || url.password().is_some() {
+        return Err("URLs with credentials are not fetched".to_string());
+    }
+    match url.host() {
+        Some(Host::Domain(domain)) => {
+            let domain = domain.trim_end_matches('.').to_ascii_lowercase();
+            if domain == "localhost" || domain.ends_with(".localhost") || domain.ends_with(".local")
+            {
+                return Err(format!("{} is a local address", domain));
+            }
+            Ok(())
+        }
+        Some(Host::Ipv4(ip)) if is_blocked_ip(&IpAddr::V4(ip)) => {
+            Err(format!("{} is a private address", ip))
+        }
+        Some(Host::Ipv6(ip)) if is_blocked_ip(&IpAddr::V6(ip)) => {
+            Err(format!("{} is a private address", ip))
+        }
+        Some(_) => Ok(()),
+        None => Err("URL has no host".to_string()),
+    }
+}
+
+/// DNS resolver for direct fetches. Filtering resolved addresses (not just
+/// the URL) stops public names that point at private ranges.
+fn public_resolver(netloc: &str) -> io::Result<Vec<SocketAddr>> {
+    let addrs: Vec<SocketAddr> = netloc
+        .to_socket_addrs()?
+        .filter(|a| !is_blocked_ip(&a.ip()))
+        .collect();
+    if addrs.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("{} resolves only to private addresses", netloc),
+        ));
+    }
+    Ok(addrs)
+}
+
+// ── URL extraction ───────────────────────────────────────────────
+
+/// Up to MAX_URLS_PER_MESSAGE distinct fetchable URLs in a message's text.
+fn extract_urls(text: &str) -> Vec<Url> {
+    let mut urls: Vec<Url> = Vec::new();
+    for word in text.split_whitespace() {
+        let Some(start) = word.find("http://").or_else(|| word.find("https://")) else {
+            continue;
+        };
+        let candidate = word[start..].trim_end_matches(|c: char| ".,;:!?)]}>'\"".contains(c));
+        let Ok(url) = Url::parse(candidate) else {
+            continue;
+        };
+        if check_url(&url).is_ok() && !urls.contains(&url) {
+            urls.push(url);
+            if urls.len() == MAX_URLS_PER_MESSAGE {
+                break;
+            }
+        }
+    }
+    urls
+}
+
+/// Chat payloads are JSON strings with the text under `content`.
+fn message_text(data: &str) -> Option<String> {
+    serde_json::from_str::<serde_json::Value>(data)
+        .ok()?
+        .get("content")?
+        .as_str()
+        .map(str::to_string)
+}
+
+// ── HTML metadata ────────────────────────────────────────────────
+
+fn decode_entities(s: &str) -> String {
+    s.replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#x27;", "'")
+        .replace("&apos;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&")
+}
+
+/// Decode, collapse whitespace and cap the length of a metadata field.
+fn clean(s: &str) -> Option<String> {
+    let text = decode_entities(s)
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+    if text.is_empty() {
+        return None;
+    }
+    Some(text.chars().take(MAX_FIELD_CHARS).collect())
+}
+
+/// Attributes of a tag body (the text after the tag name). Indices only ever
+/// stop on ASCII bytes, so the slices stay on char boundaries.
+fn parse_attrs(s: &str) -> Vec<(String, String)> {
+    let b = s.as_bytes();
+    let mut attrs = Vec::new();
+    let mut i = 0;
+    while i < b.len() && attrs.len() < MAX_ATTRS {
+        while i < b.len() && (b[i].is_ascii_whitespace() || b[i] == b'/') {
+            i += 1;
+        }
+        let name_start = i;
+        while i < b.len() && !b[i].is_ascii_whitespace() && b[i] != b'=' && b[i] != b'/' {
+            i += 1;
+        }
+        let name = s[name_start..i].to_ascii_lowercase();
+        while i < b.len() && b[i].is_ascii_whitespace() {
+            i += 1;
+        }
+        if i >= b.len() || b[i] != b'=' {
+            if !name.is_empty() {
+                attrs.push((name, String::new()));
+            }
+            continue;
+        }
+        i += 1;
+        while i < b.len() && b[i].is_ascii_whitespace() {
+            i += 1;
+        }
+        let value = if i < b.len() && (b[i] == b'"' || b[i] == b'\'') {
+            let quote = b[i];
+            i += 1;
+            let start = i;
+            while i < b.len() && b[i] != quote {
+                i += 1;
+            }
+            let value = &s[start..i];
+            i = (i + 1).min(b.len());
+            value
+        } else {
+            let start = i;
+            while i < b.len() && !b[i].is_ascii_whitespace() {
+                i += 1;
+            }
+            &s[start..i]
+        };
+        attrs.push((name, value.to_string()));
+    }
+    attrs
+}
+
+fn attr<'a>(attrs: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    attrs
+        .iter()
+        .find(|(n, _)| n == name)
+        .map(|(_, v)| v.as_str())
+}
+
+/// Scan the document head for OpenGraph tags, falling back to `<title>` and
+/// the description meta tag. Stops at `</head>`, `<body>` or MAX_TAGS.
+fn extract_meta(html: &str) -> PageMeta {
+    let mut meta = PageMeta::default();
+    let mut doc_title = None;
+    let mut rest = html;
+    for _ in 0..MAX_TAGS {
+        let Some(start) = rest.find('<') else {
+            break;
+        };
+        rest = &rest[start + 1..];
+        let Some(end) = rest.find('>') else {
+            break;
+        };
+        let tag = &rest[..end];
+        rest = &rest[end + 1..];
+        let name_end = tag
+            .find(|c: char| c.is_ascii_whitespace() || (c == '/' && !tag.starts_with('/')))
+            .unwrap_or(tag.len());
+        match tag[..name_end].to_ascii_lowercase().as_str() {
+            "meta" => {
+                let attrs = parse_attrs(&tag[name_end..]);
+                let Some(content) = attr(&attrs, "content") else {
+                    continue;
+                };
+                let key = attr(&attrs, "property")
+                    .or_else(|| attr(&attrs, "name"))
+                    .map(str::to_ascii_lowercase);
+                match key.as_deref() {
+                    Some("og:title") => meta.title = clean(content),
+                    Some("og:description") => meta.description = clean(content),
+                    Some("description") if meta.description.is_none() => {
+                        meta.description = clean(content)
+                    }
+                    Some("og:site_name") => meta.site_name = clean(content),
+                    Some("og:image" | "og:image:url" | "twitter:image") if meta.image.is_none() => {
+                        meta.image = Some(decode_entities(content.trim()))
+                    }
+                    _ => {}
+                }
+            }
+            "title" if doc_title.is_none() => {
+                if let Some(len) = rest.to_ascii_lowercase().find("</title") {
+                    doc_title = clean(&rest[..len]);
+                }
+            }
+            "/head" | "body" => break,
+            _ => {}
+        }
+    }
+    if meta.title.is_none() {
+        meta.title = doc_title;
+    }
+    meta
+}
+
+// ── Fetching ─────────────────────────────────────────────────────
+
+fn build_agent(privacy: &PrivacySettings) -> Result<ureq::Agent, String> {
+    let builder = ureq::AgentBuilder::new()
+        .timeout(FETCH_TIMEOUT)
+        .redirects(0)
+        .user_agent(USER_AGENT);
+    // Behind a proxy the proxy does the DNS lookup, so only the URL checks apply.
+    let builder = match privacy.preview_proxy.as_deref().filter(|p| !p.is_empty()) {
+        Some(proxy) => builder
+            .proxy(ureq::Proxy::new(proxy).map_err(|e| format!("Invalid preview proxy: {}", e))?),
+        None if privacy.preview_proxy_only => {
+            return Err("Proxy-only mode is on but no preview proxy is set".to_string())
+        }
+        None => builder.resolver(public_resolver),
+    };
+    Ok(builder.build())
+}
+
+/// GET `url`, following at most MAX_REDIRECTS redirects by hand so every hop
+/// goes through `check_url`.
+fn fetch(agent: &ureq::Agent, url: &Url, accept: &str) -> Result<(Url, ureq::Response), String> {
+    let mut current = url.clone();
+    for _ in 0..=MAX_REDIRECTS {
+        check_url(&current)?;
+        let resp = agent
+            .get(current.as_str())
+            .set("Accept", accept)
+            .call()
+            .map_err(|e| e.to_string())?;
+        if !(300..400).contains(&resp.status()) {
+            return Ok((current, resp));
+        }
+        let location = resp
+            .header("location")
+            .ok_or("Redirect without a Location header")?;
+        current = current.join(location).map_err(|e| e.to_string())?;
+    }
+    Err("Too many redirects".to_string())
+}
+
+/// Read at most `limit` bytes of the body; `Ok(None)` if it is longer.
+fn read_limited(resp: ureq::Response, limit: u64) -> Result<Option<Vec<u8>>, String> {
+    let declared = resp
+        .header("content-length")
+        .and_then(|l| l.parse::<u64>().ok());
+    if declared.is_some_and(|len| len > limit) {
+        return Ok(None);
+    }
+    let mut body = Vec::new();
+    resp.into_reader()
+        .take(limit + 1)
+        .read_to_end(&mut body)
+        .map_err(|e| e.to_string())?;
+    Ok((body.len() as u64 <= limit).then_some(body))
+}
+
+fn fetch_image(agent: &ureq::Agent, url: &Url) -> Option<String> {
+    let (_, resp) = fetch(agent, url, "image/*").ok()?;
+    let content_type = resp.content_type().to_ascii_lowercase();
+    // SVG is deliberately excluded: it is a document, not an image.
+    if !IMAGE_TYPES.contains(&content_type.as_str()) {
+        return None;
+    }
+    let body = read_limited(resp, MAX_IMAGE_BYTES).ok()??;
+    Some(format!(
+        "data:{};base64,{}",
+        content_type,
+        base64::engine::general_purpose::STANDARD.encode(body)
+    ))
+}
+
+fn generate(agent: &ureq::Agent, url: &Url) -> Result<Option<LinkPreview>, String> {
+    let (final_url, resp) = fetch(agent, url, "text/html")?;
+    let content_type = resp.content_type().to_ascii_lowercase();
+    if !matches!(content_type.as_str(), "text/html" | "application/xhtml+xml") {
+        return Ok(None);
+    }
+    // Oversized pages are not an error: the head is all we need.
+    let mut body = Vec::new();
+    resp.into_reader()
+        .take(MAX_PAGE_BYTES)
+        .read_to_end(&mut body)
+        .map_err(|e| e.to_string())?;
+    let meta = extract_meta(&String::from_utf8_lossy(&body));
+    if meta.title.is_none() && meta.description.is_none() {
+        return Ok(None);
+    }
+    let image = meta
+        .image
+        .and_then(|src| final_url.join(&src).ok())
+        .and_then(|src| fetch_image(agent, &src));
+    Ok(Some(LinkPreview {
+        url: url.to_string(),
+        final_url: final_url.to_string(),
+        title: meta.title,
+        description: meta.description,
+        site_name: meta.site_name,
+        image,
+    }))
+}
+
+// ── Cache ────────────────────────────────────────────────────────
+
+fn url_key(url: &Url) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    url.as_str().hash(&mut hasher);
+    hasher.finish()
+}
+
+fn cached(key: u64) -> Option<Option<LinkPreview>> {
+    let guard = CACHE.lock().ok()?;
+    match guard.as_ref()?.get(&key) {
+        Some((at, preview)) if at.elapsed() < CACHE_TTL => Some(preview.clone()),
+        _ => None,
+    }
+}
+
+fn store(key: u64, preview: Option<LinkPreview>) {
+    let Ok(mut guard) = CACHE.lock() else {
+        return;
+    };
+    let cache = guard.get_or_insert_with(HashMap::new);
+    cache.retain(|_, (at, _)| at.elapsed() < CACHE_TTL);
+    if cache.len() >= CACHE_MAX_ENTRIES {
+        if let Some(oldest) = cache.iter().min_by_key(|(_, (at, _))| *at).map(|(k, _)| *k) {
+            cache.remove(&oldest);
+        }
+    }
+    cache.insert(key, (Instant::now(), preview));
+}
+
+// ── Worker ───────────────────────────────────────────────────────
+
+fn process(app: &tauri::AppHandle, job: Job) {
+    let key = url_key(&job.url);
+    let preview = match cached(key) {
+        Some(preview) => preview,
+        None => {
+            let privacy = super::settings::current().privacy;
+            let result = build_agent(&privacy).and_then(|agent| generate(&agent, &job.url));
+            match result {
+                Ok(preview) => {
+                    store(key, preview.clone());
+                    preview
+                }
+                Err(e) => {
+                    eprintln!("Link preview for {} failed: {}", job.url, e);
+                    store(key, None);
+                    None
+                }
+            }
+        }
+    };
+    if let Some(preview) = preview {
+        super::emit_p2p_event(
+            app,
+            serde_json::json!({
+                "type": "preview-ready",
+                "channelId": job.channel_id,
+                "from": job.from,
+                "url": job.url.as_str(),
+                "preview": preview,
+            }),
+        );
+    }
+}
+
+/// Background thread that generates previews queued by `observe_event`
+/// and `observe_outbound`.
+pub(crate) fn start_worker(app: tauri::AppHandle) {
+    let (tx, rx) = mpsc::sync_channel::<Job>(QUEUE_CAPACITY);
+    if let Ok(mut guard) = QUEUE.lock() {
+        *guard = Some(tx);
+    }
+    thread::spawn(move || {
+        for job in rx {
+            process(&app, job);
+        }
+    });
+}
+
+fn enqueue(app: &tauri::AppHandle, channel_id: &str, from: Option<&str>, data: &str) {
+    // Incognito sessions never make requests on the user's behalf.
+    if super::sidecar::manager(app).incognito() {
+        return;
+    }
+    let Some(text) = message_text(data) else {
+        return;
+    };
+    let Ok(guard) = QUEUE.lock() else {
+        return;
+    };
+    let Some(ref tx) = *guard else {
+        return;
+    };
+    for url in extract_urls(&text) {
+        let job = Job {
+            url,
+            channel_id: channel_id.to_string(),
+            from: from.map(str::to_string),
+        };
+        // A full queue drops the preview rather than blocking the event reader
+        if let Err(TrySendError::Full(job)) = tx.try_send(job) {
+            eprintln!("Link preview queue full, skipping {}", job.url);
+        }
+    }
+}
+
+/// Called by the stdout reader for every parsed sidecar event.
+pub(crate) fn observe_event(app: &tauri::AppHandle, event: &serde_json::Value) {
+    if event.get("type").and_then(|t| t.as_str()) != Some("message") {
+        return;
+    }
+    if super::settings::current().privacy.link_previews != LinkPreviewMode::Everyone {
+        return;
+    }
+    enqueue(
+        app,
+        event["channelId"].as_str().unwrap_or_default(),
+        event["from"].as_str(),
+        event["data"].as_str().unwrap_or_default(),
+    );
+}
+
+/// Called by `p2p_send` once a message has been handed to the sidecar.
+pub(crate) fn observe_outbound(app: &tauri::AppHandle, channel_id: &str, data: &str) {
+    if super::settings::current().privacy.link_previews == LinkPreviewMode::Off {
+        return;
+    }
+    enqueue(app, channel_id, None, data);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn private_and_special_addresses_are_blocked() {
+        let cases = [
+            ("10.1.2.3", true),
+            ("127.0.0.1", true),
+            ("169.254.169.254", true),
+            ("100.64.0.1", true),
+            ("0.1.2.3", true),
+            ("93.184.216.34", false),
+            ("::1", true),
+            ("::", true),
+            ("fd00::1", true),
+            ("fe80::1", true),
+            ("2606:4700::1111", false),
+            // IPv4-mapped
+            ("::ffff:127.0.0.1", true),
+            ("::ffff:93.184.216.34", false),
+            // IPv4-compatible
+            ("::127.0.0.1", true),
+            ("::10.0.0.1", true),
+            ("::93.184.216.34", false),
+            // NAT64
+            ("64:ff9b::127.0.0.1", true),
+            ("64:ff9b::192.168.1.1", true),
+            ("64:ff9b::93.184.216.34", false),
+            // 6to4
+            ("2002:7f00:1::", true),
+            ("2002:a9fe:a9fe::1", true),
+            ("2002:5db8:d822::", false),
+        ];
+        for (ip, blocked) in cases {
+            let parsed: IpAddr = ip.parse().unwrap();
+            assert_eq!(is_blocked_ip(&parsed), blocked, "{}", ip);
+        }
+    }
+}
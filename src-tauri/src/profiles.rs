@@ -0,0 +1,577 @@
+// Profiles — independent identities under one install.
+// Layout: <data root>/profiles/<name>/ holds everything the sidecar and bridge
+// persist for that identity; <data root>/profiles.json remembers the active one.
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use super::error::BridgeError;
+pub const DEFAULT_PROFILE: &str = "default";
+
+const MAX_NAME_LEN: usize = 32;
+/// Where the legacy layout is gathered before it becomes `profiles/`.
+const MIGRATION_DIR: &str = "profiles.migrating";
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(2);
+
+pub const EPHEMERAL_UNAVAILABLE: &str =
+    "Not available in ephemeral mode — end the ephemeral session first";
+
+// Windows device names that cannot be used as file or directory names
+const RESERVED_NAMES: [&str; 22] = [
+    "con", "prn", "aux", "nul", "com1", "com2", "com3", "com4", "com5", "com6", "com7", "com8",
+    "com9", "lpt1", "lpt2", "lpt3", "lpt4", "lpt5", "lpt6", "lpt7", "lpt8", "lpt9",
+];
+
+static ACTIVE_PROFILE: Mutex<Option<String>> = Mutex::new(None);
+static SWITCHING: AtomicBool = AtomicBool::new(false);
+
+// Ephemeral (guest) session: data dir override and the incognito flag to
+// restore when the session ends.
+static EPHEMERAL_DIR: Mutex<Option<PathBuf>> = Mutex::new(None);
+static EPHEMERAL: AtomicBool = AtomicBool::new(false);
+static RESUME_INCOGNITO: AtomicBool = AtomicBool::new(false);
+
+#[derive(Serialize, Deserialize, Default)]
+struct ProfilesFile {
+    active: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProfileInfo {
+    pub name: String,
+    pub active: bool,
+}
+
+// ── Paths ────────────────────────────────────────────────────────
+
+fn profiles_root() -> Result<PathBuf, BridgeError> {
+    Ok(super::data_root()?.join("profiles"))
+}
+
+fn profiles_file() -> Result<PathBuf, BridgeError> {
+    Ok(super::data_root()?.join("profiles.json"))
+}
+
+pub(crate) fn profile_dir(name: &str) -> Result<PathBuf, BridgeError> {
+    Ok(profiles_root()?.join(name))
+}
+
+/// Directory of the active profile (or the ephemeral session's temp dir).
+/// Everything profile-scoped hangs off this.
+pub(crate) fn active_profile_dir() -> Result<PathBuf, BridgeError> {
+    if let Some(dir) = ephemeral_dir() {
+        return Ok(dir);
+    }
+    profile_dir(&active_profile()?)
+}
+
+pub(crate) fn active_profile() -> Result<String, BridgeError> {
+    let mut guard = ACTIVE_PROFILE.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(ref name) = *guard {
+        return Ok(name.clone());
+    }
+    if let Err(e) = migrate_legacy_layout() {
+        // On a full or read-only disk, start without migrating; it runs
+        // again on the next launch.
+        if super::storage::degraded_reason().is_none() {
+            return Err(e);
+        }
+        eprintln!("Profile migration postponed: {}", e);
+    }
+    let name = super::storage::read_to_string(&profiles_file()?)
+        .ok()
+        .and_then(|s| serde_json::from_str::<ProfilesFile>(&s).ok())
+        .map(|f| f.active)
+        .filter(|n| validate_name(n).is_ok() && profile_dir(n).map(|d| d.is_dir()).unwrap_or(false))
+        .unwrap_or_else(|| DEFAULT_PROFILE.to_string());
+    *guard = Some(name.clone());
+    Ok(name)
+}
+
+fn set_active_profile(name: &str) -> Result<(), BridgeError> {
+    let json = serde_json::to_string_pretty(&ProfilesFile {
+        active: name.to_string(),
+    })
+    .map_err(|e| e.to_string())?;
+    super::storage::write(&profiles_file()?, json)
+        .map_err(|e| BridgeError::io("Cannot save active profile", e))?;
+    *ACTIVE_PROFILE.lock().unwrap_or_else(|e| e.into_inner()) = Some(name.to_string());
+    Ok(())
+}
+
+/// First run with profile support: move whatever lives directly in the data
+/// root (identity, logs, reports) into `profiles/default/`.
+fn migrate_legacy_layout() -> Result<(), BridgeError> {
+    migrate_root(&super::data_root()?, |from, to| fs::rename(from, to))
+}
+
+/// The migration for the data root `root`, moving entries with `move_entry`.
+/// Entries are gathered in `profiles.migrating/default/`, which becomes
+/// `profiles/` only once every one of them is in, so a failure partway
+/// leaves the rest for the next launch. Should a postponed run have
+/// started a `profiles/` meanwhile, the legacy entries are moved into it and
+/// win over what that run wrote, since they hold the real identity.
+fn migrate_root(
+    root: &Path,
+    mut move_entry: impl FnMut(&Path, &Path) -> io::Result<()>,
+) -> Result<(), BridgeError> {
+    let profiles = root.join("profiles");
+    let staging = root.join(MIGRATION_DIR);
+    if profiles.is_dir() && !staging.is_dir() {
+        return Ok(());
+    }
+    let gathered = staging.join(DEFAULT_PROFILE);
+    fs::create_dir_all(&gathered).map_err(|e| {
+        super::storage::note_failure("Cannot create default profile", &e);
+        BridgeError::io("Cannot create default profile", e)
+    })?;
+
+    let entries =
+        fs::read_dir(root).map_err(|e| BridgeError::io("Cannot read data directory", e))?;
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        if name == "profiles" || name == "profiles.json" || name == MIGRATION_DIR {
+            continue;
+        }
+        move_entry(&entry.path(), &gathered.join(&name)).map_err(|e| {
+            BridgeError::io(
+                format!(
+                    "Cannot migrate {} into the default profile",
+                    name.to_string_lossy()
+                ),
+                e,
+            )
+        })?;
+    }
+
+    let unfinished = |e: io::Error| BridgeError::io("Cannot finish the profile migration", e);
+    if !profiles.is_dir() {
+        return fs::rename(&staging, &profiles).map_err(unfinished);
+    }
+    let target = profiles.join(DEFAULT_PROFILE);
+    fs::create_dir_all(&target).map_err(|e| BridgeError::io("Cannot create default profile", e))?;
+    let entries = fs::read_dir(&gathered).map_err(unfinished)?;
+    for entry in entries.flatten() {
+        let dest = target.join(entry.file_name());
+        let replaced = if dest.is_dir() {
+            fs::remove_dir_all(&dest)
+        } else if dest.exists() {
+            fs::remove_file(&dest)
+        } else {
+            Ok(())
+        };
+        replaced
+            .and_then(|_| move_entry(&entry.path(), &dest))
+            .map_err(unfinished)?;
+    }
+    // Both are empty now; anything left means a move went missing
+    fs::remove_dir(&gathered)
+        .and_then(|_| fs::remove_dir(&staging))
+        .map_err(unfinished)
+}
+
+// ── Validation ───────────────────────────────────────────────────
+
+/// Profile names become directory names, so only allow a conservative set.
+fn validate_name(name: &str) -> Result<(), BridgeError> {
+    if name.is_empty() || name.len() > MAX_NAME_LEN {
+        return Err(BridgeError::InvalidArgument(format!(
+            "Profile name must be 1-{} characters long",
+            MAX_NAME_LEN
+        )));
+    }
+    if !name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(BridgeError::InvalidArgument(
+            "Profile name may only contain letters, digits, '-' and '_'".to_string(),
+        ));
+    }
+    if RESERVED_NAMES.contains(&name.to_ascii_lowercase().as_str()) {
+        return Err(BridgeError::InvalidArgument(format!(
+            "'{}' is a reserved name",
+            name
+        )));
+    }
+    Ok(())
+}
+
+/// Find an existing profile whose name matches case-insensitively (the
+/// filesystem is case-insensitive on Windows).
+fn find_profile(name: &str) -> Result<Option<String>, BridgeError> {
+    Ok(profile_names()?
+        .into_iter()
+        .find(|n| n.eq_ignore_ascii_case(name)))
+}
+
+fn profile_names() -> Result<Vec<String>, BridgeError> {
+    active_profile()?;
+    let root = profiles_root()?;
+    let mut names: Vec<String> = fs::read_dir(&root)
+        .map_err(|e| BridgeError::io("Cannot list profiles", e))?
+        .flatten()
+        .filter(|e| e.path().is_dir())
+        .filter_map(|e| e.file_name().into_string().ok())
+        .filter(|n| validate_name(n).is_ok())
+        .collect();
+    names.sort();
+    Ok(names)
+}
+
+fn emit_lifecycle(app: &tauri::AppHandle, kind: &str, extra: serde_json::Value) {
+    let mut payload = serde_json::json!({ "type": kind });
+    if let (Some(obj), serde_json::Value::Object(more)) = (payload.as_object_mut(), extra) {
+        obj.extend(more);
+    }
+    super::emit_p2p_event(app, payload);
+}
+
+/// Stop the sidecar, run `repoint` to change where profile data lives, and
+/// start the sidecar again. Shared by profile switches and ephemeral sessions.
+async fn relaunch_sidecar<F>(
+    app: tauri::AppHandle,
+    from: String,
+    to: String,
+    incognito: bool,
+    repoint: F,
+) -> Result<(), BridgeError>
+where
+    F: FnOnce() -> Result<(), BridgeError> + Send + 'static,
+{
+    if SWITCHING.swap(true, Ordering::SeqCst) {
+        return Err(BridgeError::NotReady(
+            "A profile switch is already in progress".to_string(),
+        ));
+    }
+
+    let handle = app.clone();
+    let target = to.clone();
+    let result = tauri::async_runtime::spawn_blocking(move || {
+        emit_lifecycle(
+            &handle,
+            "profile-switching",
+            serde_json::json!({ "from": from, "to": target }),
+        );
+        let sidecar = super::sidecar::manager(&handle);
+        // Held until the new profile's sidecar is attached
+        let start = sidecar.lock_start();
+        sidecar.stop_gracefully(SHUTDOWN_GRACE);
+        super::sidecar_log::close();
+        super::history::suspend();
+        emit_lifecycle(&handle, "profile-sidecar-stopped", serde_json::json!({}));
+        let result = repoint();
+        super::history::resume();
+        result?;
+        super::start_sidecar_holding(handle.clone(), incognito, &start)
+    })
+    .await
+    .map_err(|e| BridgeError::from(e.to_string()))
+    .and_then(|r| r);
+    SWITCHING.store(false, Ordering::SeqCst);
+    // Whichever profile ended up active, its settings now apply
+    super::settings::notify_changed(&app, None);
+
+    match result {
+        Ok(()) => {
+            emit_lifecycle(
+                &app,
+                "profile-switched",
+                serde_json::json!({ "profile": to }),
+            );
+            Ok(())
+        }
+        Err(e) => {
+            emit_lifecycle(
+                &app,
+                "profile-switch-failed",
+                serde_json::json!({ "profile": to, "message": e.to_string(), "code": e.code() }),
+            );
+            Err(e)
+        }
+    }
+}
+
+/// Whether a profile switch or guest session change is restarting the sidecar.
+pub(crate) fn switch_in_progress() -> bool {
+    SWITCHING.load(Ordering::SeqCst)
+}
+
+// ── Ephemeral sessions ───────────────────────────────────────────
+
+pub(crate) fn is_ephemeral() -> bool {
+    EPHEMERAL.load(Ordering::SeqCst)
+}
+
+fn ephemeral_dir() -> Option<PathBuf> {
+    EPHEMERAL_DIR.lock().ok().and_then(|g| g.clone())
+}
+
+/// Overwrite every file with zeros before removing the tree, so identity
+/// material and logs don't linger in free space on spinning disks.
+fn secure_delete(dir: &Path) -> io::Result<()> {
+    fn scrub(path: &Path) -> io::Result<()> {
+        for entry in fs::read_dir(path)?.flatten() {
+            let p = entry.path();
+            let meta = entry.metadata()?;
+            if meta.is_dir() {
+                scrub(&p)?;
+            } else {
+                let mut f = fs::OpenOptions::new().write(true).open(&p)?;
+                let zeros = vec![0u8; 64 * 1024];
+                let mut remaining = meta.len();
+                while remaining > 0 {
+                    let n = remaining.min(zeros.len() as u64) as usize;
+                    f.write_all(&zeros[..n])?;
+                    remaining -= n as u64;
+                }
+                f.sync_all()?;
+            }
+        }
+        Ok(())
+    }
+    if !dir.exists() {
+        return Ok(());
+    }
+    let scrubbed = scrub(dir);
+    fs::remove_dir_all(dir)?;
+    scrubbed
+}
+
+/// Delete the ephemeral directory, if any. Called on session end and app exit.
+pub(crate) fn wipe_ephemeral() {
+    let dir = EPHEMERAL_DIR.lock().ok().and_then(|mut g| g.take());
+    EPHEMERAL.store(false, Ordering::SeqCst);
+    if let Some(dir) = dir {
+        super::sidecar_log::close();
+        if let Err(e) = secure_delete(&dir) {
+            eprintln!("Failed to wipe ephemeral data at {}: {}", dir.display(), e);
+        }
+    }
+}
+
+// ── Tauri commands ───────────────────────────────────────────────
+
+/// List all profiles, marking the active one.
+#[tauri::command]
+pub fn list_profiles() -> Result<Vec<ProfileInfo>, BridgeError> {
+    let active = active_profile()?;
+    Ok(profile_names()?
+        .into_iter()
+        .map(|name| ProfileInfo {
+            active: name == active,
+            name,
+        })
+        .collect())
+}
+
+/// Create an empty profile. The sidecar generates its identity on first start.
+#[tauri::command]
+pub fn create_profile(name: String) -> Result<ProfileInfo, BridgeError> {
+    validate_name(&name)?;
+    if let Some(existing) = find_profile(&name)? {
+        return Err(BridgeError::InvalidArgument(format!(
+            "Profile '{}' already exists",
+            existing
+        )));
+    }
+    fs::create_dir_all(profile_dir(&name)?)
+        .map_err(|e| BridgeError::io("Cannot create profile", e))?;
+    Ok(ProfileInfo {
+        name,
+        active: false,
+    })
+}
+
+/// Delete a profile and all of its data, once the user approves. The active
+/// profile cannot be deleted.
+#[tauri::command]
+pub async fn delete_profile(app: tauri::AppHandle, name: String) -> Result<(), BridgeError> {
+    validate_name(&name)?;
+    let name = find_profile(&name)?
+        .ok_or_else(|| BridgeError::NotFound(format!("Profile '{}' does not exist", name)))?;
+    if name == active_profile()? {
+        return Err(BridgeError::InvalidArgument(
+            "Cannot delete the active profile — switch to another profile first".to_string(),
+        ));
+    }
+    super::approvals::require_approval(
+        &app,
+        "delete-profile",
+        None,
+        serde_json::json!({ "profile": name }),
+        false,
+    )
+    .await?;
+    fs::remove_dir_all(profile_dir(&name)?).map_err(|e| BridgeError::io("Cannot delete profile", e))
+}
+
+/// Switch to another profile: stop the sidecar, repoint the data directory,
+/// and start the sidecar again under the new identity.
+#[tauri::command]
+pub async fn switch_profile(app: tauri::AppHandle, name: String) -> Result<(), BridgeError> {
+    if is_ephemeral() {
+        return Err(BridgeError::NotReady(EPHEMERAL_UNAVAILABLE.to_string()));
+    }
+    validate_name(&name)?;
+    let name = find_profile(&name)?
+        .ok_or_else(|| BridgeError::NotFound(format!("Profile '{}' does not exist", name)))?;
+    let previous = active_profile()?;
+    if name == previous {
+        return Ok(());
+    }
+    let target = name.clone();
+    let incognito = super::sidecar::manager(&app).incognito();
+    relaunch_sidecar(app, previous, name, incognito, move || {
+        set_active_profile(&target)
+    })
+    .await
+}
+
+/// Start a guest session: a throwaway identity and a temp data directory
+/// that is wiped when the session ends or the app exits.
+#[tauri::command]
+pub async fn start_ephemeral_session(app: tauri::AppHandle) -> Result<(), BridgeError> {
+    if is_ephemeral() {
+        return Err(BridgeError::NotReady(
+            "An ephemeral session is already active".to_string(),
+        ));
+    }
+    let previous = active_profile()?;
+    RESUME_INCOGNITO.store(super::sidecar::manager(&app).incognito(), Ordering::SeqCst);
+    let dir = std::env::temp_dir().join(format!(
+        "concord-ephemeral-{}-{}",
+        std::process::id(),
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or(0)
+    ));
+    relaunch_sidecar(app, previous, "ephemeral".to_string(), true, move || {
+        fs::create_dir_all(&dir).map_err(|e| BridgeError::io("Cannot create ephemeral dir", e))?;
+        *EPHEMERAL_DIR.lock().unwrap_or_else(|e| e.into_inner()) = Some(dir);
+        EPHEMERAL.store(true, Ordering::SeqCst);
+        Ok(())
+    })
+    .await
+}
+
+/// End the guest session, wipe its data, and return to the active profile,
+/// once the user approves.
+#[tauri::command]
+pub async fn end_ephemeral_session(app: tauri::AppHandle) -> Result<(), BridgeError> {
+    if !is_ephemeral() {
+        return Err(BridgeError::NotReady(
+            "No ephemeral session is active".to_string(),
+        ));
+    }
+    super::approvals::require_approval(
+        &app,
+        "end-ephemeral-session",
+        None,
+        serde_json::Value::Null,
+        false,
+    )
+    .await?;
+    let profile = active_profile()?;
+    let incognito = RESUME_INCOGNITO.load(Ordering::SeqCst);
+    relaunch_sidecar(app, "ephemeral".to_string(), profile, incognito, || {
+        wipe_ephemeral();
+        Ok(())
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh data root with a legacy layout: two files and a directory.
+    fn legacy_root(name: &str) -> PathBuf {
+        let root =
+            std::env::temp_dir().join(format!("concord-migrate-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("identity-backups")).unwrap();
+        fs::write(root.join("node-identity.json"), "legacy").unwrap();
+        fs::write(root.join("settings.json"), "{}").unwrap();
+        fs::write(root.join("identity-backups").join("old.json"), "old").unwrap();
+        root
+    }
+
+    fn names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir)
+            .unwrap()
+            .flatten()
+            .map(|e| e.file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn a_failed_migration_resumes_on_the_next_run() {
+        let root = legacy_root("resume");
+        let mut moves = 0;
+        let failed = migrate_root(&root, |from, to| {
+            moves += 1;
+            if moves == 2 {
+                return Err(io::Error::other("disk went away"));
+            }
+            fs::rename(from, to)
+        });
+        assert!(failed.is_err());
+        assert!(!root.join("profiles").exists());
+
+        migrate_root(&root, |from, to| fs::rename(from, to)).unwrap();
+        assert_eq!(names(&root), ["profiles"]);
+        let default = root.join("profiles").join(DEFAULT_PROFILE);
+        assert_eq!(
+            names(&default),
+            ["identity-backups", "node-identity.json", "settings.json"]
+        );
+        assert_eq!(
+            fs::read_to_string(default.join("identity-backups").join("old.json")).unwrap(),
+            "old"
+        );
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn a_finished_migration_is_not_repeated() {
+        let root = legacy_root("done");
+        migrate_root(&root, |from, to| fs::rename(from, to)).unwrap();
+        fs::write(root.join("exit.log"), "later").unwrap();
+        migrate_root(&root, |_, _| Err(io::Error::other("must not move"))).unwrap();
+        assert!(root.join("exit.log").exists());
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn legacy_entries_win_over_a_postponed_run() {
+        let root = legacy_root("postponed");
+        let failed = migrate_root(&root, |_, _| Err(io::Error::other("read-only")));
+        assert!(failed.is_err());
+        // The postponed session started a fresh profile in the meantime
+        let default = root.join("profiles").join(DEFAULT_PROFILE);
+        fs::create_dir_all(&default).unwrap();
+        fs::write(default.join("node-identity.json"), "fresh").unwrap();
+        fs::write(default.join("sidecar.log"), "log").unwrap();
+
+        migrate_root(&root, |from, to| fs::rename(from, to)).unwrap();
+        assert_eq!(names(&root), ["profiles"]);
+        assert_eq!(
+            fs::read_to_string(default.join("node-identity.json")).unwrap(),
+            "legacy"
+        );
+        assert!(default.join("sidecar.log").exists());
+        assert!(default.join("identity-backups").join("old.json").exists());
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,326 @@
+// Sidecar protocol — the JSON lines exchanged with scripts/p2p-sidecar.js,
+// as types. Commands are tagged by `cmd`, events by `type`; field names are
+// camelCase on the wire. Every command also carries the `requestId` the
+// bridge assigns when writing it (see `encode`), and replies echo it.
+//
+// Incoming lines are parsed into `SidecarEvent` to check their shape, but
+// the stdout pipeline still works on (and forwards) the raw JSON, so event
+// types the bridge doesn't know reach the frontend unchanged as `Other`.
+// A known type with the wrong shape is reported and forwarded as a log
+// line, the same as text that isn't JSON.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// A command for the sidecar. Variant names are the `cmd` values.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "cmd", rename_all = "snake_case")]
+pub(crate) enum SidecarCommand {
+    #[serde(rename_all = "camelCase")]
+    Send {
+        channel_id: String,
+        data: String,
+        /// Per-conversation sequence number, from sequence.rs.
+        seq: u64,
+        /// Bridge message id, for deduplication on the receiving end.
+        id: String,
+        /// Encodings this end can decode.
+        caps: Vec<String>,
+        /// Send only to this peer (a DM) instead of broadcasting.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        target_peer_id: Option<String>,
+        /// How `data` is encoded, when it isn't plain text.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        encoding: Option<String>,
+    },
+    /// Dial an invite code or multiaddr; answered by `dial_result`.
+    Dial { address: String },
+    /// Write a fresh keypair, optionally telling contacts; answered by
+    /// `identity_regenerated`.
+    RegenerateIdentity { notify: bool },
+    /// Relay round-trip for the connectivity check; answered by
+    /// `diagnose_result`.
+    Diagnose,
+    /// Stop libp2p and exit; not answered.
+    Shutdown,
+    /// Announce a file transfer (file_transfer.rs); chunks follow.
+    #[serde(rename_all = "camelCase")]
+    FileOffer {
+        transfer_id: String,
+        channel_id: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        target_peer_id: Option<String>,
+        name: String,
+        size: u64,
+        chunks: u64,
+    },
+    /// One piece of a transfer; `data` is base64.
+    #[serde(rename_all = "camelCase")]
+    FileChunk {
+        transfer_id: String,
+        channel_id: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        target_peer_id: Option<String>,
+        index: u64,
+        data: String,
+    },
+    /// All chunks were sent; `checksum` covers the whole content.
+    #[serde(rename_all = "camelCase")]
+    FileComplete {
+        transfer_id: String,
+        channel_id: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        target_peer_id: Option<String>,
+        checksum: String,
+    },
+    /// The transfer was cancelled or failed; drop what arrived.
+    #[serde(rename_all = "camelCase")]
+    FileAbort {
+        transfer_id: String,
+        channel_id: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        target_peer_id: Option<String>,
+    },
+}
+
+/// Serialise `cmd` as one protocol line carrying `request_id`.
+pub(crate) fn encode(cmd: &SidecarCommand, request_id: &str) -> Result<String, serde_json::Error> {
+    let mut value = serde_json::to_value(cmd)?;
+    if let Some(fields) = value.as_object_mut() {
+        fields.insert("requestId".to_string(), Value::from(request_id));
+    }
+    serde_json::to_string(&value)
+}
+
+/// An event from the sidecar. Variant names are the `type` values. Fields
+/// the bridge doesn't read yet are declared all the same, so a malformed
+/// event is caught when it is parsed.
+#[allow(dead_code)]
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type")]
+pub(crate) enum SidecarEvent {
+    #[serde(rename = "ready", rename_all = "camelCase")]
+    Ready {
+        peer_id: String,
+        address: Option<String>,
+        lan_address: Option<String>,
+        port: Option<u16>,
+        #[serde(default)]
+        is_ephemeral: bool,
+        invite_code: Option<String>,
+    },
+    #[serde(rename = "invite_code")]
+    InviteCode { code: String },
+    #[serde(rename = "peer:connect", rename_all = "camelCase")]
+    PeerConnected { peer_id: String, peers: Vec<String> },
+    #[serde(rename = "peer:disconnect", rename_all = "camelCase")]
+    PeerDisconnected { peer_id: String, peers: Vec<String> },
+    /// A chat message from a peer. `data` is whatever the peer sent.
+    #[serde(rename = "message", rename_all = "camelCase")]
+    Message {
+        id: Option<String>,
+        channel_id: String,
+        data: Value,
+        from: String,
+        seq: Option<u64>,
+        #[serde(default)]
+        direct: bool,
+        encoding: Option<String>,
+        caps: Option<Vec<String>>,
+    },
+    #[serde(rename = "identity_notice")]
+    IdentityNotice { from: String },
+    #[serde(rename = "net_stats", rename_all = "camelCase")]
+    NetStats {
+        listen_port: Option<u16>,
+        listen_addrs: Vec<String>,
+        peers: Vec<String>,
+    },
+    #[serde(rename = "dial_result", rename_all = "camelCase")]
+    DialResult {
+        ok: bool,
+        address: String,
+        error: Option<String>,
+        /// The dialled peer, for invite codes.
+        peer_id: Option<String>,
+        /// Connected peers after the dial.
+        peers: Option<Vec<String>>,
+        request_id: Option<String>,
+    },
+    #[serde(rename = "status", rename_all = "camelCase")]
+    Status {
+        peer_id: String,
+        peers: Vec<String>,
+        request_id: Option<String>,
+    },
+    #[serde(rename = "identity_regenerated", rename_all = "camelCase")]
+    IdentityRegenerated {
+        ok: bool,
+        old_peer_id: Option<String>,
+        new_peer_id: Option<String>,
+        /// Contacts the handover notice was sent to.
+        notified: Option<u64>,
+        error: Option<String>,
+        request_id: Option<String>,
+    },
+    #[serde(rename = "diagnose_result", rename_all = "camelCase")]
+    DiagnoseResult {
+        relay_connected: bool,
+        relay_reservation: bool,
+        invite_code_resolves: Option<bool>,
+        request_id: Option<String>,
+    },
+    #[serde(rename = "error", rename_all = "camelCase")]
+    Error {
+        message: String,
+        request_id: Option<String>,
+    },
+    #[serde(rename = "log")]
+    Log { message: String },
+    /// A type this bridge doesn't know; forwarded as is.
+    #[serde(other)]
+    Other,
+}
+
+impl SidecarEvent {
+    pub(crate) fn parse(event: &Value) -> Result<Self, serde_json::Error> {
+        Self::deserialize(event)
+    }
+
+    /// The `requestId` of a reply to a bridge command.
+    pub(crate) fn request_id(&self) -> Option<&str> {
+        match self {
+            Self::DialResult { request_id, .. }
+            | Self::Status { request_id, .. }
+            | Self::IdentityRegenerated { request_id, .. }
+            | Self::DiagnoseResult { request_id, .. }
+            | Self::Error { request_id, .. } => request_id.as_deref(),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn encoded(cmd: &SidecarCommand) -> Value {
+        serde_json::from_str(&encode(cmd, "r1").unwrap()).unwrap()
+    }
+
+    #[test]
+    fn commands_match_the_sidecar_wire_format() {
+        let send = SidecarCommand::Send {
+            channel_id: "general".to_string(),
+            data: "hi".to_string(),
+            seq: 4,
+            id: "m1".to_string(),
+            caps: vec!["deflate".to_string()],
+            target_peer_id: None,
+            encoding: None,
+        };
+        assert_eq!(
+            encoded(&send),
+            json!({"cmd": "send", "channelId": "general", "data": "hi", "seq": 4,
+                   "id": "m1", "caps": ["deflate"], "requestId": "r1"})
+        );
+
+        let dm = SidecarCommand::Send {
+            channel_id: "dm:p".to_string(),
+            data: "eJw=".to_string(),
+            seq: 1,
+            id: "m2".to_string(),
+            caps: vec![],
+            target_peer_id: Some("p".to_string()),
+            encoding: Some("deflate".to_string()),
+        };
+        let dm = encoded(&dm);
+        assert_eq!(dm["targetPeerId"], "p");
+        assert_eq!(dm["encoding"], "deflate");
+
+        assert_eq!(
+            encoded(&SidecarCommand::RegenerateIdentity { notify: true }),
+            json!({"cmd": "regenerate_identity", "notify": true, "requestId": "r1"})
+        );
+        assert_eq!(
+            encoded(&SidecarCommand::Shutdown),
+            json!({"cmd": "shutdown", "requestId": "r1"})
+        );
+        assert_eq!(
+            encoded(&SidecarCommand::FileAbort {
+                transfer_id: "t".to_string(),
+                channel_id: "general".to_string(),
+                target_peer_id: None,
+            }),
+            json!({"cmd": "file_abort", "transferId": "t", "channelId": "general",
+                   "requestId": "r1"})
+        );
+    }
+
+    #[test]
+    fn encoded_commands_are_single_lines() {
+        let send = SidecarCommand::Send {
+            channel_id: "general".to_string(),
+            data: "line one\nline two".to_string(),
+            seq: 1,
+            id: "m".to_string(),
+            caps: vec![],
+            target_peer_id: None,
+            encoding: None,
+        };
+        assert!(!encode(&send, "r").unwrap().contains('\n'));
+    }
+
+    #[test]
+    fn events_parse_into_their_variants() {
+        let ready = json!({"type": "ready", "peerId": "me", "port": 4001, "isEphemeral": true});
+        match SidecarEvent::parse(&ready).unwrap() {
+            SidecarEvent::Ready {
+                peer_id,
+                port,
+                is_ephemeral,
+                address,
+                ..
+            } => {
+                assert_eq!(peer_id, "me");
+                assert_eq!(port, Some(4001));
+                assert!(is_ephemeral);
+                assert!(address.is_none());
+            }
+            other => panic!("parsed as {:?}", other),
+        }
+
+        let message = json!({"type": "message", "channelId": "general", "from": "p",
+                             "data": "{\"content\":\"hi\"}"});
+        assert!(matches!(
+            SidecarEvent::parse(&message).unwrap(),
+            SidecarEvent::Message {
+                direct: false,
+                seq: None,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn replies_carry_their_request_id() {
+        let dial = json!({"type": "dial_result", "ok": false, "address": "/ip4/1.2.3.4",
+                          "error": "refused", "requestId": "r7"});
+        assert_eq!(SidecarEvent::parse(&dial).unwrap().request_id(), Some("r7"));
+        let log = json!({"type": "log", "message": "hello"});
+        assert_eq!(SidecarEvent::parse(&log).unwrap().request_id(), None);
+    }
+
+    #[test]
+    fn unknown_types_pass_and_malformed_known_ones_fail() {
+        let future = json!({"type": "something-new", "anything": [1, 2]});
+        assert!(matches!(
+            SidecarEvent::parse(&future).unwrap(),
+            SidecarEvent::Other
+        ));
+        let broken = json!({"type": "peer:connect", "peerId": 5});
+        assert!(SidecarEvent::parse(&broken).is_err());
+        assert!(SidecarEvent::parse(&json!({"no": "type"})).is_err());
+    }
+}
@@ -0,0 +1,76 @@
+// Safe mode — launch without the sidecar and background subsystems, so a
+// crash in the startup pipeline can't lock the user out of settings and
+// diagnostics. Engaged by `--safe-mode` or after repeated startup crashes;
+// left only through `leave_safe_mode`.
+
+use std::sync::Mutex;
+
+use super::error::BridgeError;
+
+const SAFE_MODE_FLAG: &str = "--safe-mode";
+/// Consecutive startup crashes that engage safe mode automatically.
+const CRASH_THRESHOLD: u32 = 2;
+
+static REASON: Mutex<Option<String>> = Mutex::new(None);
+
+/// Decide whether this launch runs in safe mode. Must be called after
+/// `shutdown::begin_session` has counted startup crashes.
+pub(crate) fn detect() -> bool {
+    let crashes = super::shutdown::startup_crashes();
+    let reason = if std::env::args().any(|a| a == SAFE_MODE_FLAG) {
+        Some(format!("Started with {}", SAFE_MODE_FLAG))
+    } else if crashes >= CRASH_THRESHOLD {
+        Some(format!(
+            "The last {} launches crashed during startup",
+            crashes
+        ))
+    } else {
+        None
+    };
+    let engaged = reason.is_some();
+    *REASON.lock().unwrap_or_else(|e| e.into_inner()) = reason;
+    engaged
+}
+
+pub(crate) fn active() -> bool {
+    REASON.lock().map(|g| g.is_some()).unwrap_or(false)
+}
+
+pub(crate) fn reason() -> Option<String> {
+    REASON.lock().ok().and_then(|g| g.clone())
+}
+
+/// Tell the frontend why safe mode engaged so it can show the recovery screen.
+pub(crate) fn announce(app: &tauri::AppHandle) {
+    let Some(reason) = reason() else {
+        return;
+    };
+    super::emit_p2p_event(
+        app,
+        serde_json::json!({
+            "type": "safe-mode",
+            "reason": reason,
+            "startupCrashes": super::shutdown::startup_crashes(),
+        }),
+    );
+}
+
+// ── Tauri commands ───────────────────────────────────────────────
+
+/// Leave safe mode: clear the crash counter and start the sidecar and the
+/// background subsystems that were skipped at launch.
+#[tauri::command]
+pub fn leave_safe_mode(app: tauri::AppHandle) -> Result<(), BridgeError> {
+    if REASON
+        .lock()
+        .map_err(|e| format!("Mutex poisoned: {}", e))?
+        .take()
+        .is_none()
+    {
+        return Err(BridgeError::NotReady("Not in safe mode".to_string()));
+    }
+    super::shutdown::reset_startup_crashes();
+    super::emit_p2p_event(&app, serde_json::json!({"type": "safe-mode-exited"}));
+    super::start_subsystems(app, std::time::Duration::ZERO);
+    Ok(())
+}
@@ -0,0 +1,385 @@
+// Send policies — rules a channel asks honest clients to enforce before
+// sending (no messages over N characters, no @everyone-style mentions, no
+// files over N bytes). Policies are kept per profile in send-policies.json
+// as the raw rule list, so rules this version doesn't know survive a round
+// trip and are ignored with a warning instead of failing the whole policy.
+// Violations reject `p2p_send` and `p2p_send_file` with `policy-violation`
+// naming the rule.
+// The personal guard (`sending.confirmBetween`) is softer: sending inside
+// that window asks for approval instead of refusing.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::error::BridgeError;
+
+pub(crate) const POLICIES_FILE: &str = "send-policies.json";
+/// Mentions that notify the whole channel.
+const MASS_MENTIONS: &[&str] = &["@everyone", "@here", "@channel"];
+
+/// Policies of the active profile, keyed by channel.
+static POLICIES: Mutex<Option<(PathBuf, HashMap<String, ChannelPolicy>)>> = Mutex::new(None);
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ChannelPolicy {
+    /// Rules as `{ "type": ..., ...params }`, unknown types included.
+    pub rules: Vec<Value>,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+enum Rule {
+    #[serde(rename_all = "camelCase")]
+    MaxLength {
+        max_chars: usize,
+    },
+    NoMassMentions,
+    #[serde(rename_all = "camelCase")]
+    MaxFileSize {
+        max_bytes: u64,
+    },
+}
+
+/// What is about to be sent; each rule applies to one kind.
+enum Sending<'a> {
+    /// A message's text.
+    Message(&'a str),
+    /// A file of this many bytes.
+    File(u64),
+}
+
+impl Rule {
+    const KINDS: &'static [&'static str] = &["max-length", "no-mass-mentions", "max-file-size"];
+
+    fn kind(&self) -> &'static str {
+        match self {
+            Self::MaxLength { .. } => "max-length",
+            Self::NoMassMentions => "no-mass-mentions",
+            Self::MaxFileSize { .. } => "max-file-size",
+        }
+    }
+
+    /// Why sending this breaks the rule, if it does.
+    fn violation(&self, sending: &Sending) -> Option<String> {
+        match (self, sending) {
+            (Self::MaxLength { max_chars }, Sending::Message(content)) => {
+                let len = content.chars().count();
+                (len > *max_chars).then(|| {
+                    format!(
+                        "Message is {} characters; this channel allows {}",
+                        len, max_chars
+                    )
+                })
+            }
+            (Self::NoMassMentions, Sending::Message(content)) => MASS_MENTIONS
+                .iter()
+                .find(|m| content.contains(*m))
+                .map(|m| format!("This channel doesn't allow {} mentions", m)),
+            (Self::MaxFileSize { max_bytes }, Sending::File(size)) => (size > max_bytes)
+                .then(|| format!("File is {} bytes; this channel allows {}", size, max_bytes)),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PolicyApplied {
+    /// Rule types this version doesn't know; stored but not enforced.
+    pub ignored: Vec<String>,
+}
+
+fn rule_type(rule: &Value) -> &str {
+    rule["type"].as_str().unwrap_or("<untyped>")
+}
+
+/// The rules this version can enforce; the others are skipped with a
+/// warning, so a policy written by a newer client still partly applies.
+fn parse_rules(channel_id: &str, policy: &ChannelPolicy) -> Vec<Rule> {
+    policy
+        .rules
+        .iter()
+        .filter_map(|raw| match serde_json::from_value::<Rule>(raw.clone()) {
+            Ok(rule) => Some(rule),
+            Err(e) => {
+                eprintln!(
+                    "Ignoring send rule {} for {}: {}",
+                    rule_type(raw),
+                    channel_id,
+                    e
+                );
+                None
+            }
+        })
+        .collect()
+}
+
+fn load(path: &Path) -> HashMap<String, ChannelPolicy> {
+    match super::storage::read_to_string(path) {
+        Ok(s) => serde_json::from_str(&s).unwrap_or_else(|e| {
+            eprintln!("Ignoring malformed {}: {}", path.display(), e);
+            HashMap::new()
+        }),
+        Err(_) => HashMap::new(),
+    }
+}
+
+fn save(path: &Path, policies: &HashMap<String, ChannelPolicy>) -> Result<(), BridgeError> {
+    let json = serde_json::to_string_pretty(policies).map_err(|e| e.to_string())?;
+    super::storage::write(path, json).map_err(|e| BridgeError::io("Cannot save send policies", e))
+}
+
+/// Drop the cached policies so the next use reads them from disk (after a
+/// restore replaced the file at the same path).
+pub(crate) fn invalidate() {
+    *POLICIES.lock().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+/// Run `f` on the active profile's policies, loading them after a profile
+/// switch.
+fn with_policies<T>(
+    f: impl FnOnce(&Path, &mut HashMap<String, ChannelPolicy>) -> T,
+) -> Result<T, BridgeError> {
+    let path = super::app_data_dir()?.join(POLICIES_FILE);
+    let mut guard = POLICIES.lock().unwrap_or_else(|e| e.into_inner());
+    if !matches!(*guard, Some((ref cached, _)) if *cached == path) {
+        *guard = Some((path.clone(), load(&path)));
+    }
+    let Some((_, ref mut policies)) = *guard else {
+        return Err("Send policy state unavailable".into());
+    };
+    Ok(f(&path, policies))
+}
+
+/// The text the rules apply to: `content` of a JSON message, else the raw data.
+fn content_of(data: &str) -> String {
+    serde_json::from_str::<Value>(data)
+        .ok()
+        .and_then(|m| m["content"].as_str().map(str::to_string))
+        .unwrap_or_else(|| data.to_string())
+}
+
+/// Called by `p2p_send` before anything reaches the sidecar: refuse what
+/// the channel's policy forbids, then ask for approval inside the personal
+/// guard window.
+pub(crate) async fn check(
+    app: &tauri::AppHandle,
+    channel_id: &str,
+    data: &str,
+) -> Result<(), BridgeError> {
+    enforce(app, channel_id, &Sending::Message(&content_of(data))).await
+}
+
+/// `check` for `p2p_send_file`, before the file is offered.
+pub(crate) async fn check_file(
+    app: &tauri::AppHandle,
+    channel_id: &str,
+    size: u64,
+) -> Result<(), BridgeError> {
+    enforce(app, channel_id, &Sending::File(size)).await
+}
+
+async fn enforce(
+    app: &tauri::AppHandle,
+    channel_id: &str,
+    sending: &Sending<'_>,
+) -> Result<(), BridgeError> {
+    let rules = with_policies(|_, policies| {
+        policies
+            .get(channel_id)
+            .map(|p| parse_rules(channel_id, p))
+            .unwrap_or_default()
+    })?;
+    for rule in &rules {
+        if let Some(message) = rule.violation(sending) {
+            return Err(BridgeError::PolicyViolation {
+                rule: rule.kind().to_string(),
+                message,
+            });
+        }
+    }
+
+    let Some(window) = super::settings::current().sending.confirm_between else {
+        return Ok(());
+    };
+    if super::attention::local_minute_of_day().is_some_and(|m| window.contains(m)) {
+        super::approvals::require_approval(
+            app,
+            "send-in-guarded-hours",
+            None,
+            serde_json::json!({"channelId": channel_id, "start": window.start, "end": window.end}),
+            false,
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+/// Move policies recorded under the old peer id to the new one (identity
+/// regeneration).
+pub(crate) fn rekey_peer(old: &str, new: &str) -> Result<(), BridgeError> {
+    with_policies(|path, policies| {
+        if super::identity::rekey_map(policies, old, new) {
+            save(path, policies)?;
+        }
+        Ok(())
+    })?
+}
+
+// ── Tauri commands ───────────────────────────────────────────────
+
+/// Replace the send policy of `channel_id`; an empty rule list removes it.
+/// Known rules with bad parameters are rejected; unknown rule types are
+/// stored and returned in `ignored`.
+#[tauri::command]
+pub fn set_channel_policy(
+    channel_id: String,
+    policy: ChannelPolicy,
+) -> Result<PolicyApplied, BridgeError> {
+    let mut ignored = Vec::new();
+    for raw in &policy.rules {
+        let kind = rule_type(raw);
+        if !Rule::KINDS.contains(&kind) {
+            ignored.push(kind.to_string());
+        } else if let Err(e) = serde_json::from_value::<Rule>(raw.clone()) {
+            return Err(BridgeError::InvalidArgument(format!(
+                "Invalid {} rule: {}",
+                kind, e
+            )));
+        }
+    }
+    with_policies(|path, policies| {
+        if policy.rules.is_empty() {
+            policies.remove(&channel_id);
+        } else {
+            policies.insert(channel_id, policy);
+        }
+        save(path, policies)
+    })??;
+    Ok(PolicyApplied { ignored })
+}
+
+/// The stored policy of `channel_id`, unknown rules included.
+#[tauri::command]
+pub fn get_channel_policy(channel_id: String) -> Result<ChannelPolicy, BridgeError> {
+    with_policies(|_, policies| policies.get(&channel_id).cloned().unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn policy(rules: Value) -> ChannelPolicy {
+        serde_json::from_value(json!({ "rules": rules })).unwrap()
+    }
+
+    #[test]
+    fn max_length_counts_characters() {
+        let rule = Rule::MaxLength { max_chars: 3 };
+        assert!(rule.violation(&Sending::Message("héé")).is_none());
+        assert_eq!(
+            rule.violation(&Sending::Message("abcd")).as_deref(),
+            Some("Message is 4 characters; this channel allows 3")
+        );
+    }
+
+    #[test]
+    fn mass_mentions_are_caught() {
+        let rule = Rule::NoMassMentions;
+        assert!(rule.violation(&Sending::Message("hi @alice")).is_none());
+        for mention in MASS_MENTIONS {
+            let content = format!("hey {} look", mention);
+            assert!(rule.violation(&Sending::Message(&content)).is_some());
+        }
+    }
+
+    #[test]
+    fn max_file_size_counts_bytes() {
+        let rule = Rule::MaxFileSize { max_bytes: 1024 };
+        assert!(rule.violation(&Sending::File(1024)).is_none());
+        assert_eq!(
+            rule.violation(&Sending::File(1025)).as_deref(),
+            Some("File is 1025 bytes; this channel allows 1024")
+        );
+    }
+
+    #[test]
+    fn rules_only_apply_to_their_kind_of_send() {
+        let long = "x".repeat(5000);
+        assert!(Rule::MaxFileSize { max_bytes: 1 }
+            .violation(&Sending::Message(&long))
+            .is_none());
+        assert!(Rule::MaxLength { max_chars: 1 }
+            .violation(&Sending::File(5000))
+            .is_none());
+        assert!(Rule::NoMassMentions
+            .violation(&Sending::File(5000))
+            .is_none());
+    }
+
+    #[test]
+    fn unknown_and_malformed_rules_are_skipped() {
+        let p = policy(json!([
+            {"type": "max-length", "maxChars": 10},
+            {"type": "no-links"},
+            {"type": "max-length", "maxChars": "ten"},
+            {"type": "no-mass-mentions"},
+        ]));
+        let kinds: Vec<&str> = parse_rules("general", &p).iter().map(Rule::kind).collect();
+        assert_eq!(kinds, ["max-length", "no-mass-mentions"]);
+        assert_eq!(rule_type(&json!({})), "<untyped>");
+    }
+
+    #[test]
+    fn every_kind_is_listed() {
+        let parsed = parse_rules(
+            "general",
+            &policy(json!([
+                {"type": "max-length", "maxChars": 1},
+                {"type": "no-mass-mentions"},
+                {"type": "max-file-size", "maxBytes": 1},
+            ])),
+        );
+        let kinds: Vec<&str> = parsed.iter().map(Rule::kind).collect();
+        assert_eq!(kinds, Rule::KINDS);
+    }
+
+    #[test]
+    fn rules_apply_to_message_content() {
+        assert_eq!(content_of(r#"{"content":"@here","timestamp":1}"#), "@here");
+        assert_eq!(content_of("plain text"), "plain text");
+        assert_eq!(content_of(r#"{"other":1}"#), r#"{"other":1}"#);
+    }
+
+    #[test]
+    fn unknown_rules_survive_a_save_and_load() {
+        let path =
+            std::env::temp_dir().join(format!("concord-policies-{}.json", std::process::id()));
+        let policies = HashMap::from([(
+            "general".to_string(),
+            policy(json!([{"type": "no-links", "allow": ["example.com"]}])),
+        )]);
+        save(&path, &policies).unwrap();
+        let loaded = load(&path);
+        assert_eq!(
+            loaded["general"].rules,
+            [json!({"type": "no-links", "allow": ["example.com"]})]
+        );
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn known_rules_with_bad_parameters_are_rejected() {
+        let result = set_channel_policy(
+            "general".to_string(),
+            policy(json!([{"type": "max-length"}])),
+        );
+        assert!(matches!(result, Err(BridgeError::InvalidArgument(_))));
+    }
+}
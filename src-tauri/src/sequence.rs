@@ -0,0 +1,261 @@
+// Per-conversation message sequencing.
+// Outbound: the bridge numbers every send per conversation and hands them to
+// the sidecar in order; the sidecar carries `seq` on the wire and keeps a
+// FIFO per conversation so concurrent sends can't overtake each other.
+// Counters persist in sequence.json so numbering survives restarts.
+// Inbound: gaps in a sender's numbering are reported as `possible-message-loss`,
+// and each message is stamped with sender-claimed time, bridge receive time
+// and a bridge-monotonic sequence for stable ordering.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use super::error::BridgeError;
+
+const SEQUENCE_FILE: &str = "sequence.json";
+/// Sender and receive times further apart than this are flagged as skewed.
+const CLOCK_SKEW_THRESHOLD_MS: u64 = 2 * 60 * 1000;
+
+/// Outbound counters of the active profile, keyed by conversation.
+static OUTBOUND: Mutex<Option<(PathBuf, SequenceState)>> = Mutex::new(None);
+/// Highest sequence seen per (sender, conversation). Memory only: after a
+/// restart the first message from each sender sets a new baseline.
+static INBOUND: Mutex<Option<HashMap<(String, String), u64>>> = Mutex::new(None);
+/// Receive order within this process; breaks ties between equal receive times.
+static RECEIVE_SEQ: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default, rename_all = "camelCase")]
+struct SequenceState {
+    outbound: HashMap<String, u64>,
+}
+
+/// Broadcasts in a channel and direct sends to one peer in that channel are
+/// separate streams, so each receiver sees a gap-free sequence.
+fn conversation_key(channel_id: &str, target_peer_id: Option<&str>) -> String {
+    match target_peer_id {
+        Some(peer) => format!("{}\u{1f}{}", channel_id, peer),
+        None => channel_id.to_string(),
+    }
+}
+
+fn load(path: &Path) -> SequenceState {
+    match super::storage::read_to_string(path) {
+        Ok(s) => serde_json::from_str(&s).unwrap_or_else(|e| {
+            eprintln!("Ignoring malformed {}: {}", path.display(), e);
+            SequenceState::default()
+        }),
+        Err(_) => SequenceState::default(),
+    }
+}
+
+/// Assign the next sequence number for a conversation and run `send` with it
+/// while holding the lock, so numbers reach the sidecar in order. The number
+/// is only consumed if `send` succeeds.
+pub(crate) fn send_in_order<F>(
+    channel_id: &str,
+    target_peer_id: Option<&str>,
+    send: F,
+) -> Result<(), BridgeError>
+where
+    F: FnOnce(u64) -> Result<(), BridgeError>,
+{
+    let path = super::app_data_dir()?.join(SEQUENCE_FILE);
+    let mut guard = OUTBOUND
+        .lock()
+        .map_err(|e| format!("Mutex poisoned: {}", e))?;
+    if !matches!(*guard, Some((ref cached, _)) if *cached == path) {
+        *guard = Some((path.clone(), load(&path)));
+    }
+    let Some((_, ref mut state)) = *guard else {
+        return Err("Sequence state unavailable".into());
+    };
+
+    let key = conversation_key(channel_id, target_peer_id);
+    let seq = state.outbound.get(&key).copied().unwrap_or(0) + 1;
+    send(seq)?;
+    state.outbound.insert(key, seq);
+
+    // A failed write only risks reusing a number after a crash; the send
+    // itself has already happened.
+    match serde_json::to_string(state) {
+        Ok(json) => {
+            if let Err(e) = super::storage::write(&path, json) {
+                eprintln!("Cannot save {}: {}", path.display(), e);
+            }
+        }
+        Err(e) => eprintln!("Cannot save {}: {}", path.display(), e),
+    }
+    Ok(())
+}
+
+/// Move counters recorded under the old peer id to the new one (identity
+/// regeneration), so numbering carries on instead of starting over.
+pub(crate) fn rekey_peer(old: &str, new: &str) -> Result<(), BridgeError> {
+    let path = super::app_data_dir()?.join(SEQUENCE_FILE);
+    let mut guard = OUTBOUND
+        .lock()
+        .map_err(|e| format!("Mutex poisoned: {}", e))?;
+    if !matches!(*guard, Some((ref cached, _)) if *cached == path) {
+        *guard = Some((path.clone(), load(&path)));
+    }
+    let Some((_, ref mut state)) = *guard else {
+        return Err("Sequence state unavailable".into());
+    };
+    if super::identity::rekey_map(&mut state.outbound, old, new) {
+        let json = serde_json::to_string(state).map_err(|e| e.to_string())?;
+        super::storage::write(&path, json)
+            .map_err(|e| BridgeError::io("Cannot save sequence counters", e))?;
+    }
+    drop(guard);
+
+    // Inbound DMs arrive on our own `dm:` conversation
+    if let Some(seen) = INBOUND.lock().unwrap_or_else(|e| e.into_inner()).as_mut() {
+        let moves: Vec<((String, String), String)> = seen
+            .keys()
+            .filter_map(|key| {
+                super::identity::rekeyed(&key.1, old, new).map(|to| (key.clone(), to))
+            })
+            .collect();
+        for ((sender, conversation), to) in moves {
+            if let Some(seq) = seen.remove(&(sender.clone(), conversation)) {
+                seen.insert((sender, to), seq);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Add `senderTime` (claimed by the sender, may be wrong), `receivedAt`
+/// (bridge wall clock) and `receiveSeq` to an inbound message. Ordering by
+/// `receivedAt` then `receiveSeq` is stable even when senders' clocks are off;
+/// `clockSkewMs` lets the UI flag senders whose clocks disagree with ours.
+pub(crate) fn stamp_inbound(event: &mut serde_json::Value) {
+    if event.get("type").and_then(|t| t.as_str()) != Some("message") {
+        return;
+    }
+    let received_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0);
+    let sender_time = event["data"]
+        .as_str()
+        .and_then(|d| serde_json::from_str::<serde_json::Value>(d).ok())
+        .and_then(|m| m["timestamp"].as_u64());
+    let skew = sender_time.map(|t| received_at as i64 - t as i64);
+
+    event["senderTime"] = serde_json::json!(sender_time);
+    event["receivedAt"] = serde_json::json!(received_at);
+    event["receiveSeq"] = serde_json::json!(RECEIVE_SEQ.fetch_add(1, Ordering::SeqCst));
+    event["clockSkewMs"] = serde_json::json!(skew);
+    event["clockSkewed"] =
+        serde_json::json!(skew.is_some_and(|s| s.unsigned_abs() > CLOCK_SKEW_THRESHOLD_MS));
+}
+
+/// Called by the stdout reader for every parsed sidecar event.
+pub(crate) fn observe_event(app: &tauri::AppHandle, event: &serde_json::Value) {
+    if event.get("type").and_then(|t| t.as_str()) != Some("message") {
+        return;
+    }
+    // Older peers don't number their messages
+    let (Some(from), Some(seq)) = (event["from"].as_str(), event["seq"].as_u64()) else {
+        return;
+    };
+    let channel_id = event["channelId"].as_str().unwrap_or_default();
+    let direct = event["direct"].as_bool().unwrap_or(false);
+    let key = (
+        from.to_string(),
+        conversation_key(channel_id, direct.then_some("me")),
+    );
+
+    let last = {
+        let mut guard = INBOUND.lock().unwrap_or_else(|e| e.into_inner());
+        let seen = guard.get_or_insert_with(HashMap::new);
+        let last = seen.get(&key).copied();
+        if last.map_or(true, |l| seq > l) {
+            seen.insert(key, seq);
+        }
+        last
+    };
+    let Some(last) = last else {
+        return;
+    };
+    if seq > last + 1 {
+        super::emit_p2p_event(
+            app,
+            serde_json::json!({
+                "type": "possible-message-loss",
+                "from": from,
+                "channelId": channel_id,
+                "direct": direct,
+                "expected": last + 1,
+                "received": seq,
+                "missing": seq - last - 1,
+            }),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn now_ms() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0)
+    }
+
+    #[test]
+    fn broadcasts_and_direct_sends_are_separate_conversations() {
+        assert_eq!(conversation_key("general", None), "general");
+        assert_eq!(
+            conversation_key("general", Some("peer")),
+            "general\u{1f}peer"
+        );
+    }
+
+    #[test]
+    fn inbound_messages_are_stamped_in_receive_order() {
+        let sent = now_ms();
+        let data = json!({"content": "hi", "timestamp": sent}).to_string();
+        let mut first = json!({"type": "message", "data": data});
+        let mut second = first.clone();
+        stamp_inbound(&mut first);
+        stamp_inbound(&mut second);
+
+        assert_eq!(first["senderTime"], sent);
+        assert!(first["receivedAt"].as_u64().unwrap() >= sent);
+        assert_eq!(first["clockSkewed"], false);
+        assert!(second["receiveSeq"].as_u64() > first["receiveSeq"].as_u64());
+    }
+
+    #[test]
+    fn senders_with_distant_clocks_are_flagged() {
+        let data = json!({"content": "hi", "timestamp": now_ms() + 60 * 60 * 1000}).to_string();
+        let mut event = json!({"type": "message", "data": data});
+        stamp_inbound(&mut event);
+        assert!(event["clockSkewMs"].as_i64().unwrap() < 0);
+        assert_eq!(event["clockSkewed"], true);
+
+        // Without a claimed time there is nothing to compare
+        let mut event = json!({"type": "message", "data": "not json"});
+        stamp_inbound(&mut event);
+        assert!(event["senderTime"].is_null());
+        assert_eq!(event["clockSkewed"], false);
+    }
+
+    #[test]
+    fn other_events_are_not_stamped() {
+        let mut event = json!({"type": "peer:connect"});
+        stamp_inbound(&mut event);
+        assert!(event.get("receivedAt").is_none());
+    }
+}
@@ -0,0 +1,342 @@
+// Shutdown — one bounded, idempotent routine for every exit path (window
+// close, updater restart, Windows session end), plus a per-process "running"
+// marker so the next launch can tell whether the previous one exited cleanly.
+// The marker records whether the run got past startup, which lets us count
+// consecutive startup crashes for safe mode.
+// Deliberate exits go through `exit`, which sets a documented exit code.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const MARKER_DIR: &str = "running";
+const CRASH_COUNT_FILE: &str = "startup-crashes";
+const PHASE_STARTING: &[u8] = b"starting";
+const PHASE_RUNNING: &[u8] = b"running";
+const EXIT_LOG: &str = "exit.log";
+/// The exit log starts over once it grows past this.
+const EXIT_LOG_MAX_BYTES: u64 = 64 * 1024;
+
+static SHUT_DOWN: AtomicBool = AtomicBool::new(false);
+static UNCLEAN_PREVIOUS: AtomicBool = AtomicBool::new(false);
+static STARTUP_CRASHES: AtomicU32 = AtomicU32::new(0);
+
+// ── Clean-shutdown marker ────────────────────────────────────────
+
+fn marker_dir() -> Result<PathBuf, String> {
+    Ok(super::data_root()?.join(MARKER_DIR))
+}
+
+#[cfg(windows)]
+pub(crate) fn process_alive(pid: u32) -> bool {
+    use windows_sys::Win32::Foundation::{CloseHandle, STILL_ACTIVE};
+    use windows_sys::Win32::System::Threading::{
+        GetExitCodeProcess, OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION,
+    };
+    // SAFETY: the handle is checked for null and closed before returning.
+    unsafe {
+        let handle = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+        if handle.is_null() {
+            return false;
+        }
+        let mut code = 0u32;
+        let ok = GetExitCodeProcess(handle, &mut code);
+        CloseHandle(handle);
+        ok != 0 && code == STILL_ACTIVE as u32
+    }
+}
+
+#[cfg(not(windows))]
+pub(crate) fn process_alive(_pid: u32) -> bool {
+    false
+}
+
+fn own_marker() -> Result<PathBuf, String> {
+    Ok(marker_dir()?.join(std::process::id().to_string()))
+}
+
+fn crash_count_path() -> Result<PathBuf, String> {
+    Ok(super::data_root()?.join(CRASH_COUNT_FILE))
+}
+
+fn store_crash_count(count: u32) {
+    STARTUP_CRASHES.store(count, Ordering::SeqCst);
+    if let Ok(path) = crash_count_path() {
+        let _ = fs::write(path, count.to_string());
+    }
+}
+
+/// Record that this process is starting. Markers left behind by processes
+/// that are no longer alive mean a previous run ended without `run` —
+/// a crash, a kill, or power loss. If one of them never got past startup,
+/// the consecutive startup crash count goes up.
+pub(crate) fn begin_session() {
+    let Ok(dir) = marker_dir() else {
+        return;
+    };
+    let _ = fs::create_dir_all(&dir);
+    let own = std::process::id();
+    let mut crashed_in_startup = false;
+    for entry in fs::read_dir(&dir).into_iter().flatten().flatten() {
+        let Some(pid) = entry
+            .file_name()
+            .to_str()
+            .and_then(|n| n.parse::<u32>().ok())
+        else {
+            continue;
+        };
+        if pid != own && !process_alive(pid) {
+            UNCLEAN_PREVIOUS.store(true, Ordering::SeqCst);
+            crashed_in_startup |= fs::read(entry.path()).is_ok_and(|p| p == PHASE_STARTING);
+            let _ = fs::remove_file(entry.path());
+        }
+    }
+
+    let previous = crash_count_path()
+        .ok()
+        .and_then(|p| fs::read_to_string(p).ok())
+        .and_then(|s| s.trim().parse::<u32>().ok())
+        .unwrap_or(0);
+    if crashed_in_startup {
+        store_crash_count(previous + 1);
+    } else {
+        STARTUP_CRASHES.store(previous, Ordering::SeqCst);
+    }
+
+    if let Err(e) = fs::write(dir.join(own.to_string()), PHASE_STARTING) {
+        eprintln!("Cannot write running marker: {}", e);
+        super::storage::note_failure("Cannot write running marker", &e);
+    }
+}
+
+/// Called once the app has been up long enough that startup is considered
+/// successful: a crash after this point is not a startup crash.
+pub(crate) fn startup_settled() {
+    if SHUT_DOWN.load(Ordering::SeqCst) {
+        return;
+    }
+    if let Ok(marker) = own_marker() {
+        let _ = fs::write(marker, PHASE_RUNNING);
+    }
+    // In safe mode only `leave_safe_mode` clears the count
+    if !super::safe_mode::active() {
+        reset_startup_crashes();
+    }
+}
+
+/// Whether a previous run ended without a clean shutdown.
+pub(crate) fn previous_unclean() -> bool {
+    UNCLEAN_PREVIOUS.load(Ordering::SeqCst)
+}
+
+/// Consecutive runs that crashed before `startup_settled`.
+pub(crate) fn startup_crashes() -> u32 {
+    STARTUP_CRASHES.load(Ordering::SeqCst)
+}
+
+pub(crate) fn reset_startup_crashes() {
+    if startup_crashes() != 0 {
+        store_crash_count(0);
+    }
+}
+
+/// A clean exit also resets the crash count, so quick restarts (e.g. by the
+/// updater) never look like a boot loop.
+fn mark_clean() {
+    if let Ok(marker) = own_marker() {
+        let _ = fs::remove_file(marker);
+    }
+    if !super::safe_mode::active() {
+        reset_startup_crashes();
+    }
+}
+
+// ── Shutdown routine ─────────────────────────────────────────────
+
+/// Silence the event sink, stop the sidecar (it is sent the shutdown command
+/// and killed after `sidecar.shutdownGraceSecs`), wipe any guest session,
+/// remove the sidecar's working directory and clear the running marker.
+/// Safe to call from several exit paths; only the first call acts.
+/// Settings and sequence counters are written as they change, so there is
+/// no other in-memory state to flush. `app` is None when the runtime never
+/// came up, so there is no sidecar to stop.
+pub(crate) fn run(app: Option<&tauri::AppHandle>, reason: &str) {
+    if SHUT_DOWN.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    eprintln!("Shutting down ({})", reason);
+    super::sink::discard();
+    if let Some(app) = app {
+        super::sidecar::manager(app)
+            .stop_gracefully(super::settings::current().sidecar.shutdown_grace());
+    }
+    super::profiles::wipe_ephemeral();
+    super::workdir::remove_own();
+    mark_clean();
+}
+
+/// Whether `run` has begun; nothing should start the sidecar again.
+pub(crate) fn in_progress() -> bool {
+    SHUT_DOWN.load(Ordering::SeqCst)
+}
+
+/// Windows gives very little time after WM_ENDSESSION and may never let the
+/// event loop return, so run the shutdown routine from the window procedure.
+#[cfg(windows)]
+pub(crate) fn hook_session_end(window: &tauri::WebviewWindow) {
+    use tauri::Manager;
+    use windows_sys::Win32::Foundation::{HWND, LPARAM, LRESULT, WPARAM};
+    use windows_sys::Win32::UI::Shell::{DefSubclassProc, SetWindowSubclass};
+    use windows_sys::Win32::UI::WindowsAndMessaging::WM_ENDSESSION;
+
+    unsafe extern "system" fn subclass_proc(
+        hwnd: HWND,
+        msg: u32,
+        wparam: WPARAM,
+        lparam: LPARAM,
+        _id: usize,
+        data: usize,
+    ) -> LRESULT {
+        // wparam is non-zero when the session really is ending
+        if msg == WM_ENDSESSION && wparam != 0 {
+            // SAFETY: `data` is the leaked AppHandle set up below.
+            let app = &*(data as *const tauri::AppHandle);
+            run(Some(app), "session-end");
+        }
+        DefSubclassProc(hwnd, msg, wparam, lparam)
+    }
+
+    let Ok(hwnd) = window.hwnd() else {
+        return;
+    };
+    // Leaked on purpose: the window procedure may run until the process ends
+    let app: &'static tauri::AppHandle = Box::leak(Box::new(window.app_handle().clone()));
+    // SAFETY: the subclass procedure is a plain function that lives for the
+    // whole process, the AppHandle it is given is never freed, and the
+    // window is alive while we hold a handle to it.
+    unsafe {
+        SetWindowSubclass(
+            hwnd.0 as _,
+            Some(subclass_proc),
+            1,
+            app as *const tauri::AppHandle as usize,
+        );
+    }
+}
+
+#[cfg(not(windows))]
+pub(crate) fn hook_session_end(_window: &tauri::WebviewWindow) {}
+
+// ── Exit codes ───────────────────────────────────────────────────
+
+/// Process exit codes, for scripts and the updater. Never renumber or reuse
+/// one.
+#[derive(Clone, Copy)]
+pub(crate) enum ExitCode {
+    Normal = 0,
+    /// The Tauri runtime or the main window could not be created (e.g.
+    /// WebView2 is missing).
+    RuntimeFailed = 10,
+    /// The setup hook failed.
+    SetupFailed = 11,
+}
+
+impl ExitCode {
+    const ALL: [ExitCode; 3] = [Self::Normal, Self::RuntimeFailed, Self::SetupFailed];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Normal => "normal",
+            Self::RuntimeFailed => "runtime-failed",
+            Self::SetupFailed => "setup-failed",
+        }
+    }
+}
+
+/// Name → code, as reported by `get_app_info`.
+pub(crate) fn exit_codes() -> BTreeMap<&'static str, i32> {
+    ExitCode::ALL
+        .iter()
+        .map(|code| (code.name(), *code as i32))
+        .collect()
+}
+
+/// Record the exit in exit.log. Release builds have no console, so this is
+/// the only place a startup failure leaves a trace.
+fn log_exit(code: ExitCode, reason: &str) {
+    let dir = super::storage::writable_dir(super::data_root().ok());
+    append_exit_line(&dir.join(EXIT_LOG), code, reason);
+}
+
+fn append_exit_line(path: &Path, code: ExitCode, reason: &str) {
+    let fresh = fs::metadata(path).is_ok_and(|m| m.len() > EXIT_LOG_MAX_BYTES);
+    let file = fs::OpenOptions::new()
+        .create(true)
+        .append(!fresh)
+        .write(true)
+        .truncate(fresh)
+        .open(path);
+    let at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or(0);
+    if let Ok(mut file) = file {
+        let _ = writeln!(file, "{} {} {}: {}", at, code as i32, code.name(), reason);
+    }
+}
+
+/// Run the shutdown routine, log why, and exit with `code`.
+pub(crate) fn exit(code: ExitCode, reason: &str) -> ! {
+    eprintln!(
+        "Exiting with code {} ({}): {}",
+        code as i32,
+        code.name(),
+        reason
+    );
+    // The runtime is gone or never came up; the exit handler already
+    // stopped the sidecar if there was one
+    run(None, code.name());
+    log_exit(code, reason);
+    let _ = std::io::stderr().flush();
+    std::process::exit(code as i32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn exit_codes_are_stable_and_distinct() {
+        let codes = exit_codes();
+        assert_eq!(codes["normal"], 0);
+        assert_eq!(codes["runtime-failed"], 10);
+        assert_eq!(codes["setup-failed"], 11);
+        assert_eq!(codes.len(), ExitCode::ALL.len());
+        let numbers: HashSet<i32> = codes.values().copied().collect();
+        assert_eq!(numbers.len(), codes.len());
+    }
+
+    #[test]
+    fn the_exit_log_appends_and_starts_over_when_large() {
+        let path = std::env::temp_dir().join(format!("concord-exit-{}.log", std::process::id()));
+        let _ = fs::remove_file(&path);
+        append_exit_line(&path, ExitCode::SetupFailed, "no window");
+        append_exit_line(&path, ExitCode::Normal, "quit");
+        let log = fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = log.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].ends_with(" 11 setup-failed: no window"));
+        assert!(lines[1].ends_with(" 0 normal: quit"));
+
+        fs::write(&path, vec![b'x'; EXIT_LOG_MAX_BYTES as usize + 1]).unwrap();
+        append_exit_line(&path, ExitCode::RuntimeFailed, "no webview");
+        let log = fs::read_to_string(&path).unwrap();
+        assert_eq!(log.lines().count(), 1);
+        assert!(log.contains(" 10 runtime-failed: no webview"));
+        let _ = fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,512 @@
+// Sidecar process state — the child, its stdin, and what is known about it
+// across respawns, owned by one manager so every path locks (and survives a
+// poisoned lock) the same way. Spawning and the stdout reader live in
+// `start_sidecar` in lib.rs, supervision in supervisor.rs.
+//
+// Every start (first launch, restart, hot reload, profile switch,
+// supervisor respawn) holds the start lock from its kill through the
+// attach, so two starts can never both spawn a process.
+//
+// The manager is managed Tauri state, registered in `run`; `manager(app)`
+// fetches it. Nothing in it depends on Tauri, so tests drive it with plain
+// child processes.
+
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Child, ChildStdin};
+use std::sync::{Mutex, MutexGuard, TryLockError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tauri::Manager;
+
+use super::error::BridgeError;
+
+/// The app's sidecar manager.
+pub(crate) fn manager(app: &tauri::AppHandle) -> tauri::State<'_, SidecarManager> {
+    app.state::<SidecarManager>()
+}
+
+pub(crate) struct SidecarManager {
+    state: Mutex<SidecarState>,
+    /// Separate from `state` so a write never waits behind a stop that is
+    /// polling the child.
+    stdin: Mutex<Option<ChildStdin>>,
+    /// Held by whoever is starting the sidecar; see `lock_start`.
+    starting: Mutex<()>,
+}
+
+/// Proof that the start lock is held, for `start_sidecar_holding`.
+pub(crate) struct Starting<'a> {
+    _guard: MutexGuard<'a, ()>,
+}
+
+struct SidecarState {
+    child: Option<Child>,
+    /// When the current process was spawned, in ms since the epoch.
+    started_at: Option<u64>,
+    /// Successful spawns this session, restarts included.
+    starts: u32,
+    incognito: bool,
+    /// Paths the latest start resolved.
+    script: Option<PathBuf>,
+    node: Option<PathBuf>,
+    log_path: Option<PathBuf>,
+}
+
+/// A freshly spawned sidecar, handed over by `start_sidecar`.
+pub(crate) struct Spawned {
+    pub child: Child,
+    pub stdin: ChildStdin,
+    pub incognito: bool,
+    pub script: PathBuf,
+    pub node: PathBuf,
+    pub log_path: PathBuf,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SidecarStatus {
+    pub running: bool,
+    pub pid: Option<u32>,
+    /// When the current process was spawned, in ms since the epoch.
+    pub started_at: Option<u64>,
+    /// Starts after the first one this session, automatic ones included.
+    pub restart_count: u32,
+    /// Whether a start or restart is under way.
+    pub restarting: bool,
+    pub incognito: bool,
+    /// The script and Node binary the latest start resolved.
+    pub script: Option<String>,
+    pub node: Option<String>,
+}
+
+/// How `stop_within` ended.
+#[derive(Debug, PartialEq)]
+enum Stopped {
+    /// Exited on its own, with this code if it had one.
+    Exited(Option<i32>),
+    /// Still running when the grace period ran out.
+    Killed,
+}
+
+impl SidecarManager {
+    pub(crate) const fn new() -> Self {
+        Self {
+            state: Mutex::new(SidecarState {
+                child: None,
+                started_at: None,
+                starts: 0,
+                incognito: false,
+                script: None,
+                node: None,
+                log_path: None,
+            }),
+            stdin: Mutex::new(None),
+            starting: Mutex::new(()),
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, SidecarState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn stdin(&self) -> MutexGuard<'_, Option<ChildStdin>> {
+        self.stdin.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Wait for any other start to finish, then hold the start lock. Held
+    /// from the kill through the attach, and by restarts across their stop.
+    pub(crate) fn lock_start(&self) -> Starting<'_> {
+        Starting {
+            _guard: self.starting.lock().unwrap_or_else(|e| e.into_inner()),
+        }
+    }
+
+    /// `lock_start`, or None if another start or restart holds it.
+    pub(crate) fn try_lock_start(&self) -> Option<Starting<'_>> {
+        match self.starting.try_lock() {
+            Ok(guard) => Some(Starting { _guard: guard }),
+            Err(TryLockError::Poisoned(e)) => Some(Starting {
+                _guard: e.into_inner(),
+            }),
+            Err(TryLockError::WouldBlock) => None,
+        }
+    }
+
+    /// Whether a start or restart holds the start lock.
+    pub(crate) fn is_starting(&self) -> bool {
+        matches!(self.starting.try_lock(), Err(TryLockError::WouldBlock))
+    }
+
+    /// Take over a spawned process. The previous one should be stopped
+    /// first; one still attached is killed, so it can't be orphaned.
+    pub(crate) fn attach(&self, spawned: Spawned) {
+        *self.stdin() = Some(spawned.stdin);
+        let mut state = self.state();
+        if let Some(mut previous) = state.child.take() {
+            let _ = previous.kill();
+            let _ = previous.wait();
+        }
+        state.child = Some(spawned.child);
+        state.started_at = Some(super::now_ms());
+        state.starts += 1;
+        state.incognito = spawned.incognito;
+        state.script = Some(spawned.script);
+        state.node = Some(spawned.node);
+        state.log_path = Some(spawned.log_path);
+    }
+
+    /// Whether commands can be written, i.e. stdin is attached.
+    pub(crate) fn is_running(&self) -> bool {
+        self.stdin().is_some()
+    }
+
+    /// Whether the latest start used an ephemeral identity.
+    pub(crate) fn incognito(&self) -> bool {
+        self.state().incognito
+    }
+
+    /// The log file the latest start wrote stderr to.
+    pub(crate) fn log_path(&self) -> Option<PathBuf> {
+        self.state().log_path.clone()
+    }
+
+    /// Write one protocol line; `injected` marks lines typed into the console.
+    pub(crate) fn write_line(&self, line: &str, injected: bool) -> Result<(), BridgeError> {
+        {
+            let mut guard = self.stdin();
+            let Some(ref mut stdin) = *guard else {
+                return Err(BridgeError::SidecarNotRunning);
+            };
+            writeln!(stdin, "{}", line).map_err(|e| BridgeError::io("Write to sidecar", e))?;
+            stdin
+                .flush()
+                .map_err(|e| BridgeError::io("Flush sidecar", e))?;
+        }
+        super::console::trace(super::console::Direction::Outbound, line, injected);
+        Ok(())
+    }
+
+    /// Kill the sidecar now. Its exit is expected, so it isn't respawned.
+    pub(crate) fn kill(&self) {
+        super::supervisor::expect_exit();
+        *self.stdin() = None;
+        let mut state = self.state();
+        if let Some(ref mut child) = state.child {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+        state.child = None;
+        state.started_at = None;
+    }
+
+    /// Send the shutdown command and close stdin so the sidecar can stop
+    /// libp2p and exit on its own, then kill it if it has not exited within
+    /// `grace`. The outcome goes to the debug breadcrumb.
+    pub(crate) fn stop_gracefully(&self, grace: Duration) {
+        let breadcrumb = match self.stop_within(grace) {
+            None => return,
+            Some(Stopped::Exited(code)) => format!(
+                "sidecar stopped gracefully, exit code {}",
+                code.map_or("unknown".to_string(), |c| c.to_string())
+            ),
+            Some(Stopped::Killed) => format!("sidecar killed after {:?} grace", grace),
+        };
+        super::append_breadcrumb(&breadcrumb);
+    }
+
+    /// `stop_gracefully` without the breadcrumb; None if nothing was running.
+    fn stop_within(&self, grace: Duration) -> Option<Stopped> {
+        super::supervisor::expect_exit();
+        if let Ok(line) = super::protocol::encode(
+            &super::protocol::SidecarCommand::Shutdown,
+            &super::next_request_id(),
+        ) {
+            // Closing stdin below asks for the same if this write fails
+            let _ = self.write_line(&line, false);
+        }
+        *self.stdin() = None;
+        let deadline = Instant::now() + grace;
+        let exited = loop {
+            let exited = {
+                let mut state = self.state();
+                match state.child {
+                    Some(ref mut child) => match child.try_wait() {
+                        Ok(Some(status)) => Some(Some(status)),
+                        Ok(None) => None,
+                        Err(_) => Some(None),
+                    },
+                    None => return None,
+                }
+            };
+            if exited.is_some() || Instant::now() >= deadline {
+                break exited;
+            }
+            thread::sleep(Duration::from_millis(50));
+        };
+        self.kill();
+        Some(match exited {
+            Some(status) => Stopped::Exited(status.and_then(|s| s.code())),
+            None => Stopped::Killed,
+        })
+    }
+
+    /// Detach the child after its stdout ended, unless `still_current` says
+    /// a deliberate stop or a newer start got there first. Checked under the
+    /// lock, so a concurrent `kill` sees either the child or nothing.
+    pub(crate) fn detach_if(&self, still_current: impl FnOnce() -> bool) -> Option<Child> {
+        let mut state = self.state();
+        if !still_current() {
+            return None;
+        }
+        *self.stdin() = None;
+        state.started_at = None;
+        state.child.take()
+    }
+
+    pub(crate) fn status(&self) -> SidecarStatus {
+        let restarting = self.is_starting();
+        let mut state = self.state();
+        let (running, pid) = match state.child {
+            Some(ref mut child) => (matches!(child.try_wait(), Ok(None)), Some(child.id())),
+            None => (false, None),
+        };
+        SidecarStatus {
+            running,
+            pid,
+            started_at: state.started_at,
+            restart_count: state.starts.saturating_sub(1),
+            restarting,
+            incognito: state.incognito,
+            script: state
+                .script
+                .as_ref()
+                .map(|p| p.to_string_lossy().into_owned()),
+            node: state
+                .node
+                .as_ref()
+                .map(|p| p.to_string_lossy().into_owned()),
+        }
+    }
+}
+
+impl Drop for SidecarManager {
+    /// Never leave the process running behind the manager.
+    fn drop(&mut self) {
+        *self.stdin.get_mut().unwrap_or_else(|e| e.into_inner()) = None;
+        let state = self.state.get_mut().unwrap_or_else(|e| e.into_inner());
+        if let Some(mut child) = state.child.take() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::fs;
+    use std::io::{BufRead, BufReader};
+    use std::process::{ChildStdout, Command, Stdio};
+
+    use super::*;
+
+    /// A stand-in sidecar: `sh -c script` with piped stdin.
+    fn spawn(script: &str) -> Spawned {
+        spawn_with(script, Stdio::null()).0
+    }
+
+    /// `spawn`, with stdout piped back.
+    fn spawn_piped(script: &str) -> (Spawned, ChildStdout) {
+        let (spawned, stdout) = spawn_with(script, Stdio::piped());
+        (spawned, stdout.unwrap())
+    }
+
+    fn spawn_with(script: &str, stdout: Stdio) -> (Spawned, Option<ChildStdout>) {
+        let mut child = Command::new("sh")
+            .args(["-c", script])
+            .stdin(Stdio::piped())
+            .stdout(stdout)
+            .spawn()
+            .unwrap();
+        let stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take();
+        let spawned = Spawned {
+            child,
+            stdin,
+            incognito: true,
+            script: PathBuf::from("fake-sidecar.js"),
+            node: PathBuf::from("sh"),
+            log_path: PathBuf::from("fake-sidecar.log"),
+        };
+        (spawned, stdout)
+    }
+
+    fn is_alive(pid: u32) -> bool {
+        Command::new("kill")
+            .args(["-0", &pid.to_string()])
+            .stderr(Stdio::null())
+            .status()
+            .unwrap()
+            .success()
+    }
+
+    #[test]
+    fn attach_records_the_process_and_counts_restarts() {
+        let manager = SidecarManager::new();
+        assert!(!manager.is_running());
+        manager.attach(spawn("cat > /dev/null"));
+        assert!(manager.is_running());
+        assert!(manager.incognito());
+        assert_eq!(manager.log_path(), Some(PathBuf::from("fake-sidecar.log")));
+        let status = manager.status();
+        assert!(status.running);
+        assert!(status.pid.is_some());
+        assert_eq!(status.restart_count, 0);
+
+        manager.kill();
+        assert!(!manager.is_running());
+        manager.attach(spawn("cat > /dev/null"));
+        assert_eq!(manager.status().restart_count, 1);
+        manager.kill();
+    }
+
+    #[test]
+    fn attach_kills_a_child_still_attached() {
+        let manager = SidecarManager::new();
+        manager.attach(spawn("cat > /dev/null"));
+        let first = manager.status().pid.expect("pid");
+        manager.attach(spawn("cat > /dev/null"));
+        assert_ne!(manager.status().pid, Some(first));
+        // Killed and reaped, so no process is left with that pid
+        assert!(!is_alive(first));
+        manager.kill();
+    }
+
+    #[test]
+    fn dropping_the_manager_kills_the_child() {
+        let manager = SidecarManager::new();
+        manager.attach(spawn("cat > /dev/null"));
+        let pid = manager.status().pid.expect("pid");
+        assert!(is_alive(pid));
+        drop(manager);
+        assert!(!is_alive(pid));
+    }
+
+    #[test]
+    fn replies_are_routed_to_the_request_they_answer() {
+        // Answers the first command with a dial_result echoing its requestId
+        let (spawned, stdout) = spawn_piped(
+            r#"read line
+id=$(printf '%s' "$line" | sed 's/.*"requestId":"\([^"]*\)".*/\1/')
+printf '{"type":"dial_result","ok":true,"address":"fake","requestId":"%s"}\n' "$id"
+cat > /dev/null"#,
+        );
+        let manager = SidecarManager::new();
+        manager.attach(spawned);
+
+        let request_id = crate::next_request_id();
+        let other = crate::register_request("req-never-answered");
+        let reply = crate::register_request(&request_id);
+        let dial = crate::protocol::SidecarCommand::Dial {
+            address: "fake".to_string(),
+        };
+        let line = crate::protocol::encode(&dial, &request_id).unwrap();
+        manager.write_line(&line, false).unwrap();
+
+        let mut answer = String::new();
+        BufReader::new(stdout).read_line(&mut answer).unwrap();
+        let json: serde_json::Value = serde_json::from_str(&answer).unwrap();
+        let event = crate::protocol::SidecarEvent::parse(&json).unwrap();
+        crate::route_reply(event.request_id(), &json);
+
+        let routed = reply.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(routed["type"], "dial_result");
+        assert_eq!(routed["requestId"], request_id.as_str());
+        assert!(other.try_recv().is_err());
+        manager.kill();
+    }
+
+    #[test]
+    fn only_one_start_holds_the_start_lock() {
+        let manager = SidecarManager::new();
+        assert!(!manager.is_starting());
+        let start = manager.lock_start();
+        assert!(manager.is_starting());
+        assert!(manager.try_lock_start().is_none());
+        assert!(manager.status().restarting);
+        drop(start);
+        assert!(!manager.is_starting());
+        assert!(manager.try_lock_start().is_some());
+    }
+
+    #[test]
+    fn detach_if_leaves_the_child_when_no_longer_current() {
+        let manager = SidecarManager::new();
+        manager.attach(spawn("cat > /dev/null"));
+        assert!(manager.detach_if(|| false).is_none());
+        assert!(manager.is_running());
+
+        let mut child = manager.detach_if(|| true).expect("child");
+        assert!(!manager.is_running());
+        assert!(manager.status().pid.is_none());
+        assert!(manager.detach_if(|| true).is_none());
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+
+    #[test]
+    fn stop_waits_for_a_sidecar_that_exits_on_shutdown() {
+        let manager = SidecarManager::new();
+        // Exits once it has read the shutdown command
+        manager.attach(spawn("read line; exit 3"));
+        assert_eq!(
+            manager.stop_within(Duration::from_secs(10)),
+            Some(Stopped::Exited(Some(3)))
+        );
+        assert!(!manager.is_running());
+        assert!(manager.status().pid.is_none());
+    }
+
+    #[test]
+    fn stop_sends_the_shutdown_command_then_closes_stdin() {
+        let received =
+            std::env::temp_dir().join(format!("concord-shutdown-{}.txt", std::process::id()));
+        let _ = fs::remove_file(&received);
+        let manager = SidecarManager::new();
+        // Exits only at end of input, so it sees everything written first
+        manager.attach(spawn(&format!("cat > '{}'", received.display())));
+        assert_eq!(
+            manager.stop_within(Duration::from_secs(10)),
+            Some(Stopped::Exited(Some(0)))
+        );
+
+        let lines = fs::read_to_string(&received).unwrap();
+        let lines: Vec<&str> = lines.lines().collect();
+        assert_eq!(lines.len(), 1);
+        let command: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(command["cmd"], "shutdown");
+        assert!(command["requestId"].is_string());
+        let _ = fs::remove_file(&received);
+    }
+
+    #[test]
+    fn stop_kills_a_sidecar_that_ignores_shutdown() {
+        let manager = SidecarManager::new();
+        manager.attach(spawn("exec sleep 30"));
+        let started = Instant::now();
+        assert_eq!(
+            manager.stop_within(Duration::from_millis(200)),
+            Some(Stopped::Killed)
+        );
+        assert!(started.elapsed() < Duration::from_secs(10));
+        assert!(!manager.is_running());
+    }
+
+    #[test]
+    fn stop_without_a_sidecar_does_nothing() {
+        let manager = SidecarManager::new();
+        assert_eq!(manager.stop_within(Duration::from_millis(10)), None);
+    }
+}
@@ -0,0 +1,338 @@
+// Sidecar launch options — how the Node process is started, kept in
+// config.json in the profile directory: a fixed listen port (for a port
+// forward), peers to dial on every start, a data directory other than the
+// profile's (to run two instances side by side), and extra environment
+// variables. A missing file means defaults; missing fields take theirs.
+// `start_sidecar` reads the file on every start and passes the options as
+// CONCORD_* variables; `set_sidecar_config` validates before writing and
+// can restart the sidecar so they take effect.
+//
+// With `dataDirOverride` set, the sidecar's identity lives there, so it is
+// not part of profile backups.
+
+use std::collections::BTreeMap;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use super::error::BridgeError;
+use super::settings::SettingEffect;
+
+pub(crate) const CONFIG_FILE: &str = "config.json";
+/// Variables the bridge sets itself; `extraEnv` can't override them.
+const RESERVED_PREFIX: &str = "CONCORD_";
+/// Set by the bridge so the bundled native addons are found.
+const RESERVED_NAMES: &[&str] = &["NODE_PATH"];
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default, rename_all = "camelCase")]
+pub struct SidecarConfig {
+    /// Listen on this TCP port instead of the saved or a random one.
+    pub listen_port: Option<u16>,
+    /// Multiaddrs dialled after every start.
+    pub bootstrap_peers: Vec<String>,
+    /// Identity and sidecar state go here instead of the profile directory.
+    pub data_dir_override: Option<PathBuf>,
+    /// Extra environment variables for the Node process, e.g. `DEBUG`.
+    pub extra_env: BTreeMap<String, String>,
+}
+
+fn config_path() -> Result<PathBuf, BridgeError> {
+    Ok(super::app_data_dir()?.join(CONFIG_FILE))
+}
+
+/// The current options; defaults if the file is missing or unreadable.
+pub(crate) fn load() -> SidecarConfig {
+    match config_path() {
+        Ok(path) => load_from(&path),
+        Err(_) => SidecarConfig::default(),
+    }
+}
+
+fn load_from(path: &Path) -> SidecarConfig {
+    let Ok(text) = super::storage::read_to_string(path) else {
+        return SidecarConfig::default();
+    };
+    serde_json::from_str(&text).unwrap_or_else(|e| {
+        eprintln!("Ignoring malformed config.json: {}", e);
+        SidecarConfig::default()
+    })
+}
+
+fn save(path: &Path, config: &SidecarConfig) -> Result<(), BridgeError> {
+    let json = serde_json::to_string_pretty(config).map_err(|e| e.to_string())?;
+    super::storage::write(path, json).map_err(|e| BridgeError::io("Cannot write config.json", e))
+}
+
+/// Where the sidecar keeps its identity and state.
+pub(crate) fn data_dir() -> Result<PathBuf, BridgeError> {
+    match load().data_dir_override {
+        Some(dir) => {
+            super::ensure_dir(&dir, "Cannot create the sidecar data directory")?;
+            Ok(dir)
+        }
+        None => super::app_data_dir(),
+    }
+}
+
+/// Whether `addr` looks like a multiaddr the sidecar can dial:
+/// `/ip4|ip6|dns|dns4|dns6|dnsaddr/<host>/...` with no empty parts.
+fn is_multiaddr(addr: &str) -> bool {
+    let Some(rest) = addr.strip_prefix('/') else {
+        return false;
+    };
+    let parts: Vec<&str> = rest.split('/').collect();
+    if parts.len() < 2
+        || parts
+            .iter()
+            .any(|p| p.is_empty() || p.contains(char::is_whitespace))
+    {
+        return false;
+    }
+    match parts[0] {
+        "ip4" => parts[1].parse::<Ipv4Addr>().is_ok(),
+        "ip6" => parts[1].parse::<Ipv6Addr>().is_ok(),
+        "dns" | "dns4" | "dns6" | "dnsaddr" => true,
+        _ => false,
+    }
+}
+
+impl SidecarConfig {
+    fn validate(&self) -> Result<(), BridgeError> {
+        if self.listen_port == Some(0) {
+            return Err(BridgeError::InvalidArgument(
+                "listenPort must be between 1 and 65535; leave it unset for any port".to_string(),
+            ));
+        }
+        for addr in &self.bootstrap_peers {
+            if !is_multiaddr(addr) {
+                return Err(BridgeError::InvalidAddress(addr.clone()));
+            }
+        }
+        if let Some(ref dir) = self.data_dir_override {
+            if !dir.is_absolute() {
+                return Err(BridgeError::InvalidArgument(format!(
+                    "dataDirOverride must be an absolute path: {}",
+                    dir.display()
+                )));
+            }
+            if dir.exists() && !dir.is_dir() {
+                return Err(BridgeError::InvalidArgument(format!(
+                    "dataDirOverride is not a directory: {}",
+                    dir.display()
+                )));
+            }
+        }
+        for key in self.extra_env.keys() {
+            if key.is_empty() || key.contains('=') || key.contains('\0') {
+                return Err(BridgeError::InvalidArgument(format!(
+                    "Invalid environment variable name '{}'",
+                    key
+                )));
+            }
+            let upper = key.to_ascii_uppercase();
+            if upper.starts_with(RESERVED_PREFIX) || RESERVED_NAMES.contains(&upper.as_str()) {
+                return Err(BridgeError::InvalidArgument(format!(
+                    "{} is set by Concord and can't be overridden",
+                    key
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Environment for the Node process. `start_sidecar` sets its own
+    /// variables (CONCORD_DATA_DIR, NODE_PATH) after these, so they win
+    /// even over a file saved before they were reserved.
+    pub(crate) fn env(&self) -> Vec<(String, String)> {
+        let mut env: Vec<(String, String)> = self
+            .extra_env
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+        if let Some(port) = self.listen_port {
+            env.push(("CONCORD_LISTEN_PORT".to_string(), port.to_string()));
+        }
+        if !self.bootstrap_peers.is_empty() {
+            env.push((
+                "CONCORD_BOOTSTRAP_PEERS".to_string(),
+                self.bootstrap_peers.join(","),
+            ));
+        }
+        env
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SidecarConfigSaved {
+    pub config: SidecarConfig,
+    /// `restart-required` while a running sidecar still uses the old options.
+    pub effect: SettingEffect,
+}
+
+// ── Tauri commands ───────────────────────────────────────────────
+
+#[tauri::command]
+pub fn get_sidecar_config() -> SidecarConfig {
+    load()
+}
+
+/// Validate and save the launch options. They apply from the next start;
+/// with `restart` a running sidecar is restarted now, as with
+/// `p2p_restart_sidecar`.
+#[tauri::command]
+pub async fn set_sidecar_config(
+    app: tauri::AppHandle,
+    config: SidecarConfig,
+    restart: Option<bool>,
+) -> Result<SidecarConfigSaved, BridgeError> {
+    config.validate()?;
+    save(&config_path()?, &config)?;
+    let effect = if !super::sidecar::manager(&app).is_running() {
+        SettingEffect::Immediate
+    } else if restart.unwrap_or(false) {
+        super::p2p_restart_sidecar(app).await?;
+        SettingEffect::Immediate
+    } else {
+        SettingEffect::RestartRequired
+    };
+    Ok(SidecarConfigSaved { config, effect })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn absolute_dir() -> PathBuf {
+        std::env::temp_dir()
+    }
+
+    #[test]
+    fn missing_fields_take_their_defaults() {
+        let config: SidecarConfig = serde_json::from_str(r#"{"listenPort": 4001}"#).unwrap();
+        assert_eq!(config.listen_port, Some(4001));
+        assert!(config.bootstrap_peers.is_empty());
+        assert!(config.data_dir_override.is_none());
+        assert!(serde_json::from_str::<SidecarConfig>("{}").is_ok());
+    }
+
+    #[test]
+    fn multiaddrs_are_checked_by_protocol() {
+        assert!(is_multiaddr("/ip4/203.0.113.5/tcp/4001"));
+        assert!(is_multiaddr("/ip6/::1/tcp/4001"));
+        assert!(is_multiaddr("/dnsaddr/bootstrap.libp2p.io"));
+        assert!(!is_multiaddr("ip4/1.2.3.4/tcp/1"));
+        assert!(!is_multiaddr("/ip4/999.1.1.1/tcp/1"));
+        assert!(!is_multiaddr("/ip4/1.2.3.4//tcp"));
+        assert!(!is_multiaddr("/dns/has space/tcp/1"));
+        assert!(!is_multiaddr("/udp/1"));
+    }
+
+    #[test]
+    fn a_valid_config_passes() {
+        let config = SidecarConfig {
+            listen_port: Some(4001),
+            bootstrap_peers: vec!["/dns4/relay.example/tcp/443/wss".to_string()],
+            data_dir_override: Some(absolute_dir()),
+            extra_env: BTreeMap::from([("DEBUG".to_string(), "libp2p:*".to_string())]),
+        };
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn invalid_configs_are_rejected() {
+        let check = |change: fn(&mut SidecarConfig)| {
+            let mut config = SidecarConfig::default();
+            change(&mut config);
+            config.validate().unwrap_err().code()
+        };
+        assert_eq!(check(|c| c.listen_port = Some(0)), "invalid-argument");
+        assert_eq!(
+            check(|c| c.bootstrap_peers = vec!["not-an-addr".to_string()]),
+            "invalid-address"
+        );
+        assert_eq!(
+            check(|c| c.data_dir_override = Some(PathBuf::from("relative/dir"))),
+            "invalid-argument"
+        );
+        assert_eq!(
+            check(|c| {
+                c.extra_env.insert("A=B".to_string(), String::new());
+            }),
+            "invalid-argument"
+        );
+        assert_eq!(
+            check(|c| {
+                c.extra_env
+                    .insert("concord_listen_port".to_string(), "1".to_string());
+            }),
+            "invalid-argument"
+        );
+    }
+
+    #[test]
+    fn node_path_is_reserved_in_any_case() {
+        for key in ["NODE_PATH", "node_path"] {
+            let config = SidecarConfig {
+                extra_env: BTreeMap::from([(key.to_string(), "/elsewhere".to_string())]),
+                ..Default::default()
+            };
+            assert_eq!(config.validate().unwrap_err().code(), "invalid-argument");
+        }
+    }
+
+    #[test]
+    fn a_saved_config_loads_back() {
+        let path = std::env::temp_dir().join(format!("concord-config-{}.json", std::process::id()));
+        let config = SidecarConfig {
+            listen_port: Some(4001),
+            bootstrap_peers: vec!["/dns4/relay.example/tcp/443/wss".to_string()],
+            data_dir_override: Some(absolute_dir()),
+            extra_env: BTreeMap::from([("DEBUG".to_string(), "libp2p:*".to_string())]),
+        };
+        save(&path, &config).unwrap();
+        let loaded = load_from(&path);
+        assert_eq!(
+            serde_json::to_value(&loaded).unwrap(),
+            serde_json::to_value(&config).unwrap()
+        );
+        let _ = std::fs::remove_file(&path);
+        assert!(load_from(&path).listen_port.is_none());
+    }
+
+    #[test]
+    fn a_file_as_data_dir_is_rejected() {
+        let file = std::env::temp_dir().join(format!("concord-not-a-dir-{}", std::process::id()));
+        std::fs::write(&file, "").unwrap();
+        let config = SidecarConfig {
+            data_dir_override: Some(file.clone()),
+            ..Default::default()
+        };
+        assert!(config.validate().is_err());
+        let _ = std::fs::remove_file(&file);
+    }
+
+    #[test]
+    fn options_become_environment_variables() {
+        let config = SidecarConfig {
+            listen_port: Some(4001),
+            bootstrap_peers: vec!["/ip4/1.2.3.4/tcp/1".to_string(), "/dns/a/tcp/2".to_string()],
+            data_dir_override: None,
+            extra_env: BTreeMap::from([("DEBUG".to_string(), "*".to_string())]),
+        };
+        assert_eq!(
+            config.env(),
+            [
+                ("DEBUG".to_string(), "*".to_string()),
+                ("CONCORD_LISTEN_PORT".to_string(), "4001".to_string()),
+                (
+                    "CONCORD_BOOTSTRAP_PEERS".to_string(),
+                    "/ip4/1.2.3.4/tcp/1,/dns/a/tcp/2".to_string()
+                ),
+            ]
+        );
+        assert!(SidecarConfig::default().env().is_empty());
+    }
+}
@@ -0,0 +1,152 @@
+// Sidecar log — the sidecar's stderr is piped through the bridge: a reader
+// thread per process writes each line to `sidecar.log` and emits it as a
+// `stderr` event. The log rotates to `sidecar.log.1` at MAX_LOG_BYTES, so
+// at most two files are kept however chatty libp2p gets. Events are capped
+// at MAX_EVENTS_PER_SEC; lines over the cap only go to the file, and the
+// next event carries how many were `suppressed`.
+//
+// Logs used to be written per PID (`sidecar-{pid}.log`); `remove_stale`
+// deletes those on startup.
+
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub(crate) const LOG_FILE: &str = "sidecar.log";
+const MAX_LOG_BYTES: u64 = 5 * 1024 * 1024;
+const MAX_EVENTS_PER_SEC: u32 = 20;
+
+static LOG: Mutex<Option<RotatingLog>> = Mutex::new(None);
+
+struct RotatingLog {
+    path: PathBuf,
+    file: fs::File,
+    len: u64,
+}
+
+impl RotatingLog {
+    fn open(path: &Path) -> io::Result<Self> {
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        let len = file.metadata()?.len();
+        Ok(Self {
+            path: path.to_path_buf(),
+            file,
+            len,
+        })
+    }
+
+    fn write_line(&mut self, line: &str) -> io::Result<()> {
+        if self.len > 0 && self.len + line.len() as u64 + 1 > MAX_LOG_BYTES {
+            fs::rename(&self.path, rotated(&self.path))?;
+            *self = Self::open(&self.path)?;
+        }
+        writeln!(self.file, "{}", line)?;
+        self.len += line.len() as u64 + 1;
+        Ok(())
+    }
+}
+
+fn rotated(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".1");
+    PathBuf::from(name)
+}
+
+/// Send stderr lines to `path` from now on. Opens the file first, so a
+/// start can fall back to another directory if it can't be written.
+pub(crate) fn open(path: &Path) -> io::Result<()> {
+    let mut log = LOG.lock().unwrap_or_else(|e| e.into_inner());
+    if log.as_ref().is_some_and(|log| log.path == path) {
+        return Ok(());
+    }
+    *log = Some(RotatingLog::open(path)?);
+    Ok(())
+}
+
+/// Stop writing to the log and close it, before its directory is swapped
+/// or deleted (restore, profile switch, ephemeral wipe). Lines arriving
+/// until the next `open` are dropped.
+pub(crate) fn close() {
+    *LOG.lock().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+fn append(line: &str) {
+    if let Some(ref mut log) = *LOG.lock().unwrap_or_else(|e| e.into_inner()) {
+        // A full disk is reported by storage.rs elsewhere; the line is lost
+        let _ = log.write_line(line);
+    }
+}
+
+/// Start the reader for one sidecar's stderr. It ends when the process does.
+pub(crate) fn start_reader(app: tauri::AppHandle, stderr: impl Read + Send + 'static) {
+    thread::spawn(move || {
+        let mut window = Instant::now();
+        let mut sent = 0;
+        let mut suppressed = 0;
+        for line in BufReader::new(stderr).lines() {
+            let Ok(line) = line else { break };
+            append(&line);
+            if window.elapsed() >= Duration::from_secs(1) {
+                window = Instant::now();
+                sent = 0;
+            }
+            if sent >= MAX_EVENTS_PER_SEC {
+                suppressed += 1;
+                continue;
+            }
+            sent += 1;
+            let mut event = serde_json::json!({"type": "stderr", "message": line});
+            if suppressed > 0 {
+                event["suppressed"] = serde_json::json!(suppressed);
+                suppressed = 0;
+            }
+            super::emit_p2p_event(&app, event);
+        }
+    });
+}
+
+/// The last `lines` lines of the log, reaching back into the rotated file
+/// when the current one is shorter; everything when `lines` is None.
+pub(crate) fn read(path: &Path, lines: Option<usize>) -> String {
+    let current = fs::read_to_string(path).unwrap_or_default();
+    let previous = if !lines.is_some_and(|n| current.lines().count() >= n) {
+        fs::read_to_string(rotated(path)).unwrap_or_default()
+    } else {
+        String::new()
+    };
+    let Some(wanted) = lines else {
+        return previous + &current;
+    };
+    let mut tail: Vec<&str> = current
+        .lines()
+        .rev()
+        .chain(previous.lines().rev())
+        .take(wanted)
+        .collect();
+    tail.reverse();
+    let mut out = tail.join("\n");
+    if !out.is_empty() {
+        out.push('\n');
+    }
+    out
+}
+
+/// Delete per-PID logs (`sidecar-*.log`) left by earlier versions.
+pub(crate) fn remove_stale(dir: &Path) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if name.starts_with("sidecar-") && name.ends_with(".log") {
+            let _ = fs::remove_file(entry.path());
+        }
+    }
+}
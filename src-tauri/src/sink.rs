@@ -0,0 +1,253 @@
+// Event sink — every `p2p-event` goes through here on its way to the webview.
+// When emitting keeps failing (typically the window being destroyed while the
+// reader thread is still busy), events are held in a bounded buffer instead of
+// being built and thrown away one at a time. After a backoff a heartbeat probes
+// whether the webview is back; if so the buffer is replayed and a
+// `sink-recovered` summary follows. Once shutdown starts the sink discards
+// everything so late sidecar output can't disturb the exit path.
+
+use std::collections::VecDeque;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde_json::{json, Value};
+use tauri::Emitter;
+
+/// Consecutive failures before the sink is considered unhealthy.
+const FAILURE_THRESHOLD: u32 = 3;
+/// Events held while unhealthy; the oldest are dropped first.
+const BUFFER_CAPACITY: usize = 512;
+const BACKOFF_MIN: Duration = Duration::from_millis(250);
+const BACKOFF_MAX: Duration = Duration::from_secs(30);
+
+static SINK: Mutex<Option<EventSink>> = Mutex::new(None);
+static DISCARD: AtomicBool = AtomicBool::new(false);
+
+/// Where events end up: the webview in the app, anything else in tests.
+pub(crate) trait EmitTarget {
+    fn emit_event(&self, event: &Value) -> Result<(), String>;
+}
+
+impl EmitTarget for tauri::AppHandle {
+    fn emit_event(&self, event: &Value) -> Result<(), String> {
+        // Emitting into a window that is being torn down has been seen to panic
+        match catch_unwind(AssertUnwindSafe(|| self.emit("p2p-event", event))) {
+            Ok(result) => result.map_err(|e| e.to_string()),
+            Err(_) => Err("emit panicked".to_string()),
+        }
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct EventSink {
+    failures: u32,
+    backoff: Duration,
+    retry_at: Option<Instant>,
+    unhealthy_since: Option<Instant>,
+    buffer: VecDeque<Value>,
+    dropped: u64,
+}
+
+impl EventSink {
+    pub(crate) fn healthy(&self) -> bool {
+        self.unhealthy_since.is_none()
+    }
+
+    /// Deliver `event`, or hold it if the target is failing. Events always
+    /// reach the target in the order they were delivered.
+    pub(crate) fn deliver(&mut self, target: &impl EmitTarget, event: Value) {
+        if self.buffer.len() == BUFFER_CAPACITY {
+            self.buffer.pop_front();
+            self.dropped += 1;
+        }
+        self.buffer.push_back(event);
+
+        if self.retry_at.is_some_and(|at| Instant::now() < at) {
+            return;
+        }
+        if !self.healthy() {
+            if let Err(e) = target.emit_event(&json!({"type": "sink-heartbeat"})) {
+                self.failed(&e);
+                return;
+            }
+        }
+        self.flush(target);
+    }
+
+    fn flush(&mut self, target: &impl EmitTarget) {
+        let replayed = self.buffer.len();
+        while let Some(event) = self.buffer.front() {
+            if let Err(e) = target.emit_event(event) {
+                self.failed(&e);
+                return;
+            }
+            self.buffer.pop_front();
+            self.failures = 0;
+        }
+        self.retry_at = None;
+
+        if let Some(since) = self.unhealthy_since.take() {
+            eprintln!("Event sink recovered ({} dropped)", self.dropped);
+            let _ = target.emit_event(&json!({
+                "type": "sink-recovered",
+                "replayed": replayed,
+                "dropped": self.dropped,
+                "downForMs": since.elapsed().as_millis() as u64,
+            }));
+            self.dropped = 0;
+        }
+    }
+
+    fn failed(&mut self, error: &str) {
+        self.failures += 1;
+        if self.failures < FAILURE_THRESHOLD {
+            return;
+        }
+        if self.unhealthy_since.is_none() {
+            eprintln!("Event sink unhealthy: {}", error);
+            self.unhealthy_since = Some(Instant::now());
+            self.backoff = BACKOFF_MIN;
+        } else {
+            self.backoff = (self.backoff * 2).min(BACKOFF_MAX);
+        }
+        self.retry_at = Some(Instant::now() + self.backoff);
+    }
+}
+
+/// Send an event to the frontend through the shared sink.
+pub(crate) fn emit(app: &tauri::AppHandle, event: Value) {
+    if DISCARD.load(Ordering::SeqCst) {
+        return;
+    }
+    let mut guard = SINK.lock().unwrap_or_else(|e| e.into_inner());
+    guard
+        .get_or_insert_with(EventSink::default)
+        .deliver(app, event);
+}
+
+/// Drop all further events; called once shutdown has begun.
+pub(crate) fn discard() {
+    DISCARD.store(true, Ordering::SeqCst);
+    if let Some(sink) = SINK.lock().unwrap_or_else(|e| e.into_inner()).as_mut() {
+        sink.buffer.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::{Cell, RefCell};
+
+    #[derive(Default)]
+    struct FakeWebview {
+        down: Cell<bool>,
+        received: RefCell<Vec<Value>>,
+    }
+
+    impl EmitTarget for FakeWebview {
+        fn emit_event(&self, event: &Value) -> Result<(), String> {
+            if self.down.get() {
+                return Err("window destroyed".to_string());
+            }
+            self.received.borrow_mut().push(event.clone());
+            Ok(())
+        }
+    }
+
+    fn types(webview: &FakeWebview) -> Vec<String> {
+        webview
+            .received
+            .borrow()
+            .iter()
+            .map(|e| e["type"].as_str().unwrap_or_default().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn events_pass_straight_through_while_healthy() {
+        let webview = FakeWebview::default();
+        let mut sink = EventSink::default();
+        sink.deliver(&webview, json!({"type": "a"}));
+        sink.deliver(&webview, json!({"type": "b"}));
+        assert_eq!(types(&webview), ["a", "b"]);
+        assert!(sink.healthy());
+        assert!(sink.buffer.is_empty());
+    }
+
+    #[test]
+    fn held_events_are_replayed_in_order_after_recovery() {
+        let webview = FakeWebview::default();
+        let mut sink = EventSink::default();
+        webview.down.set(true);
+        for i in 0..FAILURE_THRESHOLD {
+            sink.deliver(&webview, json!({"type": format!("e{}", i)}));
+        }
+        assert!(!sink.healthy());
+        assert_eq!(sink.backoff, BACKOFF_MIN);
+
+        // Within the backoff nothing is attempted
+        webview.down.set(false);
+        sink.deliver(&webview, json!({"type": "e3"}));
+        assert!(webview.received.borrow().is_empty());
+
+        sink.retry_at = None;
+        sink.deliver(&webview, json!({"type": "e4"}));
+        assert_eq!(
+            types(&webview),
+            [
+                "sink-heartbeat",
+                "e0",
+                "e1",
+                "e2",
+                "e3",
+                "e4",
+                "sink-recovered"
+            ]
+        );
+        let summary = webview.received.borrow().last().cloned().unwrap();
+        assert_eq!(summary["replayed"], 5);
+        assert_eq!(summary["dropped"], 0);
+        assert!(sink.healthy());
+    }
+
+    #[test]
+    fn backoff_doubles_while_the_target_stays_down() {
+        let webview = FakeWebview::default();
+        let mut sink = EventSink::default();
+        webview.down.set(true);
+        for _ in 0..FAILURE_THRESHOLD {
+            sink.deliver(&webview, json!({"type": "x"}));
+        }
+        for expected in [BACKOFF_MIN * 2, BACKOFF_MIN * 4] {
+            sink.retry_at = None;
+            sink.deliver(&webview, json!({"type": "x"}));
+            assert_eq!(sink.backoff, expected);
+        }
+        sink.backoff = BACKOFF_MAX;
+        sink.retry_at = None;
+        sink.deliver(&webview, json!({"type": "x"}));
+        assert_eq!(sink.backoff, BACKOFF_MAX);
+    }
+
+    #[test]
+    fn the_oldest_held_events_are_dropped_at_capacity() {
+        let webview = FakeWebview::default();
+        let mut sink = EventSink::default();
+        webview.down.set(true);
+        for i in 0..BUFFER_CAPACITY + 2 {
+            sink.deliver(&webview, json!({"type": "x", "n": i}));
+        }
+        assert_eq!(sink.buffer.len(), BUFFER_CAPACITY);
+        assert_eq!(sink.dropped, 2);
+        assert_eq!(sink.buffer[0]["n"], 2);
+
+        webview.down.set(false);
+        sink.retry_at = None;
+        sink.deliver(&webview, json!({"type": "x"}));
+        let summary = webview.received.borrow().last().cloned().unwrap();
+        assert_eq!(summary["type"], "sink-recovered");
+        assert_eq!(summary["dropped"], 3);
+    }
+}
@@ -0,0 +1,297 @@
+// Storage health — keep running when the data directory is on a full or
+// read-only disk. The first write that fails that way (or the startup probe)
+// puts the bridge in degraded mode and emits `storage-degraded`. Persistent
+// state written through `write` (settings, sequence numbers, remembered
+// approvals) then stays in memory, and a retry thread probes the disk until
+// it can flush everything and emits `storage-recovered`.
+// Explicit file operations (identity, backups) still fail loudly.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde_json::json;
+
+const PROBE_FILE: &str = ".write-probe";
+const RETRY_INTERVAL: Duration = Duration::from_secs(15);
+/// Where diagnostics and logs go while the data directory is unusable.
+const FALLBACK_DIR: &str = "Concord";
+
+// Windows error codes for a write-protected or full volume
+const ERROR_WRITE_PROTECT: i32 = 19;
+const ERROR_HANDLE_DISK_FULL: i32 = 39;
+const ERROR_DISK_FULL: i32 = 112;
+
+static STATE: Mutex<Option<State>> = Mutex::new(None);
+static APP: Mutex<Option<tauri::AppHandle>> = Mutex::new(None);
+
+#[derive(Default)]
+struct State {
+    degraded: Option<Degraded>,
+    /// Latest contents of every file whose write was deferred.
+    pending: HashMap<PathBuf, Vec<u8>>,
+    retrying: bool,
+}
+
+struct Degraded {
+    reason: String,
+    since: Instant,
+}
+
+fn with_state<T>(f: impl FnOnce(&mut State) -> T) -> T {
+    let mut guard = STATE.lock().unwrap_or_else(|e| e.into_inner());
+    f(guard.get_or_insert_with(State::default))
+}
+
+/// Whether `e` means the volume is full or read-only, as opposed to a
+/// problem with one particular file.
+pub(crate) fn is_storage_failure(e: &io::Error) -> bool {
+    matches!(
+        e.raw_os_error(),
+        Some(ERROR_WRITE_PROTECT | ERROR_HANDLE_DISK_FULL | ERROR_DISK_FULL)
+    )
+}
+
+pub(crate) fn degraded_reason() -> Option<String> {
+    with_state(|s| s.degraded.as_ref().map(|d| d.reason.clone()))
+}
+
+/// A writable directory for diagnostics and logs: `preferred` normally, the
+/// temp directory while storage is degraded or `preferred` can't be used.
+pub(crate) fn writable_dir(preferred: Option<PathBuf>) -> PathBuf {
+    match preferred {
+        Some(dir) if degraded_reason().is_none() && fs::create_dir_all(&dir).is_ok() => dir,
+        _ => {
+            let dir = std::env::temp_dir().join(FALLBACK_DIR);
+            let _ = fs::create_dir_all(&dir);
+            dir
+        }
+    }
+}
+
+fn emit(event: serde_json::Value) {
+    let app = APP.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    if let Some(app) = app {
+        super::emit_p2p_event(&app, event);
+    }
+}
+
+fn degraded_event(reason: &str) -> serde_json::Value {
+    json!({"type": "storage-degraded", "reason": reason})
+}
+
+/// Enter degraded mode after `e` failed `context`. Does nothing for errors
+/// that aren't storage failures, or if already degraded.
+pub(crate) fn note_failure(context: &str, e: &io::Error) {
+    if !is_storage_failure(e) {
+        return;
+    }
+    let reason = format!("{}: {}", context, e);
+    let (entered, start_retry) = with_state(|s| {
+        if s.degraded.is_some() {
+            return (false, false);
+        }
+        s.degraded = Some(Degraded {
+            reason: reason.clone(),
+            since: Instant::now(),
+        });
+        (true, !std::mem::replace(&mut s.retrying, true))
+    });
+    if entered {
+        eprintln!("Storage degraded, persistence paused: {}", reason);
+        emit(degraded_event(&reason));
+    }
+    if start_retry {
+        thread::spawn(retry_loop);
+    }
+}
+
+/// Write `contents` to `path`, or keep them in memory while storage is
+/// degraded. Only storage failures are deferred; other errors are returned.
+pub(crate) fn write(path: &Path, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    let contents = contents.as_ref();
+    let deferred = with_state(|s| {
+        if s.degraded.is_some() {
+            s.pending.insert(path.to_path_buf(), contents.to_vec());
+        }
+        s.degraded.is_some()
+    });
+    if deferred {
+        return Ok(());
+    }
+    match fs::write(path, contents) {
+        Err(e) if is_storage_failure(&e) => {
+            note_failure(&format!("Cannot write {}", path.display()), &e);
+            with_state(|s| s.pending.insert(path.to_path_buf(), contents.to_vec()));
+            Ok(())
+        }
+        result => result,
+    }
+}
+
+/// Read a file written through `write`, including a deferred write.
+pub(crate) fn read_to_string(path: &Path) -> io::Result<String> {
+    let pending = with_state(|s| s.pending.get(path).cloned());
+    match pending {
+        Some(bytes) => {
+            String::from_utf8(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+        }
+        None => fs::read_to_string(path),
+    }
+}
+
+fn probe(dir: &Path) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    let probe = dir.join(PROBE_FILE);
+    fs::write(&probe, b"ok")?;
+    fs::remove_file(&probe)
+}
+
+/// Flush deferred writes once the disk takes writes again. Returns false if
+/// storage is still unusable.
+fn try_recover() -> bool {
+    if let Ok(root) = super::data_root() {
+        if probe(&root).is_err() {
+            return false;
+        }
+    }
+    let pending: Vec<(PathBuf, Vec<u8>)> = with_state(|s| s.pending.drain().collect());
+    let mut failed = Vec::new();
+    let mut flushed = 0;
+    for (path, contents) in pending {
+        match fs::write(&path, &contents) {
+            Ok(()) => flushed += 1,
+            Err(e) if is_storage_failure(&e) => failed.push((path, contents)),
+            Err(e) => eprintln!("Dropping deferred write to {}: {}", path.display(), e),
+        }
+    }
+    let down_for = with_state(|s| {
+        // Writes deferred meanwhile are newer than anything that failed
+        for (path, contents) in failed {
+            s.pending.entry(path).or_insert(contents);
+        }
+        if !s.pending.is_empty() {
+            return None;
+        }
+        s.retrying = false;
+        s.degraded.take().map(|d| d.since.elapsed())
+    });
+    let Some(down_for) = down_for else {
+        return false;
+    };
+    eprintln!("Storage recovered; flushed {} deferred writes", flushed);
+    emit(json!({
+        "type": "storage-recovered",
+        "flushed": flushed,
+        "downForMs": down_for.as_millis() as u64,
+    }));
+    true
+}
+
+fn retry_loop() {
+    loop {
+        thread::sleep(RETRY_INTERVAL);
+        if try_recover() {
+            return;
+        }
+    }
+}
+
+/// Probe the data directory at startup; a full or read-only disk puts the
+/// app in degraded mode instead of failing later, halfway through startup.
+pub(crate) fn init(app: &tauri::AppHandle) {
+    *APP.lock().unwrap_or_else(|e| e.into_inner()) = Some(app.clone());
+    let Some(base) = super::platform_data_dir() else {
+        return;
+    };
+    if let Err(e) = probe(&base.join("Concord")) {
+        note_failure("Data directory is not writable", &e);
+    }
+}
+
+/// Re-emit `storage-degraded` for a frontend that has just mounted.
+pub(crate) fn announce(app: &tauri::AppHandle) {
+    if let Some(reason) = degraded_reason() {
+        super::emit_p2p_event(app, degraded_event(&reason));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("concord-storage-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn only_full_or_read_only_volumes_count_as_storage_failures() {
+        for code in [ERROR_WRITE_PROTECT, ERROR_HANDLE_DISK_FULL, ERROR_DISK_FULL] {
+            assert!(is_storage_failure(&io::Error::from_raw_os_error(code)));
+        }
+        assert!(!is_storage_failure(&io::Error::from(
+            io::ErrorKind::NotFound
+        )));
+        assert!(!is_storage_failure(&io::Error::from(
+            io::ErrorKind::PermissionDenied
+        )));
+    }
+
+    #[test]
+    fn writes_go_to_disk_and_other_errors_are_returned() {
+        let dir = scratch("write");
+        let path = dir.join("settings.json");
+        write(&path, "{}").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "{}");
+        assert_eq!(read_to_string(&path).unwrap(), "{}");
+
+        let missing = dir.join("no-such-dir").join("x.json");
+        assert_eq!(
+            write(&missing, "{}").unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn deferred_writes_are_read_back_from_memory() {
+        let dir = scratch("deferred");
+        let path = dir.join("sequence.json");
+        with_state(|s| {
+            s.pending
+                .insert(path.clone(), b"{\"outbound\":{}}".to_vec());
+        });
+        assert_eq!(read_to_string(&path).unwrap(), "{\"outbound\":{}}");
+        assert!(!path.exists());
+        with_state(|s| s.pending.remove(&path));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn the_probe_leaves_nothing_behind() {
+        let dir = scratch("probe").join("nested");
+        probe(&dir).unwrap();
+        assert!(!dir.join(PROBE_FILE).exists());
+        let _ = fs::remove_dir_all(dir.parent().unwrap());
+    }
+
+    #[test]
+    fn unusable_preferred_dirs_fall_back_to_temp() {
+        let fallback = std::env::temp_dir().join(FALLBACK_DIR);
+        assert_eq!(writable_dir(None), fallback);
+
+        let dir = scratch("writable");
+        let file = dir.join("not-a-dir");
+        fs::write(&file, "").unwrap();
+        assert_eq!(writable_dir(Some(file)), fallback);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,145 @@
+// Sidecar supervision — when the sidecar exits on its own (crash, OOM,
+// node.exe killed by antivirus) it is started again after a backoff, up to
+// MAX_ATTEMPTS in a row; a run that lasted STABLE_AFTER starts the count
+// over. Every start bumps a generation and every deliberate stop
+// (`SidecarManager::kill`, `stop_gracefully`) bumps it again, so a stdout
+// reader that ends in a generation that is no longer current knows the exit
+// was wanted. Nothing is respawned once shutdown has begun.
+//
+// Progress is reported as `sidecar-status` events (`exited`, `restarting`,
+// `running`, `failed`, or `stopped` for deliberate stops), and a successful
+// respawn is followed by `sidecar-ready` so the frontend can dial its peers
+// again.
+
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Delay before each consecutive restart; the last one repeats.
+const BACKOFF: &[Duration] = &[
+    Duration::from_secs(1),
+    Duration::from_secs(2),
+    Duration::from_secs(5),
+    Duration::from_secs(15),
+    Duration::from_secs(30),
+];
+const MAX_ATTEMPTS: u32 = 8;
+/// A sidecar that ran this long before exiting was not crash-looping.
+const STABLE_AFTER: Duration = Duration::from_secs(60);
+/// How long an exiting sidecar gets to be reaped before it is killed.
+const REAP_TIMEOUT: Duration = Duration::from_secs(2);
+
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+/// Consecutive restarts without a stable run in between.
+static ATTEMPTS: AtomicU32 = AtomicU32::new(0);
+
+/// The current generation; `start_sidecar` hands it to its stdout reader.
+pub(crate) fn generation() -> u64 {
+    GENERATION.load(Ordering::SeqCst)
+}
+
+/// Mark the running sidecar's exit as deliberate. Call before stopping it.
+pub(crate) fn expect_exit() {
+    GENERATION.fetch_add(1, Ordering::SeqCst);
+}
+
+fn emit_status(app: &tauri::AppHandle, state: &str, fields: serde_json::Value) {
+    let mut event = serde_json::json!({"type": "sidecar-status", "state": state});
+    if let (Some(event), Some(fields)) = (event.as_object_mut(), fields.as_object()) {
+        event.extend(fields.clone());
+    }
+    super::emit_p2p_event(app, event);
+}
+
+/// Take the exited child of `generation` and return its exit code. None if
+/// a deliberate stop got there first.
+fn reap(app: &tauri::AppHandle, generation: u64) -> Option<Option<i32>> {
+    let mut child = super::sidecar::manager(app)
+        .detach_if(|| GENERATION.load(Ordering::SeqCst) == generation)?;
+    // stdout can close a moment before the process is gone
+    let deadline = Instant::now() + REAP_TIMEOUT;
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) => return Some(status.code()),
+            Ok(None) if Instant::now() < deadline => thread::sleep(Duration::from_millis(50)),
+            _ => {
+                let _ = child.kill();
+                return Some(child.wait().ok().and_then(|s| s.code()));
+            }
+        }
+    }
+}
+
+/// Called by the stdout reader of `generation` when the sidecar's stdout
+/// ends. Restarts the sidecar unless the exit was deliberate; blocks the
+/// reader thread through the backoff.
+pub(crate) fn reader_ended(app: &tauri::AppHandle, mut generation: u64, started: Instant) {
+    let Some(exit_code) = reap(app, generation) else {
+        emit_status(app, "stopped", serde_json::json!({}));
+        return;
+    };
+    let exited = serde_json::json!({"type": "error", "message": "Sidecar process exited"});
+    super::announce::observe_event(app, &exited);
+    super::emit_p2p_event(app, exited);
+    emit_status(app, "exited", serde_json::json!({"exitCode": exit_code}));
+    if started.elapsed() >= STABLE_AFTER {
+        ATTEMPTS.store(0, Ordering::SeqCst);
+    }
+
+    loop {
+        if super::shutdown::in_progress() {
+            return;
+        }
+        let attempt = ATTEMPTS.fetch_add(1, Ordering::SeqCst) + 1;
+        if attempt > MAX_ATTEMPTS {
+            // The next deliberate start gets a fresh budget
+            ATTEMPTS.store(0, Ordering::SeqCst);
+            super::outbox::mark_down(app);
+            emit_status(app, "failed", serde_json::json!({"attempts": MAX_ATTEMPTS}));
+            super::emit_p2p_event(
+                app,
+                serde_json::json!({
+                    "type": "error",
+                    "message": format!("Sidecar keeps exiting; gave up after {} restarts", MAX_ATTEMPTS),
+                }),
+            );
+            return;
+        }
+        let delay = BACKOFF[(attempt as usize - 1).min(BACKOFF.len() - 1)];
+        emit_status(
+            app,
+            "restarting",
+            serde_json::json!({"attempt": attempt, "retryInMs": delay.as_millis() as u64}),
+        );
+        thread::sleep(delay);
+        // Checked under the start lock: restarted, stopped or shut down by
+        // someone else in the meantime
+        let sidecar = super::sidecar::manager(app);
+        let start = sidecar.lock_start();
+        if GENERATION.load(Ordering::SeqCst) != generation || super::shutdown::in_progress() {
+            return;
+        }
+        let result = super::start_sidecar_holding(app.clone(), sidecar.incognito(), &start);
+        drop(start);
+        match result {
+            Ok(()) => {
+                if super::shutdown::in_progress() {
+                    super::sidecar::manager(app).kill();
+                    return;
+                }
+                emit_status(app, "running", serde_json::json!({"attempt": attempt}));
+                super::emit_p2p_event(app, serde_json::json!({"type": "sidecar-ready"}));
+                return;
+            }
+            Err(e) => {
+                eprintln!("Sidecar restart {} failed: {}", attempt, e);
+                super::emit_p2p_event(
+                    app,
+                    serde_json::json!({"type": "error", "message": format!("Sidecar restart failed: {}", e)}),
+                );
+                // start_sidecar stopped the old generation before failing
+                generation = GENERATION.load(Ordering::SeqCst);
+            }
+        }
+    }
+}
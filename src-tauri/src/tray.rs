@@ -0,0 +1,211 @@
+// Tray icon — Show and Quit, plus the five most recently active
+// conversations with their unread counts. Picking a conversation brings the
+// window back and emits `navigate-to-conversation`. Counts follow the
+// title's unread count: muted conversations and focus sessions don't add
+// to them, and they clear when the window gets focus. With
+// `privacy.trayNames` off the menu shows counts, not conversation names.
+// The menu is rebuilt at most every REBUILD_INTERVAL, on the main thread.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use tauri::menu::{Menu, MenuEvent, MenuItem, PredefinedMenuItem};
+use tauri::tray::TrayIconBuilder;
+use tauri::Manager;
+
+const TRAY_ID: &str = "main";
+const MAIN_WINDOW: &str = "main";
+const MAX_LISTED: usize = 5;
+/// Conversations remembered for recency; only the first MAX_LISTED show.
+const MAX_TRACKED: usize = 50;
+const MAX_NAME_CHARS: usize = 32;
+const REBUILD_INTERVAL: Duration = Duration::from_secs(2);
+const CONVERSATION_ITEM: &str = "conversation:";
+const SHOW_ITEM: &str = "show";
+const QUIT_ITEM: &str = "quit";
+
+/// Conversations by latest message, most recent first.
+static ACTIVITY: Mutex<Vec<Conversation>> = Mutex::new(Vec::new());
+static DIRTY: AtomicBool = AtomicBool::new(false);
+/// The current menu and the one it replaced. The replaced one lives until
+/// the next rebuild, so a menu that is open when it is swapped out stays
+/// valid.
+static MENUS: Mutex<Vec<Menu<tauri::Wry>>> = Mutex::new(Vec::new());
+
+struct Conversation {
+    id: String,
+    unread: u32,
+}
+
+/// Called by the attention tracker for every message; `unread` when it
+/// counts towards the unread title.
+pub(crate) fn observe_message(conversation_id: &str, unread: bool) {
+    let mut activity = ACTIVITY.lock().unwrap_or_else(|e| e.into_inner());
+    let mut conversation = match activity.iter().position(|c| c.id == conversation_id) {
+        Some(index) => activity.remove(index),
+        None => Conversation {
+            id: conversation_id.to_string(),
+            unread: 0,
+        },
+    };
+    if unread {
+        conversation.unread += 1;
+    }
+    activity.insert(0, conversation);
+    activity.truncate(MAX_TRACKED);
+    DIRTY.store(true, Ordering::SeqCst);
+}
+
+/// Focus returned to the window; everything counts as read.
+pub(crate) fn clear_unread() {
+    let mut activity = ACTIVITY.lock().unwrap_or_else(|e| e.into_inner());
+    if activity.iter().any(|c| c.unread > 0) {
+        activity.iter_mut().for_each(|c| c.unread = 0);
+        DIRTY.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Relabel the menu when `privacy.trayNames` changes. A settings subscriber.
+pub(crate) fn settings_changed(_app: &tauri::AppHandle) {
+    DIRTY.store(true, Ordering::SeqCst);
+}
+
+fn truncate(name: &str) -> String {
+    if name.chars().count() <= MAX_NAME_CHARS {
+        return name.to_string();
+    }
+    let mut short: String = name.chars().take(MAX_NAME_CHARS - 1).collect();
+    short.push('…');
+    short
+}
+
+fn label(index: usize, conversation: &Conversation, show_names: bool) -> String {
+    let name = if show_names {
+        truncate(&conversation.id)
+    } else {
+        format!("Conversation {}", index + 1)
+    };
+    match conversation.unread {
+        0 => name,
+        n => format!("{} ({})", name, n),
+    }
+}
+
+fn build_menu(app: &tauri::AppHandle) -> tauri::Result<Menu<tauri::Wry>> {
+    let show_names = super::settings::current().privacy.tray_names;
+    let menu = Menu::new(app)?;
+    let listed: Vec<(String, String)> = ACTIVITY
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .take(MAX_LISTED)
+        .enumerate()
+        .map(|(i, c)| {
+            (
+                format!("{}{}", CONVERSATION_ITEM, c.id),
+                label(i, c, show_names),
+            )
+        })
+        .collect();
+    for (id, text) in &listed {
+        menu.append(&MenuItem::with_id(app, id, text, true, None::<&str>)?)?;
+    }
+    if !listed.is_empty() {
+        menu.append(&PredefinedMenuItem::separator(app)?)?;
+    }
+    menu.append(&MenuItem::with_id(
+        app,
+        SHOW_ITEM,
+        "Show Concord",
+        true,
+        None::<&str>,
+    )?)?;
+    menu.append(&MenuItem::with_id(
+        app,
+        QUIT_ITEM,
+        "Quit",
+        true,
+        None::<&str>,
+    )?)?;
+    Ok(menu)
+}
+
+fn rebuild(app: &tauri::AppHandle) {
+    let Some(tray) = app.tray_by_id(TRAY_ID) else {
+        return;
+    };
+    let menu = match build_menu(app) {
+        Ok(menu) => menu,
+        Err(e) => {
+            eprintln!("Cannot build tray menu: {}", e);
+            return;
+        }
+    };
+    if let Err(e) = tray.set_menu(Some(menu.clone())) {
+        eprintln!("Cannot update tray menu: {}", e);
+        return;
+    }
+    retain(menu);
+}
+
+fn retain(menu: Menu<tauri::Wry>) {
+    let mut menus = MENUS.lock().unwrap_or_else(|e| e.into_inner());
+    menus.push(menu);
+    if menus.len() > 2 {
+        menus.remove(0);
+    }
+}
+
+fn show_window(app: &tauri::AppHandle) {
+    if let Some(window) = app.get_webview_window(MAIN_WINDOW) {
+        let _ = window.unminimize();
+        let _ = window.show();
+        let _ = window.set_focus();
+    }
+}
+
+fn on_menu_event(app: &tauri::AppHandle, event: MenuEvent) {
+    match event.id().as_ref() {
+        SHOW_ITEM => show_window(app),
+        QUIT_ITEM => app.exit(0),
+        id => {
+            let Some(conversation) = id.strip_prefix(CONVERSATION_ITEM) else {
+                return;
+            };
+            show_window(app);
+            super::emit_p2p_event(
+                app,
+                serde_json::json!({
+                    "type": "navigate-to-conversation",
+                    "conversationId": conversation,
+                }),
+            );
+        }
+    }
+}
+
+/// Create the tray icon and start the throttled menu rebuilds.
+pub(crate) fn init(app: &tauri::AppHandle) -> tauri::Result<()> {
+    let menu = build_menu(app)?;
+    let mut builder = TrayIconBuilder::with_id(TRAY_ID)
+        .tooltip("Concord")
+        .menu(&menu)
+        .on_menu_event(on_menu_event);
+    if let Some(icon) = app.default_window_icon() {
+        builder = builder.icon(icon.clone());
+    }
+    builder.build(app)?;
+    retain(menu);
+
+    let app = app.clone();
+    thread::spawn(move || loop {
+        thread::sleep(REBUILD_INTERVAL);
+        if DIRTY.swap(false, Ordering::SeqCst) {
+            let handle = app.clone();
+            let _ = app.run_on_main_thread(move || rebuild(&handle));
+        }
+    });
+    Ok(())
+}
@@ -0,0 +1,156 @@
+// Sidecar working directory — the sidecar runs in `sidecar-cwd/<pid>` under
+// the profile's data dir rather than the project root or the exe directory
+// (Program Files, where writes fail silently). Whatever it or its
+// dependencies drop into `./` stays contained. Directories left behind by
+// earlier sessions are removed at the next start. The sidecar resolves its
+// own files from the script path, so it doesn't depend on the cwd.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use super::error::BridgeError;
+
+const WORKDIR: &str = "sidecar-cwd";
+/// A session directory larger than this is emptied on the next sidecar start.
+const MAX_SESSION_BYTES: u64 = 64 * 1024 * 1024;
+/// Files older than this are removed from a session directory on restart.
+const MAX_FILE_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+fn root() -> Result<PathBuf, BridgeError> {
+    Ok(super::app_data_dir()?.join(WORKDIR))
+}
+
+fn remove(path: &Path) {
+    let result = if path.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    };
+    if let Err(e) = result {
+        eprintln!("Cannot remove {}: {}", path.display(), e);
+    }
+}
+
+fn size_of(path: &Path) -> u64 {
+    match fs::symlink_metadata(path) {
+        Ok(m) if m.is_dir() => fs::read_dir(path)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|e| size_of(&e.path()))
+            .sum(),
+        Ok(m) => m.len(),
+        Err(_) => 0,
+    }
+}
+
+fn is_stale(path: &Path) -> bool {
+    fs::symlink_metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| SystemTime::now().duration_since(t).ok())
+        .is_some_and(|age| age > MAX_FILE_AGE)
+}
+
+/// Create this session's working directory and clean up around it: other
+/// sessions' directories go unless their process is still running, and this
+/// session's own directory is trimmed when a restart finds it old or large.
+pub(crate) fn prepare() -> Result<PathBuf, BridgeError> {
+    prepare_in(&root()?, std::process::id())
+}
+
+fn prepare_in(root: &Path, own: u32) -> Result<PathBuf, BridgeError> {
+    for entry in fs::read_dir(root).into_iter().flatten().flatten() {
+        let pid = entry
+            .file_name()
+            .to_str()
+            .and_then(|n| n.parse::<u32>().ok());
+        match pid {
+            Some(pid) if pid == own || super::shutdown::process_alive(pid) => {}
+            _ => remove(&entry.path()),
+        }
+    }
+
+    let dir = root.join(own.to_string());
+    if size_of(&dir) > MAX_SESSION_BYTES {
+        remove(&dir);
+    } else {
+        for entry in fs::read_dir(&dir).into_iter().flatten().flatten() {
+            if is_stale(&entry.path()) {
+                remove(&entry.path());
+            }
+        }
+    }
+    fs::create_dir_all(&dir)
+        .map_err(|e| BridgeError::io("Cannot create sidecar working directory", e))?;
+    Ok(dir)
+}
+
+/// Remove this session's directory; part of the shutdown routine.
+pub(crate) fn remove_own() {
+    let Ok(root) = root() else {
+        return;
+    };
+    let dir = root.join(std::process::id().to_string());
+    if dir.exists() {
+        remove(&dir);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+
+    fn scratch(name: &str) -> PathBuf {
+        let root =
+            std::env::temp_dir().join(format!("concord-workdir-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        root
+    }
+
+    #[test]
+    fn other_sessions_and_stray_files_are_removed() {
+        let root = scratch("others");
+        // Not a live process on any platform the tests run on
+        fs::create_dir_all(root.join("4294967295")).unwrap();
+        fs::write(root.join("stray.log"), "x").unwrap();
+
+        let dir = prepare_in(&root, 42).unwrap();
+        assert_eq!(dir, root.join("42"));
+        assert!(dir.is_dir());
+        let left: Vec<_> = fs::read_dir(&root).unwrap().flatten().collect();
+        assert_eq!(left.len(), 1);
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn a_restart_trims_only_stale_files() {
+        let root = scratch("stale");
+        let dir = root.join("42");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("fresh.txt"), "x").unwrap();
+        let old = File::create(dir.join("old.txt")).unwrap();
+        old.set_modified(SystemTime::now() - MAX_FILE_AGE - Duration::from_secs(60))
+            .unwrap();
+        drop(old);
+
+        prepare_in(&root, 42).unwrap();
+        assert!(dir.join("fresh.txt").exists());
+        assert!(!dir.join("old.txt").exists());
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn size_counts_nested_files() {
+        let root = scratch("size");
+        fs::create_dir_all(root.join("a").join("b")).unwrap();
+        fs::write(root.join("one"), [0u8; 10]).unwrap();
+        fs::write(root.join("a").join("b").join("two"), [0u8; 5]).unwrap();
+        assert_eq!(size_of(&root), 15);
+        assert_eq!(size_of(&root.join("missing")), 0);
+        let _ = fs::remove_dir_all(&root);
+    }
+}
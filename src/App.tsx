@@ -14,6 +14,9 @@ import { getSidecarLog } from './services/p2pBridge';
 import { useSpaceStore } from './stores/spaceStore';
 import { useIncognitoStore } from './stores/incognitoStore';
 
+/** Lines of the sidecar log shown in the debug panel. */
+const SIDECAR_LOG_LINES = 500;
+
 export default function App() {
   const [view, setView] = useState<'connect' | 'chat'>('connect');
   const [centerTab, setCenterTab] = useState<'chat' | 'network'>('chat');
@@ -51,10 +54,10 @@ export default function App() {
 
   useEffect(() => {
     const interval = setInterval(async () => {
-      const log = await getSidecarLog();
+      const log = await getSidecarLog(SIDECAR_LOG_LINES);
       if (log) setSidecarLog(log);
     }, 3000);
-    getSidecarLog().then((log) => {
+    getSidecarLog(SIDECAR_LOG_LINES).then((log) => {
       if (log) setSidecarLog(log);
     });
     return () => clearInterval(interval);
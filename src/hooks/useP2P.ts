@@ -12,6 +12,7 @@ import {
   dialPeer as bridgeDial,
   listenP2PEvents,
   restartP2P as bridgeRestart,
+  errorMessage,
   type P2PEvent,
 } from '../services/p2pBridge';
 import { initIdentity, sign } from '../services/identity';
@@ -98,7 +99,7 @@ export function useP2P(channelId: string = DEFAULT_CHANNEL) {
 
         log('Listening for sidecar events (sidecar auto-started by backend)...');
       } catch (e) {
-        const msg = e instanceof Error ? e.message : String(e);
+        const msg = errorMessage(e);
         setError(msg);
         setStatus('error');
         log(`Init error: ${msg}`);
@@ -355,7 +356,7 @@ export function useP2P(channelId: string = DEFAULT_CHANNEL) {
       try {
         await bridgeRestart(incognito);
       } catch (e) {
-        const msg = e instanceof Error ? e.message : String(e);
+        const msg = errorMessage(e);
         setError(msg);
         setStatus('error');
         log(`Restart failed: ${msg}`);
@@ -387,7 +388,7 @@ export function useP2P(channelId: string = DEFAULT_CHANNEL) {
         userDialsRef.current.add(trimmed);
         await bridgeDial(trimmed);
       } catch (e) {
-        const msg = e instanceof Error ? e.message : String(e);
+        const msg = errorMessage(e);
         setError(msg);
         log(`Dial invoke error: ${msg}`);
       }
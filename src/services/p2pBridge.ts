@@ -29,15 +29,32 @@ export interface P2PInviteCodeEvent {
 
 export interface P2PMessageEvent {
   type: 'message';
+  /** Bridge message id; synthetic (`syn-…`) for peers that don't send one. */
+  id: string;
   channelId: string;
   data: string;
   from: string;
+  /** Sender's per-conversation sequence number (absent from older peers). */
+  seq?: number;
+  /** True for a targeted send, false for a channel broadcast. */
+  direct?: boolean;
+  /** Time claimed by the sender (ms); may be wrong if their clock is off. */
+  senderTime: number | null;
+  /** Bridge wall-clock receive time (ms). Sort by this, then `receiveSeq`. */
+  receivedAt: number;
+  receiveSeq: number;
+  /** receivedAt − senderTime; `clockSkewed` when beyond the bridge threshold. */
+  clockSkewMs: number | null;
+  clockSkewed: boolean;
+  /** How to draw the sender; same in every window. */
+  presentation?: PresentationHint;
 }
 
 export interface P2PPeerEvent {
   type: 'peer:connect' | 'peer:disconnect';
   peerId: string;
   peers: string[];
+  presentation?: PresentationHint;
 }
 
 export interface P2PDialResultEvent {
@@ -47,6 +64,8 @@ export interface P2PDialResultEvent {
   peerId?: string;
   error?: string;
   peers?: string[];
+  /** The command this answers; `dialPeer` already resolves with it. */
+  requestId?: string;
 }
 
 export interface P2PErrorEvent {
@@ -59,6 +78,282 @@ export interface P2PLogEvent {
   message: string;
 }
 
+/** A sender skipped sequence numbers; messages may have been lost. */
+export interface P2PPossibleMessageLossEvent {
+  type: 'possible-message-loss';
+  from: string;
+  channelId: string;
+  direct: boolean;
+  expected: number;
+  received: number;
+  missing: number;
+}
+
+export interface LinkPreview {
+  url: string;
+  finalUrl: string;
+  title: string | null;
+  description: string | null;
+  siteName: string | null;
+  /** `data:` URL; previews never make the webview contact the linked host. */
+  image: string | null;
+}
+
+/** Follow-up to a `message` when `privacy.linkPreviews` allows a preview. */
+export interface P2PPreviewReadyEvent {
+  type: 'preview-ready';
+  channelId: string;
+  from: string | null;
+  url: string;
+  preview: LinkPreview;
+}
+
+/** The webview missed events (e.g. during a reload); held ones were replayed. */
+export interface P2PSinkRecoveredEvent {
+  type: 'sink-recovered';
+  replayed: number;
+  dropped: number;
+  downForMs: number;
+}
+
+/** Dev only: the sidecar script changed and the sidecar was restarted. */
+export interface P2PSidecarHotReloadedEvent {
+  type: 'sidecar-hot-reloaded';
+  paths: string[];
+}
+
+/** Dev only, with `developer.autoRestart` off: offer `restartP2P`. */
+export interface P2PSidecarScriptChangedEvent {
+  type: 'sidecar-script-changed';
+  paths: string[];
+}
+
+/**
+ * Sidecar supervision. `exited` is an unexpected exit, followed by
+ * `restarting` with a backoff and then `running` (and a `sidecar-ready`
+ * event) or, after repeated failures, `failed`. `stopped` is a deliberate
+ * stop such as a restart or profile switch.
+ */
+export interface P2PSidecarStatusEvent {
+  type: 'sidecar-status';
+  state: 'exited' | 'restarting' | 'running' | 'failed' | 'stopped';
+  exitCode?: number | null;
+  attempt?: number;
+  retryInMs?: number;
+  attempts?: number;
+}
+
+/** The sidecar came back after a crash; dial peers again. */
+export interface P2PSidecarReadyEvent {
+  type: 'sidecar-ready';
+}
+
+/** `restartSidecar` is stopping the sidecar. */
+export interface P2PSidecarRestartingEvent {
+  type: 'sidecar-restarting';
+}
+
+/** `restartSidecar` finished; the new process is running. */
+export interface P2PSidecarStartedEvent {
+  type: 'sidecar-started';
+  pid: number | null;
+}
+
+/** Progress of an outgoing file transfer (`sendFile`). */
+export interface P2PFileProgressEvent {
+  type: 'file-progress';
+  transferId: string;
+  sent: number;
+  total: number;
+}
+
+/** A peer's file transfer: an offer, then chunks (base64 `data`, by
+ *  `index`), then `file_complete` with a `crc32:` checksum of the content,
+ *  or `file_abort`. Reassembly is up to the receiver. */
+export interface P2PFileTransferEvent {
+  type: 'file_offer' | 'file_chunk' | 'file_complete' | 'file_abort';
+  from: string;
+  transferId: string;
+  channelId: string;
+  direct: boolean;
+  name?: string;
+  size?: number;
+  chunks?: number;
+  index?: number;
+  data?: string;
+  checksum?: string;
+}
+
+/** A line the sidecar wrote to stderr. Capped at 20 events a second;
+ *  `suppressed` counts the lines skipped since the last event (they are
+ *  still in the log). */
+export interface P2PStderrEvent {
+  type: 'stderr';
+  message: string;
+  suppressed?: number;
+}
+
+/** Commands queued while the sidecar was starting were written to it. */
+export interface P2PQueueFlushedEvent {
+  type: 'queue-flushed';
+  count: number;
+}
+
+/** Queued commands were discarded: they waited too long, or the sidecar
+ *  failed to start. `messageIds` lists the sends among them. */
+export interface P2PQueueDroppedEvent {
+  type: 'queue-dropped';
+  reason: 'expired' | 'sidecar-failed';
+  messageIds: string[];
+}
+
+/** A peer is flooding; its messages are now held and released in batches. */
+export interface P2PPeerFlaggedFloodingEvent {
+  type: 'peer-flagged-flooding';
+  peerId: string;
+  received: number;
+  rateViolations: number;
+  repeatViolations: number;
+  oversized: number;
+  queued: number;
+  dropped: number;
+}
+
+export interface P2PPeerFloodingEndedEvent {
+  type: 'peer-flooding-ended';
+  peerId: string;
+  /** Held messages discarded because the per-peer queue was full. */
+  dropped: number;
+}
+
+/** A sensitive operation is waiting for the user; answer with `resolveApproval`. */
+export interface ApprovalRequest {
+  nonce: string;
+  kind: string;
+  scope: string | null;
+  details: unknown;
+  /** Whether "remember my choice" may be offered. */
+  rememberable: boolean;
+  requestedAt: number;
+  expiresInSecs: number;
+}
+
+export interface P2PApprovalRequestedEvent extends ApprovalRequest {
+  type: 'approval-requested';
+}
+
+export interface P2PApprovalExpiredEvent {
+  type: 'approval-expired';
+  nonce: string;
+}
+
+/** One raw protocol line, streamed while the console is subscribed. */
+export interface P2PProtocolTraceEvent {
+  type: 'protocol-trace';
+  direction: 'in' | 'out';
+  at: number;
+  /** Sensitive values masked, cut to the per-line cap. */
+  line: string;
+  /** Full length before truncation, in characters. */
+  length: number;
+  truncated: boolean;
+  /** Written through `consoleSendRaw`. */
+  injected: boolean;
+}
+
+/** The data directory's disk is full or read-only; changes are kept in memory. */
+export interface P2PStorageDegradedEvent {
+  type: 'storage-degraded';
+  reason: string;
+}
+
+export interface P2PStorageRecoveredEvent {
+  type: 'storage-recovered';
+  /** Deferred writes persisted on recovery. */
+  flushed: number;
+  downForMs: number;
+}
+
+/** A timed mute ran out; the conversation notifies again. */
+export interface P2PMuteExpiredEvent {
+  type: 'mute-expired';
+  conversationId: string;
+}
+
+/** A DM from an unknown peer was held back as a message request. */
+export interface P2PMessageRequestEvent {
+  type: 'message-request';
+  peerId: string;
+  /** Messages now held for this peer. */
+  held: number;
+  /** Whether this is the peer's first held message. */
+  first: boolean;
+}
+
+export interface P2PMessageRequestResolvedEvent {
+  type: 'message-request-resolved';
+  peerId: string;
+  response: MessageRequestResponse;
+}
+
+export interface FocusMention {
+  conversationId: string;
+  from: string;
+  messageId: string;
+  receivedAt: number;
+}
+
+/** What arrived during a focus session; sent when it ends. */
+export interface P2PFocusDigestEvent {
+  type: 'focus-digest';
+  startedAt: number;
+  endedAt: number;
+  /** Ended with `endFocusSession` rather than running out. */
+  endedEarly: boolean;
+  total: number;
+  /** Busiest conversation first. */
+  conversations: { conversationId: string; count: number }[];
+  mentions: FocusMention[];
+}
+
+/** A presentation override changed; redraw `hint.id`. */
+export interface P2PPresentationChangedEvent {
+  type: 'presentation-changed';
+  hint: PresentationHint;
+}
+
+export type OperationState = 'queued' | 'running' | 'succeeded' | 'failed' | 'cancelled';
+
+/** A long-running operation (backup, restore, connectivity check). */
+export interface Operation {
+  id: string;
+  /** `backup` (create, restore, scheduled) or `connectivity-check`. */
+  kind: string;
+  description: string;
+  state: OperationState;
+  phase: string | null;
+  done: number;
+  total: number;
+  /** Kind-specific detail of the latest step. */
+  detail: unknown;
+  startedAt: number;
+  finishedAt: number | null;
+  /** The error a failed operation ended with. */
+  error: BridgeError | null;
+  cancelRequested: boolean;
+}
+
+/** Any change to an operation, from queued to finished. */
+export interface P2POperationProgressEvent extends Operation {
+  type: 'operation-progress';
+}
+
+/** A conversation was picked from the tray menu; the window is already shown. */
+export interface P2PNavigateToConversationEvent {
+  type: 'navigate-to-conversation';
+  conversationId: string;
+}
+
 export type P2PEvent =
   | P2PReadyEvent
   | P2PInviteCodeEvent
@@ -66,7 +361,69 @@ export type P2PEvent =
   | P2PPeerEvent
   | P2PDialResultEvent
   | P2PErrorEvent
-  | P2PLogEvent;
+  | P2PLogEvent
+  | P2PPreviewReadyEvent
+  | P2PPossibleMessageLossEvent
+  | P2PSinkRecoveredEvent
+  | P2PSidecarHotReloadedEvent
+  | P2PSidecarScriptChangedEvent
+  | P2PSidecarStatusEvent
+  | P2PSidecarReadyEvent
+  | P2PSidecarRestartingEvent
+  | P2PSidecarStartedEvent
+  | P2PStderrEvent
+  | P2PFileProgressEvent
+  | P2PFileTransferEvent
+  | P2PQueueFlushedEvent
+  | P2PQueueDroppedEvent
+  | P2PPeerFlaggedFloodingEvent
+  | P2PPeerFloodingEndedEvent
+  | P2PApprovalRequestedEvent
+  | P2PApprovalExpiredEvent
+  | P2PProtocolTraceEvent
+  | P2PStorageDegradedEvent
+  | P2PStorageRecoveredEvent
+  | P2PMuteExpiredEvent
+  | P2PMessageRequestEvent
+  | P2PMessageRequestResolvedEvent
+  | P2PFocusDigestEvent
+  | P2PPresentationChangedEvent
+  | P2POperationProgressEvent
+  | P2PNavigateToConversationEvent;
+
+// ── Errors ───────────────────────────────────────────────────────
+
+/** What every command rejects with. Match on `code`; `message` may be reworded. */
+export interface BridgeError {
+  code:
+    | 'sidecar-not-running'
+    | 'timeout'
+    | 'invalid-address'
+    | 'not-ready'
+    | 'io'
+    | 'invalid-argument'
+    | 'not-found'
+    | 'approval-denied'
+    | 'cancelled'
+    | 'policy-violation'
+    | 'dial-failed'
+    | 'queue-full'
+    | 'payload-too-large'
+    | 'rate-limited'
+    | 'unclassified';
+  message: string;
+  details: Record<string, unknown> | null;
+}
+
+export function isBridgeError(e: unknown): e is BridgeError {
+  return typeof e === 'object' && e !== null && 'code' in e && 'message' in e;
+}
+
+/** Human-readable text for anything a command (or other code) threw. */
+export function errorMessage(e: unknown): string {
+  if (isBridgeError(e)) return e.message;
+  return e instanceof Error ? e.message : String(e);
+}
 
 // ── Commands ─────────────────────────────────────────────────────
 
@@ -75,16 +432,80 @@ export async function startP2P(): Promise<void> {
   await invoke('start_p2p');
 }
 
-/** Send a message to a channel.
+/** Send a message to a channel. Resolves with the bridge message id.
  *  If targetPeerId is provided, send only to that peer (DM).
- *  Otherwise broadcast to all connected peers. */
-export async function sendMessage(channelId: string, data: string, targetPeerId?: string): Promise<void> {
-  await invoke('p2p_send', { channelId, data, targetPeerId: targetPeerId ?? null });
+ *  Otherwise broadcast to all connected peers. While the sidecar is
+ *  starting the message is queued (see `queue-flushed` / `queue-dropped`);
+ *  rejects with `queue-full` if it could not be. */
+export async function sendMessage(channelId: string, data: string, targetPeerId?: string): Promise<string> {
+  return invoke<string>('p2p_send', { channelId, data, targetPeerId: targetPeerId ?? null });
+}
+
+export interface DialResult {
+  address: string;
+  /** The dialled peer, for invite codes. */
+  peerId: string | null;
+  /** Connected peers after the dial. */
+  peers: string[];
+}
+
+/**
+ * Dial a remote peer address and wait for the outcome. Rejects with
+ * `dial-failed` (`details.address`) when the peer can't be reached, or
+ * `timeout` after `sidecar.dialTimeoutSecs` (15s by default).
+ */
+export async function dialPeer(address: string): Promise<DialResult> {
+  return invoke<DialResult>('p2p_dial', { address });
+}
+
+export interface FileSent {
+  transferId: string;
+  name: string;
+  size: number;
+  /** `crc32:` and the CRC-32 of the content in hex. */
+  checksum: string;
+}
+
+/**
+ * Send a file (up to 100 MB) to a channel, or only to targetPeerId.
+ * Resolves once every chunk is handed to the sidecar; progress arrives as
+ * `file-progress` events, the first of which carries the transfer id.
+ * Rejects with `payload-too-large` for bigger files, with
+ * `policy-violation` when the channel's send policy forbids the file, and
+ * with `cancelled` after `cancelTransfer`.
+ */
+export async function sendFile(channelId: string, filePath: string, targetPeerId?: string): Promise<FileSent> {
+  return invoke<FileSent>('p2p_send_file', { channelId, filePath, targetPeerId: targetPeerId ?? null });
+}
+
+export interface StoredMessage {
+  id: number;
+  /** The bridge message id, when the message had one. */
+  messageId: string | null;
+  channelId: string;
+  /** Sender of inbound messages, recipient of outbound DMs. */
+  peerId: string | null;
+  direction: 'inbound' | 'outbound';
+  /** When the bridge saw it (ms since epoch). */
+  timestamp: number;
+  body: string;
+}
+
+/** Stored messages of a channel before `before` (default: now), oldest
+ *  first; at most `limit` (capped at 500). Page back by passing the oldest
+ *  timestamp seen. */
+export async function getMessageHistory(channelId: string, limit: number, before?: number): Promise<StoredMessage[]> {
+  return invoke<StoredMessage[]>('get_message_history', { channelId, before: before ?? null, limit });
+}
+
+/** Stored messages containing `query`, newest first (at most 200). */
+export async function searchMessages(query: string, channelId?: string): Promise<StoredMessage[]> {
+  return invoke<StoredMessage[]>('search_messages', { query, channelId: channelId ?? null });
 }
 
-/** Tell the sidecar to dial a remote peer address. */
-export async function dialPeer(address: string): Promise<void> {
-  await invoke('p2p_dial', { address });
+/** Stop a running file transfer; the receivers get `file_abort`. */
+export async function cancelTransfer(transferId: string): Promise<void> {
+  await invoke('p2p_cancel_transfer', { transferId });
 }
 
 /** Restart the sidecar, optionally in incognito mode (ephemeral identity). */
@@ -92,15 +513,542 @@ export async function restartP2P(incognito: boolean): Promise<void> {
   await invoke('restart_p2p', { incognito });
 }
 
-/** Read the sidecar's stderr log file. */
-export async function getSidecarLog(): Promise<string> {
+export interface SidecarStatus {
+  running: boolean;
+  pid: number | null;
+  /** When the current process was spawned (ms since epoch). */
+  startedAt: number | null;
+  /** Starts after the first this session, automatic restarts included. */
+  restartCount: number;
+  /** A start or restart is under way. */
+  restarting: boolean;
+  incognito: boolean;
+  script: string | null;
+  node: string | null;
+}
+
+/**
+ * Stop the sidecar cleanly and start it again in the same mode, e.g. when
+ * it hangs. Rejects with `not-ready` while another start or restart is
+ * running.
+ */
+export async function restartSidecar(): Promise<SidecarStatus> {
+  return invoke<SidecarStatus>('p2p_restart_sidecar');
+}
+
+export async function getSidecarStatus(): Promise<SidecarStatus> {
+  return invoke<SidecarStatus>('p2p_sidecar_status');
+}
+
+/** How the sidecar is launched; read from config.json on every start. */
+export interface SidecarConfig {
+  /** Fixed TCP listen port; null for the saved or a random one. */
+  listenPort: number | null;
+  /** Multiaddrs dialled after every start. */
+  bootstrapPeers: string[];
+  /** Absolute path for the sidecar's identity and state; null for the profile directory. */
+  dataDirOverride: string | null;
+  /** Extra environment variables; NODE_PATH and names starting with CONCORD_ are rejected. */
+  extraEnv: Record<string, string>;
+}
+
+export async function getSidecarConfig(): Promise<SidecarConfig> {
+  return invoke<SidecarConfig>('get_sidecar_config');
+}
+
+/**
+ * Validate and save the launch options; they apply from the next start, or
+ * now with `restart`. Resolves with `restart-required` while the running
+ * sidecar still uses the old options. Rejects with `invalid-argument` or
+ * `invalid-address` without writing anything.
+ */
+export async function setSidecarConfig(
+  config: SidecarConfig,
+  restart = false,
+): Promise<SettingEffect> {
+  const saved = await invoke<{ config: SidecarConfig; effect: SettingEffect }>(
+    'set_sidecar_config',
+    { config, restart },
+  );
+  return saved.effect;
+}
+
+/** Read the sidecar's stderr log: the last `tailLines` lines, or all of it. */
+export async function getSidecarLog(tailLines?: number): Promise<string> {
   try {
-    return await invoke<string>('get_sidecar_log');
+    return await invoke<string>('get_sidecar_log', { tailLines: tailLines ?? null });
   } catch {
     return '';
   }
 }
 
+export interface ConnectivityCheckStep {
+  id: string;
+  label: string;
+  status: 'pass' | 'warn' | 'fail' | 'skipped';
+  durationMs: number;
+  detail: string;
+  data: unknown;
+}
+
+export interface ConnectivityReport {
+  startedAt: number;
+  durationMs: number;
+  steps: ConnectivityCheckStep[];
+  conclusions: string[];
+}
+
+/**
+ * Run the NAT/relay connectivity check. Progress arrives as `operation-progress`
+ * events of kind `connectivity-check`, each step's result in `detail`.
+ */
+export async function runConnectivityCheck(): Promise<ConnectivityReport> {
+  return invoke<ConnectivityReport>('run_connectivity_check');
+}
+
+export interface AppInfo {
+  version: string;
+  profile: string;
+  dataDir: string;
+  incognito: boolean;
+  ephemeral: boolean;
+  /** When true the backend keeps the window title in sync; don't set it from JS. */
+  bridgeManagesTitle: boolean;
+  /** The previous run crashed or was killed instead of shutting down cleanly. */
+  uncleanPreviousShutdown: boolean;
+  /** Why safe mode engaged; null when running normally. */
+  safeMode: string | null;
+  /** Redelivered or echoed messages dropped since launch. */
+  duplicatesDropped: number;
+  /** Why persistence is paused (disk full or read-only); null when healthy. */
+  storageDegraded: string | null;
+  /** Process exit codes by name, e.g. `{ normal: 0, "setup-failed": 11 }`. */
+  exitCodes: Record<string, number>;
+}
+
+export interface ProfileInfo {
+  name: string;
+  active: boolean;
+}
+
+/** App version, active profile, and data directory. */
+export async function getAppInfo(): Promise<AppInfo> {
+  return invoke<AppInfo>('get_app_info');
+}
+
+export async function listProfiles(): Promise<ProfileInfo[]> {
+  return invoke<ProfileInfo[]>('list_profiles');
+}
+
+export async function createProfile(name: string): Promise<ProfileInfo> {
+  return invoke<ProfileInfo>('create_profile', { name });
+}
+
+/**
+ * Delete a profile and all of its data once the user approves the prompt;
+ * rejects with `approval-denied` otherwise. Refused for the active profile.
+ */
+export async function deleteProfile(name: string): Promise<void> {
+  await invoke('delete_profile', { name });
+}
+
+/** Switch profiles. The sidecar restarts; watch for `profile-switched`. */
+export async function switchProfile(name: string): Promise<void> {
+  await invoke('switch_profile', { name });
+}
+
+/** Start a guest session with a throwaway identity; nothing is persisted. */
+export async function startEphemeralSession(): Promise<void> {
+  await invoke('start_ephemeral_session');
+}
+
+/**
+ * End the guest session, wipe its data, and return to the active profile.
+ * Asks for approval first; rejects with `approval-denied` without it.
+ */
+export async function endEphemeralSession(): Promise<void> {
+  await invoke('end_ephemeral_session');
+}
+
+export interface RegeneratedIdentity {
+  oldPeerId: string;
+  newPeerId: string;
+  backup: string;
+  notified: number;
+}
+
+export interface IdentityBackup {
+  name: string;
+  createdAt: number;
+  size: number;
+}
+
+/** Replace the identity. Resolves once the user approves the prompt. */
+export async function regenerateIdentity(notifyContacts?: boolean): Promise<RegeneratedIdentity> {
+  return invoke<RegeneratedIdentity>('regenerate_identity', {
+    notifyContacts: notifyContacts ?? null,
+  });
+}
+
+export async function listIdentityBackups(): Promise<IdentityBackup[]> {
+  return invoke<IdentityBackup[]>('list_identity_backups');
+}
+
+/** Restore a previous identity. Resolves once the user approves the prompt. */
+export async function restoreIdentityBackup(name: string): Promise<void> {
+  await invoke('restore_identity_backup', { name });
+}
+
+/** Leave safe mode: clears the crash counter and starts the sidecar. */
+export async function leaveSafeMode(): Promise<void> {
+  await invoke('leave_safe_mode');
+}
+
+/** Bitmask values for `restoreBackup` components. */
+export const BACKUP_COMPONENT_IDENTITY = 1;
+export const BACKUP_COMPONENT_SETTINGS = 2;
+export const BACKUP_COMPONENT_HISTORY = 4;
+/** Launch options, mutes, message requests, send policies, contact overrides, approvals. */
+export const BACKUP_COMPONENT_STATE = 8;
+
+export interface BackupSummary {
+  path: string;
+  files: number;
+  bytes: number;
+  components: number;
+}
+
+export interface RestoreSummary {
+  profile: string;
+  createdAt: number;
+  files: number;
+  components: number;
+}
+
+/** Write a passphrase-encrypted backup. Progress arrives as `operation-progress` events of kind `backup`. */
+export async function createBackup(
+  path: string,
+  passphrase: string,
+  includeAttachments: boolean,
+): Promise<BackupSummary> {
+  return invoke<BackupSummary>('create_backup', { path, passphrase, includeAttachments });
+}
+
+/**
+ * Restore a backup once the user approves the prompt; the sidecar restarts.
+ * Omit `components` to restore everything.
+ */
+export async function restoreBackup(
+  path: string,
+  passphrase: string,
+  components?: number,
+): Promise<RestoreSummary> {
+  return invoke<RestoreSummary>('restore_backup', {
+    path,
+    passphrase,
+    components: components ?? null,
+  });
+}
+
+/** Set (or clear with null) the passphrase used by scheduled auto-backups. */
+export async function setAutoBackupPassphrase(passphrase: string | null): Promise<void> {
+  await invoke('set_auto_backup_passphrase', { passphrase });
+}
+
+export interface CommandArgSpec {
+  name: string;
+  kind: 'string' | 'boolean' | 'integer' | 'json';
+  required: boolean;
+  description: string;
+}
+
+export interface CommandSpec {
+  name: string;
+  description: string;
+  args: CommandArgSpec[];
+  /** Asks for approval (an `approval-requested` event) before it runs. */
+  destructive: boolean;
+}
+
+/** Slash commands the backend can run; use for autocomplete and validation. */
+export async function getCommandRegistry(): Promise<CommandSpec[]> {
+  return invoke<CommandSpec[]>('get_command_registry');
+}
+
+/** Run a registry command. Resolves with that command's normal result. */
+export async function executeRegisteredCommand(
+  name: string,
+  args: Record<string, unknown> = {},
+): Promise<unknown> {
+  return invoke('execute_registered_command', { name, argsJson: JSON.stringify(args) });
+}
+
+/** All settings of the active profile (shape mirrors the Rust `Settings` struct). */
+export async function getSettings(): Promise<Record<string, unknown>> {
+  return invoke<Record<string, unknown>>('get_settings');
+}
+
+/** Whether a settings change is already in effect or needs a restart. */
+export type SettingEffect = 'immediate' | 'restart-required';
+
+/** Set one setting by dotted key, e.g. `attention.flashCount`. */
+export async function setSetting(key: string, value: unknown): Promise<SettingEffect> {
+  const applied = await invoke<{ effect: SettingEffect }>('set_setting', { key, value });
+  return applied.effect;
+}
+
+/** Restore all settings to their defaults once approved. Emits `settings-reset`. */
+export async function resetSettings(): Promise<Record<string, unknown>> {
+  return invoke<Record<string, unknown>>('reset_settings');
+}
+
+export type MergeStrategy = 'replace' | 'merge-prefer-file' | 'merge-prefer-local';
+
+export interface SettingChange {
+  /** Dotted key, e.g. `attention.flashCount`. */
+  key: string;
+  from: unknown;
+  to: unknown;
+}
+
+export interface SettingsImported {
+  changes: SettingChange[];
+  warnings: string[];
+  /** False for a dry run. */
+  applied: boolean;
+}
+
+/**
+ * Write settings to a portable file: no secrets, paths relative to the
+ * home folder. Returns the keys that were left out or rewritten.
+ */
+export async function exportSettings(path: string): Promise<{ adjusted: string[] }> {
+  return invoke<{ adjusted: string[] }>('export_settings', { path });
+}
+
+/** Import an exported settings file; `dryRun` only lists the changes. Emits `settings-imported`. */
+export async function importSettings(
+  path: string,
+  strategy: MergeStrategy,
+  dryRun = false,
+): Promise<SettingsImported> {
+  return invoke<SettingsImported>('import_settings', { path, strategy, dryRun });
+}
+
+// ── Mutes ────────────────────────────────────────────────────────
+
+export interface MutedConversation {
+  /** Channel id; `dm:<peer>` for direct messages. */
+  conversationId: string;
+  /** Unix ms when the mute lifts; null until unmuted. */
+  until: number | null;
+}
+
+/** Mute a conversation for `durationSecs`, or until unmuted if omitted. */
+export async function muteConversation(
+  id: string,
+  durationSecs?: number,
+): Promise<MutedConversation> {
+  return invoke<MutedConversation>('mute_conversation', { id, durationSecs: durationSecs ?? null });
+}
+
+export async function unmuteConversation(id: string): Promise<void> {
+  await invoke('unmute_conversation', { id });
+}
+
+export async function listMuted(): Promise<MutedConversation[]> {
+  return invoke<MutedConversation[]>('list_muted');
+}
+
+// ── Message requests ─────────────────────────────────────────────
+
+export type MessageRequestResponse = 'accept' | 'decline' | 'block';
+
+export interface MessageRequest {
+  peerId: string;
+  firstAt: number;
+  /** Held `message` events, oldest first. */
+  messages: P2PMessageEvent[];
+  /** Messages discarded because the per-peer cap was reached. */
+  dropped: number;
+}
+
+export async function listMessageRequests(): Promise<MessageRequest[]> {
+  return invoke<MessageRequest[]>('list_message_requests');
+}
+
+/** Accepting delivers the held messages as ordinary `message` events. */
+export async function respondMessageRequest(
+  peerId: string,
+  response: MessageRequestResponse,
+): Promise<void> {
+  await invoke('respond_message_request', { peerId, response });
+}
+
+// ── Send policies ────────────────────────────────────────────────
+
+/**
+ * Rules checked before `p2pSend` and `sendFile`; a violation rejects with
+ * `policy-violation` and `details.rule`. Unknown rule types are kept but
+ * not enforced.
+ */
+export type SendRule =
+  | { type: 'max-length'; maxChars: number }
+  | { type: 'no-mass-mentions' }
+  | { type: 'max-file-size'; maxBytes: number }
+  | { type: string; [param: string]: unknown };
+
+export interface ChannelPolicy {
+  rules: SendRule[];
+}
+
+/** Replace a channel's policy; returns rule types this version ignores. */
+export async function setChannelPolicy(
+  channelId: string,
+  policy: ChannelPolicy,
+): Promise<{ ignored: string[] }> {
+  return invoke<{ ignored: string[] }>('set_channel_policy', { channelId, policy });
+}
+
+export async function getChannelPolicy(channelId: string): Promise<ChannelPolicy> {
+  return invoke<ChannelPolicy>('get_channel_policy', { channelId });
+}
+
+// ── Operations ───────────────────────────────────────────────────
+
+/** This session's operations, finished ones included. */
+export async function listOperations(): Promise<Operation[]> {
+  return invoke<Operation[]>('list_operations');
+}
+
+/** Ask an operation to stop; it ends as `cancelled` at its next checkpoint. */
+export async function cancelOperation(id: string): Promise<void> {
+  await invoke('cancel_operation', { id });
+}
+
+// ── Maintenance ──────────────────────────────────────────────────
+
+export interface MaintenanceJobStatus {
+  name: string;
+  pending: boolean;
+  /** When the job last finished a run, successfully or not. */
+  lastRunAt: number | null;
+  lastError: string | null;
+  /** The last pass yielded to user activity before the job finished. */
+  interrupted: boolean;
+  /** After a failure, scheduled passes skip the job until then. */
+  retryAt: number | null;
+}
+
+export interface MaintenanceStatus {
+  running: boolean;
+  /** Idle, `maintenance.window` and `maintenance.requireAc` all hold now. */
+  conditionsMet: boolean;
+  jobs: MaintenanceJobStatus[];
+}
+
+export async function getMaintenanceStatus(): Promise<MaintenanceStatus> {
+  return invoke<MaintenanceStatus>('get_maintenance_status');
+}
+
+/**
+ * Run pending background jobs now, regardless of idle, window and power.
+ * Rejects with `not-ready` if a pass is already running.
+ */
+export async function runMaintenanceNow(): Promise<MaintenanceStatus> {
+  return invoke<MaintenanceStatus>('run_maintenance_now');
+}
+
+// ── Presentation hints ───────────────────────────────────────────
+
+export interface PresentationHint {
+  id: string;
+  /** 0–359 */
+  hue: number;
+  initials: string;
+  overridden: boolean;
+  /** Hue derivation version; hues only change when it does. */
+  version: number;
+}
+
+export interface PresentationOverride {
+  hue?: number | null;
+  initials?: string | null;
+}
+
+/** Stable colour and initials for peer and channel ids. */
+export async function getPresentationHints(ids: string[]): Promise<PresentationHint[]> {
+  return invoke<PresentationHint[]>('get_presentation_hints', { ids });
+}
+
+/** Set, or clear with null, the override for `id`. */
+export async function setPresentationOverride(
+  id: string,
+  hint: PresentationOverride | null,
+): Promise<PresentationHint> {
+  return invoke<PresentationHint>('set_presentation_override', { id, hint });
+}
+
+// ── Focus sessions ───────────────────────────────────────────────
+
+export interface FocusStatus {
+  startedAt: number;
+  endsAt: number;
+}
+
+/** Start (or extend) a focus session; attention cues pause until it ends. */
+export async function startFocusSession(durationSecs: number): Promise<FocusStatus> {
+  return invoke<FocusStatus>('start_focus_session', { durationSecs });
+}
+
+/** End the session now; the digest is also emitted as `focus-digest`. */
+export async function endFocusSession(): Promise<Omit<P2PFocusDigestEvent, 'type'>> {
+  return invoke<Omit<P2PFocusDigestEvent, 'type'>>('end_focus_session');
+}
+
+export async function focusStatus(): Promise<FocusStatus | null> {
+  return invoke<FocusStatus | null>('focus_status');
+}
+
+// ── Approvals ────────────────────────────────────────────────────
+
+/** Prompts still waiting for an answer, e.g. after the UI mounts late. */
+export async function pendingApprovals(): Promise<ApprovalRequest[]> {
+  return invoke<ApprovalRequest[]>('pending_approvals');
+}
+
+/** Answer a prompt. `remember` only applies to rememberable requests. */
+export async function resolveApproval(
+  nonce: string,
+  approved: boolean,
+  remember?: boolean,
+): Promise<void> {
+  await invoke('resolve_approval', { nonce, approved, remember: remember ?? null });
+}
+
+export async function clearRememberedApprovals(): Promise<void> {
+  await invoke('clear_remembered_approvals');
+}
+
+// ── Protocol console (developer mode) ────────────────────────────
+
+/** Stream raw protocol traffic as `protocol-trace` events. */
+export async function consoleSubscribe(): Promise<void> {
+  await invoke('console_subscribe');
+}
+
+export async function consoleUnsubscribe(): Promise<void> {
+  await invoke('console_unsubscribe');
+}
+
+/**
+ * DANGER: writes `jsonLine` to the sidecar unchecked. A bad line can break
+ * the session or leak data; for protocol debugging only.
+ */
+export async function consoleSendRaw(jsonLine: string): Promise<void> {
+  await invoke('console_send_raw', { jsonLine });
+}
+
 // ── Event listener ───────────────────────────────────────────────
 
 /**